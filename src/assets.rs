@@ -1,4 +1,10 @@
-use crate::animations::SpriteAnimation;
+use crate::{
+    animations::SpriteAnimation,
+    creatures::SpeciesDatabase,
+    dialogue::DialogueDatabase,
+    items::ItemDatabase,
+    maps::{MapDefinition, SpriteGrid, SpriteSheetDefinition, TiledFormat},
+};
 use amethyst::{
     animation::{
         Animation, InterpolationFunction, Sampler, SpriteRenderChannel, SpriteRenderPrimitive,
@@ -6,10 +12,11 @@ use amethyst::{
     assets::{AssetStorage, Handle, Loader, Progress, RonFormat},
     ecs::prelude::World,
     renderer::{
-        PngFormat, SpriteRender, SpriteSheet, SpriteSheetFormat, SpriteSheetHandle, Texture,
-        TextureHandle, TextureMetadata,
+        PngFormat, Sprite, SpriteRender, SpriteSheet, SpriteSheetFormat, SpriteSheetHandle,
+        Texture, TextureHandle, TextureMetadata,
     },
 };
+use std::path::Path;
 
 /// Load a sprite sheet from the asset directory.
 pub fn load_sprite_sheet<N: Into<String>>(
@@ -55,6 +62,122 @@ where
     )
 }
 
+/// Load the sprite sheet described by a definition from the asset directory.
+pub fn load_sprite_sheet_definition(
+    definition: &SpriteSheetDefinition,
+    world: &World,
+) -> SpriteSheetHandle {
+    match definition {
+        SpriteSheetDefinition::File {
+            texture,
+            definition,
+        } => {
+            let texture = load_texture(texture.as_str(), world);
+            load_sprite_sheet(definition.as_str(), texture, world)
+        }
+        SpriteSheetDefinition::Grid { texture, grid } => {
+            let texture = load_texture(texture.as_str(), world);
+            build_grid_sprite_sheet(grid, texture, world)
+        }
+    }
+}
+
+/// Build a sprite sheet by cutting a texture into a grid of sprites.
+pub fn build_grid_sprite_sheet(
+    grid: &SpriteGrid,
+    texture: TextureHandle,
+    world: &World,
+) -> SpriteSheetHandle {
+    let sprites = (0..grid.count)
+        .map(|index| {
+            let column = index % grid.columns;
+            let row = index / grid.columns;
+            Sprite::from_pixel_values(
+                grid.image_width,
+                grid.image_height,
+                grid.sprite_width,
+                grid.sprite_height,
+                grid.margin + column * (grid.sprite_width + grid.spacing),
+                grid.margin + row * (grid.sprite_height + grid.spacing),
+                [0.0, 0.0],
+            )
+        })
+        .collect();
+
+    let loader = world.read_resource::<Loader>();
+    loader.load_from_data(
+        SpriteSheet { texture, sprites },
+        (),
+        &world.read_resource::<AssetStorage<SpriteSheet>>(),
+    )
+}
+
+/// Load a map definition from the asset directory.
+/// Maps ending with `.json` are expected to be Tiled exports, other maps are read as RON.
+pub fn load_map<P: Progress>(path: &str, progress: P, world: &World) -> Handle<MapDefinition> {
+    let loader = world.read_resource::<Loader>();
+    let storage = world.read_resource::<AssetStorage<MapDefinition>>();
+
+    if path.ends_with(".json") {
+        let directory = Path::new(path)
+            .parent()
+            .and_then(|p| p.to_str())
+            .unwrap_or("")
+            .to_owned();
+        loader.load(path, TiledFormat, directory, progress, &storage)
+    } else {
+        loader.load(path, RonFormat, (), progress, &storage)
+    }
+}
+
+/// Load the species database from the asset directory.
+pub fn load_species_database<N, P>(path: N, progress: P, world: &World) -> Handle<SpeciesDatabase>
+where
+    N: Into<String>,
+    P: Progress,
+{
+    let loader = world.read_resource::<Loader>();
+    loader.load(
+        path,
+        RonFormat,
+        (),
+        progress,
+        &world.read_resource::<AssetStorage<SpeciesDatabase>>(),
+    )
+}
+
+/// Load the item database from the asset directory.
+pub fn load_item_database<N, P>(path: N, progress: P, world: &World) -> Handle<ItemDatabase>
+where
+    N: Into<String>,
+    P: Progress,
+{
+    let loader = world.read_resource::<Loader>();
+    loader.load(
+        path,
+        RonFormat,
+        (),
+        progress,
+        &world.read_resource::<AssetStorage<ItemDatabase>>(),
+    )
+}
+
+/// Load the dialogue database from the asset directory.
+pub fn load_dialogue_database<N, P>(path: N, progress: P, world: &World) -> Handle<DialogueDatabase>
+where
+    N: Into<String>,
+    P: Progress,
+{
+    let loader = world.read_resource::<Loader>();
+    loader.load(
+        path,
+        RonFormat,
+        (),
+        progress,
+        &world.read_resource::<AssetStorage<DialogueDatabase>>(),
+    )
+}
+
 /// Load a sprite render animation from a loaded sprite animation.
 pub fn load_sprite_render_animation(
     world: &World,
@@ -0,0 +1,228 @@
+use super::{Battle, BattleOutcome};
+use crate::{
+    creatures::{SpeciesDatabase, Status},
+    items::{describe_outcome, EffectOutcome, ItemDatabase},
+};
+
+/// One side of a battle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Player,
+    Wild,
+}
+
+impl Side {
+    pub fn opponent(self) -> Side {
+        match self {
+            Side::Player => Side::Wild,
+            Side::Wild => Side::Player,
+        }
+    }
+}
+
+/// Something which happened during a battle. Creatures are referred to by their side
+/// and their index in the side's team, so the log can be replayed and displayed later.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BattleEvent {
+    /// A creature was sent to fight.
+    SentOut {
+        side: Side,
+        creature: usize,
+    },
+    MoveUsed {
+        side: Side,
+        creature: usize,
+        move_id: String,
+    },
+    /// The creature had no move with PP left and struggled.
+    NoMoveLeft {
+        side: Side,
+        creature: usize,
+    },
+    Missed {
+        side: Side,
+        creature: usize,
+    },
+    Damaged {
+        side: Side,
+        creature: usize,
+        amount: u32,
+        critical: bool,
+        /// Type effectiveness multiplier, 1 for neutral.
+        effectiveness: f32,
+    },
+    StatusInflicted {
+        side: Side,
+        creature: usize,
+        status: Status,
+    },
+    /// Damage dealt by poison or burn at the end of the turn.
+    StatusDamaged {
+        side: Side,
+        creature: usize,
+        status: Status,
+        amount: u32,
+    },
+    /// The creature could not act because of its status.
+    Immobilized {
+        side: Side,
+        creature: usize,
+        status: Status,
+    },
+    /// The creature woke up or thawed.
+    StatusCured {
+        side: Side,
+        creature: usize,
+    },
+    Fainted {
+        side: Side,
+        creature: usize,
+    },
+    FleeFailed,
+    /// The player used an item on a creature of the party.
+    ItemUsed {
+        item: String,
+        creature: usize,
+    },
+    /// An item changed something on a creature of the party.
+    ItemApplied {
+        creature: usize,
+        outcome: EffectOutcome,
+    },
+    CaptureItemThrown {
+        item: String,
+    },
+    /// The capture item shook, once per successful capture check.
+    Shook,
+    /// The wild creature escaped from the capture item.
+    BrokeFree,
+    Ended(BattleOutcome),
+}
+
+impl BattleEvent {
+    /// Describe the event as a line of text.
+    pub fn describe(
+        &self,
+        battle: &Battle,
+        database: &SpeciesDatabase,
+        items: &ItemDatabase,
+    ) -> String {
+        let name = |side: Side, creature: usize| {
+            let creature = battle.creature(side, creature);
+            let name = creature.name(database).to_owned();
+            match side {
+                Side::Player => name,
+                Side::Wild => format!("Wild {}", name),
+            }
+        };
+
+        match self {
+            BattleEvent::SentOut { side, creature } => match side {
+                Side::Player => format!("Go, {}!", name(*side, *creature)),
+                Side::Wild => format!("{} appeared!", name(*side, *creature)),
+            },
+            BattleEvent::MoveUsed {
+                side,
+                creature,
+                move_id,
+            } => format!(
+                "{} used {}!",
+                name(*side, *creature),
+                database.moves[move_id].name
+            ),
+            BattleEvent::NoMoveLeft { side, creature } => {
+                format!("{} has no move left and struggles!", name(*side, *creature))
+            }
+            BattleEvent::Missed { side, creature } => {
+                format!("{}'s attack missed!", name(*side, *creature))
+            }
+            BattleEvent::Damaged {
+                side,
+                creature,
+                amount,
+                critical,
+                effectiveness,
+            } => {
+                let mut line = format!("{} lost {} HP.", name(*side, *creature), amount);
+                if *critical {
+                    line.push_str(" A critical hit!");
+                }
+                if *effectiveness > 1.0 {
+                    line.push_str(" It's super effective!");
+                } else if *effectiveness == 0.0 {
+                    line.push_str(" It had no effect...");
+                } else if *effectiveness < 1.0 {
+                    line.push_str(" It's not very effective...");
+                }
+                line
+            }
+            BattleEvent::StatusInflicted {
+                side,
+                creature,
+                status,
+            } => format!("{} is {}!", name(*side, *creature), status_name(*status)),
+            BattleEvent::StatusDamaged {
+                side,
+                creature,
+                status,
+                amount,
+            } => format!(
+                "{} is hurt by its {} and lost {} HP.",
+                name(*side, *creature),
+                match status {
+                    Status::Burned => "burn",
+                    _ => "poison",
+                },
+                amount
+            ),
+            BattleEvent::Immobilized {
+                side,
+                creature,
+                status,
+            } => format!(
+                "{} is {} and can't move!",
+                name(*side, *creature),
+                status_name(*status)
+            ),
+            BattleEvent::StatusCured { side, creature } => {
+                format!("{} is back in shape!", name(*side, *creature))
+            }
+            BattleEvent::Fainted { side, creature } => {
+                format!("{} fainted!", name(*side, *creature))
+            }
+            BattleEvent::FleeFailed => "Couldn't get away!".to_owned(),
+            BattleEvent::ItemUsed { item, creature } => format!(
+                "You used a {} on {}.",
+                items.items[item].name,
+                name(Side::Player, *creature)
+            ),
+            BattleEvent::ItemApplied { creature, outcome } => {
+                describe_outcome(outcome, &name(Side::Player, *creature), database)
+            }
+            BattleEvent::CaptureItemThrown { item } => {
+                format!("You threw a {}!", items.items[item].name)
+            }
+            BattleEvent::Shook => "...".to_owned(),
+            BattleEvent::BrokeFree => "Oh no! The creature broke free!".to_owned(),
+            BattleEvent::Ended(outcome) => match outcome {
+                BattleOutcome::Won => "You won!".to_owned(),
+                BattleOutcome::Lost => "You have no creature left to fight...".to_owned(),
+                BattleOutcome::Fled => "Got away safely!".to_owned(),
+                BattleOutcome::Captured => format!(
+                    "Gotcha! {} was caught!",
+                    battle.active(Side::Wild).name(database)
+                ),
+            },
+        }
+    }
+}
+
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Poisoned => "poisoned",
+        Status::Burned => "burned",
+        Status::Paralyzed => "paralyzed",
+        Status::Asleep { .. } => "asleep",
+        Status::Frozen => "frozen",
+    }
+}
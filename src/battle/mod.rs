@@ -0,0 +1,1230 @@
+//! Battle rules, independent of the game engine.
+//!
+//! A `Battle` is driven by the actions chosen for the player, the wild side picks its moves
+//! by itself. Every random roll comes from the battle's own `Rng`, so a battle is entirely
+//! determined by its seed and the player's actions, and can be replayed.
+
+mod events;
+mod types;
+
+pub use self::{
+    events::{BattleEvent, Side},
+    types::effectiveness,
+};
+
+use crate::{
+    creatures::{
+        Creature, CreatureType, MoveCategory, MoveEffect, SpeciesDatabase, Status, StatusEffect,
+    },
+    items::{ItemDatabase, ItemUseError, UseContext},
+    rng::Rng,
+};
+use std::{error::Error, fmt};
+
+const CRITICAL_CHANCE: f32 = 1.0 / 16.0;
+const CRITICAL_MULTIPLIER: f32 = 1.5;
+/// Multiplier when the move has one of the attacker's types.
+const SAME_TYPE_BONUS: f32 = 1.5;
+/// Power of the move used when no move has PP left.
+const STRUGGLE_POWER: u32 = 50;
+const PARALYSIS_SKIP_CHANCE: f32 = 0.25;
+const THAW_CHANCE: f32 = 0.2;
+const MAX_SLEEP_TURNS: u32 = 3;
+/// Number of successful checks needed to capture a creature.
+/// The capture item shakes once per successful check, up to 3 times.
+const CAPTURE_CHECKS: u32 = 4;
+
+/// A fight between the player's party and wild creatures.
+pub struct Battle {
+    player: Team,
+    wild: Team,
+    rng: Rng,
+    outcome: Option<BattleOutcome>,
+    flee_attempts: u32,
+    /// The player's creature fainted and must be replaced before the next turn.
+    switch_required: bool,
+    log: Vec<BattleEvent>,
+}
+
+struct Team {
+    creatures: Vec<Creature>,
+    /// Index of the creature currently fighting.
+    active: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BattleOutcome {
+    /// Every wild creature fainted.
+    Won,
+    /// Every creature of the party fainted.
+    Lost,
+    /// The player ran away.
+    Fled,
+    /// The wild creature was captured.
+    Captured,
+}
+
+/// What the player does during a turn.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BattleAction {
+    /// Use the move at this index in the active creature's moves.
+    /// Any move struggles when no move has PP left.
+    Move(usize),
+    /// Send the creature at this index in the party.
+    Switch(usize),
+    /// Use an item by id: capture items are thrown at the wild creature, other items are used
+    /// on the creature at index `target` in the party. The caller is in charge of consuming it.
+    UseItem {
+        item: String,
+        target: usize,
+    },
+    Flee,
+}
+
+/// An action which can not be played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActionError {
+    BattleOver,
+    /// A fainted creature must be replaced first.
+    SwitchRequired,
+    NoSuchMove,
+    NoPpLeft,
+    /// The creature does not exist, is fainted or is already fighting.
+    CannotSwitch,
+    /// There is no creature to use the item on.
+    InvalidTarget,
+    Item(ItemUseError),
+}
+
+/// A battle which can not be started.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BattleError {
+    /// The side has no creature to fight with.
+    EmptyTeam(Side),
+}
+
+impl Battle {
+    /// Start a battle. The first creature of the party able to fight goes first.
+    /// Fail if a side has no creature.
+    pub fn new(party: Vec<Creature>, wild: Vec<Creature>, seed: u64) -> Result<Self, BattleError> {
+        if party.is_empty() {
+            return Err(BattleError::EmptyTeam(Side::Player));
+        }
+        if wild.is_empty() {
+            return Err(BattleError::EmptyTeam(Side::Wild));
+        }
+        let mut battle = Battle {
+            player: Team::new(party),
+            wild: Team::new(wild),
+            rng: Rng::new(seed),
+            outcome: None,
+            flee_attempts: 0,
+            switch_required: false,
+            log: Vec::new(),
+        };
+
+        for &side in &[Side::Wild, Side::Player] {
+            let active = battle.active_index(side);
+            if battle.active(side).is_fainted() {
+                let outcome = match side {
+                    Side::Player => BattleOutcome::Lost,
+                    Side::Wild => BattleOutcome::Won,
+                };
+                battle.end(outcome);
+                break;
+            }
+            battle.log.push(BattleEvent::SentOut {
+                side,
+                creature: active,
+            });
+        }
+
+        Ok(battle)
+    }
+
+    pub fn creature(&self, side: Side, index: usize) -> &Creature {
+        &self.team(side).creatures[index]
+    }
+
+    pub fn creatures(&self, side: Side) -> &[Creature] {
+        &self.team(side).creatures
+    }
+
+    /// Index of the creature fighting for a side.
+    pub fn active_index(&self, side: Side) -> usize {
+        self.team(side).active
+    }
+
+    pub fn active(&self, side: Side) -> &Creature {
+        let team = self.team(side);
+        &team.creatures[team.active]
+    }
+
+    pub fn outcome(&self) -> Option<BattleOutcome> {
+        self.outcome
+    }
+
+    pub fn switch_required(&self) -> bool {
+        self.switch_required
+    }
+
+    /// Every event since the start of the battle.
+    pub fn log(&self) -> &[BattleEvent] {
+        &self.log
+    }
+
+    /// The wild creature captured by the player, if any.
+    pub fn captured(&self) -> Option<&Creature> {
+        if self.outcome == Some(BattleOutcome::Captured) {
+            Some(self.active(Side::Wild))
+        } else {
+            None
+        }
+    }
+
+    /// Give the party back, with the damage taken during the battle.
+    pub fn into_party(self) -> Vec<Creature> {
+        self.player.creatures
+    }
+
+    /// Play the player's action and the wild creature's answer.
+    /// Return the events of the turn.
+    pub fn play_turn(
+        &mut self,
+        action: BattleAction,
+        database: &SpeciesDatabase,
+        items: &ItemDatabase,
+    ) -> Result<Vec<BattleEvent>, ActionError> {
+        self.check_action(&action, database, items)?;
+        let start = self.log.len();
+
+        // Replacing a fainted creature does not cost a turn.
+        if self.switch_required {
+            if let BattleAction::Switch(index) = action {
+                self.switch(index);
+                self.switch_required = false;
+            }
+            return Ok(self.log[start..].to_vec());
+        }
+
+        let player_move = match action {
+            BattleAction::Move(index) => Some(self.usable_move(Side::Player, index)),
+            BattleAction::Switch(index) => {
+                self.switch(index);
+                None
+            }
+            BattleAction::UseItem { item, target } => {
+                let definition = &items.items[&item];
+                match definition.capture_bonus() {
+                    Some(bonus) => {
+                        if self.try_capture(&item, bonus, database) {
+                            return Ok(self.log[start..].to_vec());
+                        }
+                    }
+                    None => self.use_item(&item, target, database, items),
+                }
+                None
+            }
+            BattleAction::Flee => {
+                if self.try_flee(database) {
+                    return Ok(self.log[start..].to_vec());
+                }
+                None
+            }
+        };
+        let wild = self.active_index(Side::Wild);
+        let wild_move = self.choose_wild_move();
+
+        match player_move {
+            Some(player_move) => {
+                let player_speed = self.speed(Side::Player, database);
+                let wild_speed = self.speed(Side::Wild, database);
+                let player_first = player_speed > wild_speed
+                    || (player_speed == wild_speed && self.rng.chance(0.5));
+                if player_first {
+                    self.act(Side::Player, player_move, database);
+                    self.act_wild(wild, wild_move, database);
+                } else {
+                    self.act_wild(wild, wild_move, database);
+                    self.act(Side::Player, player_move, database);
+                }
+            }
+            None => self.act_wild(wild, wild_move, database),
+        }
+
+        self.end_turn(database);
+        Ok(self.log[start..].to_vec())
+    }
+
+    fn check_action(
+        &self,
+        action: &BattleAction,
+        database: &SpeciesDatabase,
+        items: &ItemDatabase,
+    ) -> Result<(), ActionError> {
+        if self.outcome.is_some() {
+            return Err(ActionError::BattleOver);
+        }
+
+        match action {
+            _ if self.switch_required && !is_switch(action) => Err(ActionError::SwitchRequired),
+            BattleAction::Move(index) => {
+                let index = *index;
+                let creature = self.active(Side::Player);
+                if creature.moves.iter().all(|m| m.pp == 0) {
+                    // Struggle
+                    Ok(())
+                } else {
+                    match creature.moves.get(index) {
+                        None => Err(ActionError::NoSuchMove),
+                        Some(learned) if learned.pp == 0 => Err(ActionError::NoPpLeft),
+                        Some(_) => Ok(()),
+                    }
+                }
+            }
+            BattleAction::Switch(index) => match self.player.creatures.get(*index) {
+                Some(creature) if !creature.is_fainted() && *index != self.player.active => Ok(()),
+                _ => Err(ActionError::CannotSwitch),
+            },
+            BattleAction::UseItem { item, target } => {
+                let definition = items
+                    .item(item)
+                    .ok_or(ActionError::Item(ItemUseError::UnknownItem))?;
+                if !definition.usable_in(UseContext::Battle) {
+                    return Err(ActionError::Item(ItemUseError::NotUsableHere));
+                }
+                if definition.capture_bonus().is_some() {
+                    return Ok(());
+                }
+                match self.player.creatures.get(*target) {
+                    Some(creature) if definition.affects(creature, database) => Ok(()),
+                    Some(_) => Err(ActionError::Item(ItemUseError::NoEffect)),
+                    None => Err(ActionError::InvalidTarget),
+                }
+            }
+            BattleAction::Flee => Ok(()),
+        }
+    }
+
+    fn team(&self, side: Side) -> &Team {
+        match side {
+            Side::Player => &self.player,
+            Side::Wild => &self.wild,
+        }
+    }
+
+    fn team_mut(&mut self, side: Side) -> &mut Team {
+        match side {
+            Side::Player => &mut self.player,
+            Side::Wild => &mut self.wild,
+        }
+    }
+
+    fn active_mut(&mut self, side: Side) -> &mut Creature {
+        let team = self.team_mut(side);
+        &mut team.creatures[team.active]
+    }
+
+    fn end(&mut self, outcome: BattleOutcome) {
+        self.outcome = Some(outcome);
+        self.log.push(BattleEvent::Ended(outcome));
+    }
+
+    fn switch(&mut self, index: usize) {
+        self.player.active = index;
+        self.log.push(BattleEvent::SentOut {
+            side: Side::Player,
+            creature: index,
+        });
+    }
+
+    /// Return the move to use: the requested one, or None to struggle when no move has PP.
+    fn usable_move(&self, side: Side, index: usize) -> Option<usize> {
+        let moves = &self.active(side).moves;
+        if moves.iter().all(|m| m.pp == 0) {
+            None
+        } else {
+            Some(index)
+        }
+    }
+
+    /// The wild creature picks a random move with PP left.
+    fn choose_wild_move(&mut self) -> Option<usize> {
+        let usable = self
+            .active(Side::Wild)
+            .moves
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.pp > 0)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        if usable.is_empty() {
+            None
+        } else {
+            Some(usable[self.rng.range(0, usable.len() as u32 - 1) as usize])
+        }
+    }
+
+    /// Speed of the active creature of a side, paralysis halves it.
+    fn speed(&self, side: Side, database: &SpeciesDatabase) -> u32 {
+        let creature = self.active(side);
+        let speed = creature.stats(database).speed;
+        if creature.status == Some(Status::Paralyzed) {
+            speed / 2
+        } else {
+            speed
+        }
+    }
+
+    /// Always succeeds if the player's creature is faster, otherwise the odds grow with each attempt.
+    fn try_flee(&mut self, database: &SpeciesDatabase) -> bool {
+        let player_speed = self.speed(Side::Player, database);
+        let wild_speed = self.speed(Side::Wild, database).max(1);
+        self.flee_attempts += 1;
+
+        let odds = player_speed * 128 / wild_speed + 30 * self.flee_attempts;
+        if player_speed >= wild_speed || odds > 255 || self.rng.range(0, 255) < odds {
+            self.end(BattleOutcome::Fled);
+            true
+        } else {
+            self.log.push(BattleEvent::FleeFailed);
+            false
+        }
+    }
+
+    /// Use an item on a creature of the party, its effects are checked beforehand.
+    fn use_item(
+        &mut self,
+        item: &str,
+        target: usize,
+        database: &SpeciesDatabase,
+        items: &ItemDatabase,
+    ) {
+        self.log.push(BattleEvent::ItemUsed {
+            item: item.to_owned(),
+            creature: target,
+        });
+        let outcomes = items.items[item].apply(&mut self.player.creatures[target], database);
+        for outcome in outcomes {
+            self.log.push(BattleEvent::ItemApplied {
+                creature: target,
+                outcome,
+            });
+        }
+    }
+
+    /// Throw a capture item. The odds grow with the species catch rate, the item bonus,
+    /// the damage taken by the creature and its status.
+    fn try_capture(&mut self, item: &str, bonus: f32, database: &SpeciesDatabase) -> bool {
+        let odds = {
+            let wild = self.active(Side::Wild);
+            let max_hp = wild.stats(database).hp.max(1) as f32;
+            let catch_rate = f32::from(database.species[&wild.species].catch_rate);
+            let status_bonus = match wild.status {
+                Some(Status::Asleep { .. }) | Some(Status::Frozen) => 2.0,
+                Some(_) => 1.5,
+                None => 1.0,
+            };
+            (3.0 * max_hp - 2.0 * wild.hp as f32) * catch_rate * bonus / (3.0 * max_hp)
+                * status_bonus
+        };
+        self.log.push(BattleEvent::CaptureItemThrown {
+            item: item.to_owned(),
+        });
+
+        // Each check succeeds with a probability of (odds / 255) ^ (1 / 4).
+        let checks = if odds >= 255.0 {
+            CAPTURE_CHECKS
+        } else {
+            let check_odds = (1_048_560.0 / (16_711_680.0 / odds.max(1.0)).sqrt().sqrt()) as u32;
+            let mut checks = 0;
+            while checks < CAPTURE_CHECKS && self.rng.range(0, 65535) < check_odds {
+                checks += 1;
+            }
+            checks
+        };
+        for _ in 0..checks.min(CAPTURE_CHECKS - 1) {
+            self.log.push(BattleEvent::Shook);
+        }
+
+        if checks == CAPTURE_CHECKS {
+            self.end(BattleOutcome::Captured);
+            true
+        } else {
+            self.log.push(BattleEvent::BrokeFree);
+            false
+        }
+    }
+
+    /// Check whether the active creature of a side can act despite its status.
+    fn can_act(&mut self, side: Side) -> bool {
+        let creature = self.active_index(side);
+        let status = self.active(side).status;
+        let (new_status, can_act) = match status {
+            Some(Status::Asleep { turns: 0 }) => (None, true),
+            Some(Status::Asleep { turns }) => (Some(Status::Asleep { turns: turns - 1 }), false),
+            Some(Status::Frozen) if self.rng.chance(THAW_CHANCE) => (None, true),
+            Some(Status::Frozen) => (status, false),
+            Some(Status::Paralyzed) if self.rng.chance(PARALYSIS_SKIP_CHANCE) => (status, false),
+            _ => (status, true),
+        };
+
+        if let (Some(status), false) = (status, can_act) {
+            self.log.push(BattleEvent::Immobilized {
+                side,
+                creature,
+                status,
+            });
+        } else if status.is_some() && new_status.is_none() {
+            self.log.push(BattleEvent::StatusCured { side, creature });
+        }
+        self.active_mut(side).status = new_status;
+        can_act
+    }
+
+    /// Make the wild creature use the move it chose, unless it fainted and was replaced
+    /// before its turn: the next one waits for the following turn.
+    fn act_wild(&mut self, creature: usize, move_index: Option<usize>, database: &SpeciesDatabase) {
+        if self.active_index(Side::Wild) == creature {
+            self.act(Side::Wild, move_index, database);
+        }
+    }
+
+    /// Make the active creature of a side use a move, or struggle, on the other side.
+    fn act(&mut self, side: Side, move_index: Option<usize>, database: &SpeciesDatabase) {
+        let creature = self.active_index(side);
+        if self.outcome.is_some() || self.active(side).is_fainted() || !self.can_act(side) {
+            return;
+        }
+
+        let (power, move_type, category, accuracy, effect) = match move_index {
+            Some(index) => {
+                let learned = &mut self.active_mut(side).moves[index];
+                learned.pp -= 1;
+                let move_id = learned.move_id.clone();
+                let definition = &database.moves[&move_id];
+                self.log.push(BattleEvent::MoveUsed {
+                    side,
+                    creature,
+                    move_id,
+                });
+                (
+                    definition.power,
+                    Some(definition.move_type),
+                    definition.category,
+                    definition.accuracy,
+                    definition.effect,
+                )
+            }
+            None => {
+                self.log.push(BattleEvent::NoMoveLeft { side, creature });
+                (STRUGGLE_POWER, None, MoveCategory::Physical, 100, None)
+            }
+        };
+
+        if self.rng.range(1, 100) > accuracy {
+            self.log.push(BattleEvent::Missed { side, creature });
+            return;
+        }
+
+        let target_side = side.opponent();
+        let target = self.active_index(target_side);
+        if category != MoveCategory::Status {
+            let (amount, critical, effectiveness) =
+                self.compute_damage(side, power, move_type, category, database);
+            let defender = self.active_mut(target_side);
+            defender.hp = defender.hp.saturating_sub(amount);
+            self.log.push(BattleEvent::Damaged {
+                side: target_side,
+                creature: target,
+                amount,
+                critical,
+                effectiveness,
+            });
+        }
+
+        if let Some(effect) = effect {
+            self.try_inflict(target_side, effect);
+        }
+        self.check_fainted(target_side);
+    }
+
+    /// Compute the damage of a move used by the active creature of `side` on the other side.
+    /// Return the damage, whether it is a critical hit and the type effectiveness.
+    fn compute_damage(
+        &mut self,
+        side: Side,
+        power: u32,
+        move_type: Option<CreatureType>,
+        category: MoveCategory,
+        database: &SpeciesDatabase,
+    ) -> (u32, bool, f32) {
+        let (base, same_type_bonus, effectiveness) = {
+            let attacker = self.active(side);
+            let defender = self.active(side.opponent());
+            let attacker_stats = attacker.stats(database);
+            let defender_stats = defender.stats(database);
+            let (mut attack, defense) = match category {
+                MoveCategory::Special => (
+                    attacker_stats.special_attack,
+                    defender_stats.special_defense,
+                ),
+                _ => (attacker_stats.attack, defender_stats.defense),
+            };
+            if category == MoveCategory::Physical && attacker.status == Some(Status::Burned) {
+                attack /= 2;
+            }
+
+            let base = (2 * attacker.level / 5 + 2) * power * attack / defense.max(1) / 50 + 2;
+            match move_type {
+                Some(move_type) => {
+                    let attacker_types = &database.species[&attacker.species].types;
+                    let defender_types = &database.species[&defender.species].types;
+                    let bonus = if attacker_types.contains(&move_type) {
+                        SAME_TYPE_BONUS
+                    } else {
+                        1.0
+                    };
+                    (base, bonus, effectiveness(move_type, defender_types))
+                }
+                None => (base, 1.0, 1.0),
+            }
+        };
+
+        let critical = self.rng.chance(CRITICAL_CHANCE);
+        let random = self.rng.range(85, 100) as f32 / 100.0;
+        let multiplier = same_type_bonus
+            * effectiveness
+            * random
+            * if critical { CRITICAL_MULTIPLIER } else { 1.0 };
+
+        let damage = if effectiveness > 0.0 {
+            ((base as f32 * multiplier) as u32).max(1)
+        } else {
+            0
+        };
+        (damage, critical, effectiveness)
+    }
+
+    /// Roll a move's status effect on the active creature of a side.
+    /// A creature only has one status at a time.
+    fn try_inflict(&mut self, side: Side, effect: MoveEffect) {
+        let target = self.active(side);
+        if target.is_fainted() || target.status.is_some() || self.rng.range(1, 100) > effect.chance
+        {
+            return;
+        }
+
+        let status = match effect.status {
+            StatusEffect::Poison => Status::Poisoned,
+            StatusEffect::Burn => Status::Burned,
+            StatusEffect::Paralysis => Status::Paralyzed,
+            StatusEffect::Sleep => Status::Asleep {
+                turns: self.rng.range(1, MAX_SLEEP_TURNS),
+            },
+            StatusEffect::Freeze => Status::Frozen,
+        };
+        let creature = self.active_index(side);
+        self.active_mut(side).status = Some(status);
+        self.log.push(BattleEvent::StatusInflicted {
+            side,
+            creature,
+            status,
+        });
+    }
+
+    /// Poison and burn hurt the active creatures at the end of each turn.
+    fn end_turn(&mut self, database: &SpeciesDatabase) {
+        for &side in &[Side::Player, Side::Wild] {
+            if self.outcome.is_some() || self.switch_required {
+                return;
+            }
+
+            let creature = self.active(side);
+            let status = match creature.status {
+                Some(status) if !creature.is_fainted() => status,
+                _ => continue,
+            };
+            let divisor = match status {
+                Status::Poisoned => 8,
+                Status::Burned => 16,
+                _ => continue,
+            };
+            let amount = (creature.stats(database).hp / divisor).max(1);
+
+            let creature = self.active_mut(side);
+            creature.hp = creature.hp.saturating_sub(amount);
+            let creature = self.active_index(side);
+            self.log.push(BattleEvent::StatusDamaged {
+                side,
+                creature,
+                status,
+                amount,
+            });
+            self.check_fainted(side);
+        }
+    }
+
+    /// Handle the active creature of a side fainting: the wild side sends its next creature,
+    /// the player has to choose one. The battle ends when a side has no creature left.
+    fn check_fainted(&mut self, side: Side) {
+        if !self.active(side).is_fainted() {
+            return;
+        }
+
+        let creature = self.active_index(side);
+        self.log.push(BattleEvent::Fainted { side, creature });
+        let next = self
+            .team(side)
+            .creatures
+            .iter()
+            .position(|c| !c.is_fainted());
+        match (side, next) {
+            (Side::Player, None) => self.end(BattleOutcome::Lost),
+            (Side::Wild, None) => self.end(BattleOutcome::Won),
+            (Side::Player, Some(_)) => self.switch_required = true,
+            (Side::Wild, Some(next)) => {
+                self.wild.active = next;
+                self.log.push(BattleEvent::SentOut {
+                    side,
+                    creature: next,
+                });
+            }
+        }
+    }
+}
+
+impl Team {
+    fn new(creatures: Vec<Creature>) -> Self {
+        let active = creatures.iter().position(|c| !c.is_fainted()).unwrap_or(0);
+        Team { creatures, active }
+    }
+}
+
+fn is_switch(action: &BattleAction) -> bool {
+    match action {
+        BattleAction::Switch(_) => true,
+        _ => false,
+    }
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ActionError::BattleOver => write!(f, "The battle is over."),
+            ActionError::SwitchRequired => write!(f, "Choose a creature to send out."),
+            ActionError::NoSuchMove => write!(f, "There is no such move."),
+            ActionError::NoPpLeft => write!(f, "There is no PP left for this move."),
+            ActionError::CannotSwitch => write!(f, "This creature can't fight."),
+            ActionError::InvalidTarget => write!(f, "There is no creature to use this on."),
+            ActionError::Item(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for ActionError {}
+
+impl fmt::Display for BattleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BattleError::EmptyTeam(Side::Player) => write!(f, "The party has no creature."),
+            BattleError::EmptyTeam(Side::Wild) => write!(f, "There is no creature to fight."),
+        }
+    }
+}
+
+impl Error for BattleError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{types::type_multiplier, *};
+    use crate::creatures::{CreatureType::*, LearnedMove, Stats};
+
+    const SPECIES: &str = r#"(
+        species: {
+            "sparky": (
+                name: "Sparky",
+                types: [Electric],
+                base_stats: (hp: 50, attack: 50, defense: 50, special_attack: 50, special_defense: 50, speed: 90),
+                learnset: [(level: 1, move_id: "spark")],
+                sprite: (sheet: File(texture: "sparky.png", definition: "sparky.ron"), index: 0),
+                catch_rate: 255,
+                base_experience: 50,
+            ),
+            "muddy": (
+                name: "Muddy",
+                types: [Ground],
+                base_stats: (hp: 50, attack: 50, defense: 50, special_attack: 50, special_defense: 50, speed: 30),
+                learnset: [(level: 1, move_id: "tackle")],
+                sprite: (sheet: File(texture: "muddy.png", definition: "muddy.ron"), index: 0),
+                catch_rate: 255,
+                base_experience: 50,
+            ),
+            "watery": (
+                name: "Watery",
+                types: [Water],
+                base_stats: (hp: 50, attack: 50, defense: 50, special_attack: 50, special_defense: 50, speed: 30),
+                learnset: [(level: 1, move_id: "tackle")],
+                sprite: (sheet: File(texture: "watery.png", definition: "watery.ron"), index: 0),
+                catch_rate: 255,
+                base_experience: 50,
+            ),
+            "leafy": (
+                name: "Leafy",
+                types: [Grass, Flying],
+                base_stats: (hp: 50, attack: 50, defense: 50, special_attack: 50, special_defense: 50, speed: 30),
+                learnset: [(level: 1, move_id: "tackle")],
+                sprite: (sheet: File(texture: "leafy.png", definition: "leafy.ron"), index: 0),
+                catch_rate: 45,
+                base_experience: 50,
+            ),
+        },
+        moves: {
+            "tackle": (name: "Tackle", move_type: Normal, category: Physical, power: 40, accuracy: 100, pp: 35),
+            "spark": (name: "Spark", move_type: Electric, category: Special, power: 40, accuracy: 100, pp: 30),
+            "poison_powder": (
+                name: "Poison Powder", move_type: Grass, category: Status, power: 0, accuracy: 100, pp: 35,
+                effect: Some((status: Poison, chance: 100)),
+            ),
+        },
+    )"#;
+
+    const ITEMS: &str = r#"(
+        items: {
+            "potion": (name: "Potion", description: "", pocket: Medicine, effects: [Heal(hp: 20)]),
+            "rustball": (name: "Rustball", description: "", pocket: CaptureDevices, effects: [Capture(bonus: 1.0)]),
+            "ultra_rustball": (name: "Ultra Rustball", description: "", pocket: CaptureDevices, effects: [Capture(bonus: 2.0)]),
+        },
+    )"#;
+
+    const LEVEL: u32 = 50;
+    const SEEDS: u64 = 200;
+
+    fn databases() -> (SpeciesDatabase, ItemDatabase) {
+        (
+            ron::de::from_str(SPECIES).unwrap(),
+            ron::de::from_str(ITEMS).unwrap(),
+        )
+    }
+
+    /// A creature without individual values, so its stats only depend on its species.
+    fn creature(species: &str, database: &SpeciesDatabase) -> Creature {
+        let mut creature = Creature::new(species, LEVEL, database, &mut Rng::new(1));
+        creature.ivs = Stats::default();
+        creature.heal(database);
+        creature
+    }
+
+    fn battle(player: &str, wild: &str, seed: u64, database: &SpeciesDatabase) -> Battle {
+        let party = vec![creature(player, database)];
+        Battle::new(party, vec![creature(wild, database)], seed).unwrap()
+    }
+
+    fn moves_used(events: &[BattleEvent], side: Side) -> usize {
+        events
+            .iter()
+            .filter(|event| match event {
+                BattleEvent::MoveUsed { side: user, .. } => *user == side,
+                _ => false,
+            })
+            .count()
+    }
+
+    #[test]
+    fn type_effectiveness_multiplies_each_type() {
+        assert_eq!(type_multiplier(Fire, Grass), 2.0);
+        assert_eq!(type_multiplier(Water, Grass), 0.5);
+        assert_eq!(type_multiplier(Normal, Water), 1.0);
+        assert_eq!(effectiveness(Electric, &[Water, Flying]), 4.0);
+        assert_eq!(effectiveness(Electric, &[Grass, Flying]), 1.0);
+        assert_eq!(effectiveness(Electric, &[Water, Ground]), 0.0);
+    }
+
+    #[test]
+    fn damage_follows_the_formula() {
+        let (database, items) = databases();
+        // Level 50 creatures with the same special stats: a 40 power move has a base damage
+        // of 19, the same type bonus and the effectiveness apply, then a random factor from
+        // 0.85 to 1 and critical hits.
+        let cases = [
+            ("watery", 2.0, (48, 57), (72, 85)),
+            ("leafy", 1.0, (24, 28), (36, 42)),
+            ("muddy", 0.0, (0, 0), (0, 0)),
+        ];
+        for &(wild, expected, normal, critical_range) in &cases {
+            for seed in 0..SEEDS {
+                let mut battle = battle("sparky", wild, seed, &database);
+                let events = battle
+                    .play_turn(BattleAction::Move(0), &database, &items)
+                    .unwrap();
+                let damage = events.iter().find_map(|event| match *event {
+                    BattleEvent::Damaged {
+                        side: Side::Wild,
+                        amount,
+                        critical,
+                        effectiveness,
+                        ..
+                    } => Some((amount, critical, effectiveness)),
+                    _ => None,
+                });
+                let (amount, critical, effectiveness) = damage.unwrap();
+                let (min, max) = if critical { critical_range } else { normal };
+                assert_eq!(effectiveness, expected, "{}", wild);
+                assert!(
+                    min <= amount && amount <= max,
+                    "{} took {} damage",
+                    wild,
+                    amount
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn the_fastest_creature_moves_first() {
+        let (database, items) = databases();
+        for seed in 0..SEEDS {
+            for &(player, wild, first) in &[
+                ("sparky", "muddy", Side::Player),
+                ("watery", "sparky", Side::Wild),
+            ] {
+                let mut battle = battle(player, wild, seed, &database);
+                let events = battle
+                    .play_turn(BattleAction::Move(0), &database, &items)
+                    .unwrap();
+                let sides = events
+                    .iter()
+                    .filter_map(|event| match *event {
+                        BattleEvent::MoveUsed { side, .. } => Some(side),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(sides, [first, first.opponent()]);
+            }
+        }
+    }
+
+    fn capture_count(hp: u32, status: Option<Status>, item: &str) -> u64 {
+        let (database, items) = databases();
+        let mut captures = 0;
+        for seed in 0..SEEDS {
+            let mut wild = creature("leafy", &database);
+            wild.hp = hp;
+            wild.status = status;
+            let party = vec![creature("sparky", &database)];
+            let mut battle = Battle::new(party, vec![wild], seed).unwrap();
+            let action = BattleAction::UseItem {
+                item: item.to_owned(),
+                target: 0,
+            };
+            battle.play_turn(action, &database, &items).unwrap();
+            if battle.outcome() == Some(BattleOutcome::Captured) {
+                captures += 1;
+            }
+        }
+        captures
+    }
+
+    #[test]
+    fn capture_odds_grow_with_damage_status_and_item() {
+        let max_hp = creature("leafy", &databases().0).hp;
+        let healthy = capture_count(max_hp, None, "rustball");
+        let weakened = capture_count(1, None, "rustball");
+        let asleep = capture_count(1, Some(Status::Asleep { turns: 3 }), "rustball");
+        let better_item = capture_count(1, Some(Status::Asleep { turns: 3 }), "ultra_rustball");
+        assert!(healthy < weakened, "{} {}", healthy, weakened);
+        assert!(weakened < asleep, "{} {}", weakened, asleep);
+        assert!(asleep < better_item, "{} {}", asleep, better_item);
+    }
+
+    #[test]
+    fn capture_is_certain_once_the_odds_are_maxed() {
+        let (database, items) = databases();
+        for seed in 0..SEEDS {
+            let mut wild = creature("muddy", &database);
+            wild.hp = 1;
+            let party = vec![creature("sparky", &database)];
+            let mut battle = Battle::new(party, vec![wild], seed).unwrap();
+            let action = BattleAction::UseItem {
+                item: "ultra_rustball".to_owned(),
+                target: 0,
+            };
+            let events = battle.play_turn(action, &database, &items).unwrap();
+            assert_eq!(
+                events,
+                [
+                    BattleEvent::CaptureItemThrown {
+                        item: "ultra_rustball".to_owned()
+                    },
+                    BattleEvent::Shook,
+                    BattleEvent::Shook,
+                    BattleEvent::Shook,
+                    BattleEvent::Ended(BattleOutcome::Captured),
+                ]
+            );
+            assert_eq!(battle.captured().unwrap().species, "muddy");
+        }
+    }
+
+    fn play_until_the_end(seed: u64) -> Vec<BattleEvent> {
+        let (database, items) = databases();
+        let mut battle = battle("watery", "leafy", seed, &database);
+        let mut turn = 0;
+        while battle.outcome().is_none() {
+            let action = match turn % 3 {
+                2 => BattleAction::UseItem {
+                    item: "potion".to_owned(),
+                    target: 0,
+                },
+                _ => BattleAction::Move(0),
+            };
+            // The potion has no effect while the creature is not hurt.
+            if battle.play_turn(action, &database, &items).is_err() {
+                battle
+                    .play_turn(BattleAction::Move(0), &database, &items)
+                    .unwrap();
+            }
+            turn += 1;
+        }
+        battle.log().to_vec()
+    }
+
+    #[test]
+    fn the_same_seed_replays_the_same_battle() {
+        let log = play_until_the_end(42);
+        assert_eq!(log, play_until_the_end(42));
+        assert_ne!(log, play_until_the_end(43));
+        assert!(log.len() > 10);
+    }
+
+    #[test]
+    fn items_need_a_target_in_the_party() {
+        let (database, items) = databases();
+        let mut battle = battle("sparky", "muddy", 0, &database);
+        let action = BattleAction::UseItem {
+            item: "potion".to_owned(),
+            target: 1,
+        };
+        assert_eq!(
+            battle.play_turn(action, &database, &items).unwrap_err(),
+            ActionError::InvalidTarget
+        );
+    }
+
+    #[test]
+    fn battles_need_a_creature_on_each_side() {
+        let (database, _) = databases();
+        let sparky = || vec![creature("sparky", &database)];
+        assert_eq!(
+            Battle::new(Vec::new(), sparky(), 0).err(),
+            Some(BattleError::EmptyTeam(Side::Player))
+        );
+        assert_eq!(
+            Battle::new(sparky(), Vec::new(), 0).err(),
+            Some(BattleError::EmptyTeam(Side::Wild))
+        );
+    }
+
+    #[test]
+    fn a_wild_creature_sent_out_after_a_faint_waits_for_the_next_turn() {
+        let (database, items) = databases();
+        for seed in 0..SEEDS {
+            // The first wild creature can only use its second move, the next one has a single move.
+            let mut first = creature("leafy", &database);
+            first.hp = 1;
+            first.moves.push(first.moves[0].clone());
+            first.moves[0].pp = 0;
+            let wild = vec![first, creature("watery", &database)];
+            let party = vec![creature("sparky", &database)];
+            let mut battle = Battle::new(party, wild, seed).unwrap();
+
+            let events = battle
+                .play_turn(BattleAction::Move(0), &database, &items)
+                .unwrap();
+            assert!(events.contains(&BattleEvent::SentOut {
+                side: Side::Wild,
+                creature: 1,
+            }));
+            assert_eq!(moves_used(&events, Side::Wild), 0);
+            assert_eq!(battle.active(Side::Wild).moves[0].pp, 35);
+        }
+    }
+
+    #[test]
+    fn statuses_are_inflicted_hurt_and_immobilize() {
+        let (database, items) = databases();
+        for seed in 0..SEEDS {
+            let mut poisoned = battle("sparky", "muddy", seed, &database);
+            poisoned.player.creatures[0].moves[0] = LearnedMove {
+                move_id: "poison_powder".to_owned(),
+                pp: 35,
+            };
+            let events = poisoned
+                .play_turn(BattleAction::Move(0), &database, &items)
+                .unwrap();
+            let max_hp = poisoned.active(Side::Wild).stats(&database).hp;
+            assert!(events.contains(&BattleEvent::StatusInflicted {
+                side: Side::Wild,
+                creature: 0,
+                status: Status::Poisoned,
+            }));
+            assert_eq!(
+                events.last(),
+                Some(&BattleEvent::StatusDamaged {
+                    side: Side::Wild,
+                    creature: 0,
+                    status: Status::Poisoned,
+                    amount: max_hp / 8,
+                })
+            );
+            assert_eq!(poisoned.active(Side::Wild).hp, max_hp - max_hp / 8);
+
+            let mut asleep = battle("sparky", "muddy", seed, &database);
+            asleep.wild.creatures[0].status = Some(Status::Asleep { turns: 2 });
+            let events = asleep
+                .play_turn(BattleAction::Move(0), &database, &items)
+                .unwrap();
+            assert!(events.contains(&BattleEvent::Immobilized {
+                side: Side::Wild,
+                creature: 0,
+                status: Status::Asleep { turns: 2 },
+            }));
+            assert_eq!(moves_used(&events, Side::Wild), 0);
+            assert_eq!(
+                asleep.active(Side::Wild).status,
+                Some(Status::Asleep { turns: 1 })
+            );
+        }
+    }
+
+    #[test]
+    fn critical_hits_multiply_the_damage() {
+        let (database, items) = databases();
+        let (mut normal, mut critical) = (Vec::new(), Vec::new());
+        for seed in 0..SEEDS {
+            let mut battle = battle("sparky", "leafy", seed, &database);
+            let events = battle
+                .play_turn(BattleAction::Move(0), &database, &items)
+                .unwrap();
+            for event in events {
+                if let BattleEvent::Damaged {
+                    side: Side::Wild,
+                    amount,
+                    critical: is_critical,
+                    ..
+                } = event
+                {
+                    if is_critical {
+                        critical.push(amount);
+                    } else {
+                        normal.push(amount);
+                    }
+                }
+            }
+        }
+        // About one hit in 16 is critical.
+        assert!(!critical.is_empty() && critical.len() < normal.len() / 4);
+        let (min, max) = (normal.iter().min().unwrap(), normal.iter().max().unwrap());
+        for amount in critical {
+            let base = amount as f32 / CRITICAL_MULTIPLIER;
+            assert!(
+                *min as f32 - 1.0 <= base && base <= *max as f32 + 1.0,
+                "{}",
+                amount
+            );
+        }
+    }
+
+    #[test]
+    fn switching_costs_a_turn() {
+        let (database, items) = databases();
+        for seed in 0..SEEDS {
+            let party = vec![creature("muddy", &database), creature("watery", &database)];
+            let wild = vec![creature("sparky", &database)];
+            let mut battle = Battle::new(party, wild, seed).unwrap();
+            let events = battle
+                .play_turn(BattleAction::Switch(1), &database, &items)
+                .unwrap();
+            assert_eq!(
+                events[0],
+                BattleEvent::SentOut {
+                    side: Side::Player,
+                    creature: 1,
+                }
+            );
+            assert_eq!(moves_used(&events, Side::Player), 0);
+            assert_eq!(moves_used(&events, Side::Wild), 1);
+            assert_eq!(battle.active_index(Side::Player), 1);
+        }
+    }
+
+    #[test]
+    fn a_fainted_creature_is_replaced_for_free() {
+        let (database, items) = databases();
+        for seed in 0..SEEDS {
+            let mut weak = creature("watery", &database);
+            weak.hp = 1;
+            let party = vec![weak, creature("muddy", &database)];
+            let wild = vec![creature("sparky", &database)];
+            let mut battle = Battle::new(party, wild, seed).unwrap();
+
+            let events = battle
+                .play_turn(BattleAction::Move(0), &database, &items)
+                .unwrap();
+            assert!(events.contains(&BattleEvent::Fainted {
+                side: Side::Player,
+                creature: 0,
+            }));
+            assert_eq!(moves_used(&events, Side::Player), 0);
+            assert!(battle.switch_required());
+            assert_eq!(
+                battle.play_turn(BattleAction::Move(0), &database, &items),
+                Err(ActionError::SwitchRequired)
+            );
+
+            let events = battle
+                .play_turn(BattleAction::Switch(1), &database, &items)
+                .unwrap();
+            assert_eq!(
+                events,
+                [BattleEvent::SentOut {
+                    side: Side::Player,
+                    creature: 1,
+                }]
+            );
+            assert!(!battle.switch_required());
+        }
+    }
+
+    #[test]
+    fn fleeing_succeeds_when_faster_and_gets_easier_with_each_attempt() {
+        let (database, items) = databases();
+        let mut failures = 0;
+        for seed in 0..SEEDS {
+            let mut fast = battle("sparky", "muddy", seed, &database);
+            let events = fast
+                .play_turn(BattleAction::Flee, &database, &items)
+                .unwrap();
+            assert_eq!(events, [BattleEvent::Ended(BattleOutcome::Fled)]);
+
+            // Muddy can't outrun Sparky, whose moves don't affect it.
+            let mut slow = battle("muddy", "sparky", seed, &database);
+            let mut attempts = 0;
+            while slow.outcome().is_none() {
+                let events = slow
+                    .play_turn(BattleAction::Flee, &database, &items)
+                    .unwrap();
+                if slow.outcome().is_none() {
+                    assert_eq!(events[0], BattleEvent::FleeFailed);
+                    assert_eq!(moves_used(&events, Side::Wild), 1);
+                    failures += 1;
+                }
+                attempts += 1;
+            }
+            assert_eq!(slow.outcome(), Some(BattleOutcome::Fled));
+            // The odds exceed 255 on the 8th attempt.
+            assert!(attempts <= 8, "{} attempts", attempts);
+        }
+        assert!(failures > 0);
+    }
+}
@@ -0,0 +1,32 @@
+use crate::creatures::CreatureType::{self, *};
+
+/// Damage multiplier of a move of type `attack` against a creature of type `defense`.
+pub fn type_multiplier(attack: CreatureType, defense: CreatureType) -> f32 {
+    match (attack, defense) {
+        (Fire, Grass) | (Fire, Bug) => 2.0,
+        (Fire, Fire) | (Fire, Water) => 0.5,
+        (Water, Fire) | (Water, Ground) => 2.0,
+        (Water, Water) | (Water, Grass) => 0.5,
+        (Grass, Water) | (Grass, Ground) => 2.0,
+        (Grass, Fire) | (Grass, Grass) | (Grass, Flying) | (Grass, Bug) => 0.5,
+        (Electric, Water) | (Electric, Flying) => 2.0,
+        (Electric, Grass) | (Electric, Electric) => 0.5,
+        (Electric, Ground) => 0.0,
+        (Ground, Fire) | (Ground, Electric) => 2.0,
+        (Ground, Grass) | (Ground, Bug) => 0.5,
+        (Ground, Flying) => 0.0,
+        (Flying, Grass) | (Flying, Bug) => 2.0,
+        (Flying, Electric) => 0.5,
+        (Bug, Grass) => 2.0,
+        (Bug, Fire) | (Bug, Flying) => 0.5,
+        _ => 1.0,
+    }
+}
+
+/// Damage multiplier of a move of type `attack` against a creature having all the `defense` types.
+pub fn effectiveness(attack: CreatureType, defense: &[CreatureType]) -> f32 {
+    defense
+        .iter()
+        .map(|&defense| type_multiplier(attack, defense))
+        .product()
+}
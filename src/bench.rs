@@ -0,0 +1,100 @@
+use crate::{
+    components::{Active, Body, CollisionMarker, Dynamic, Shape, Tilemap},
+    events::CollisionEvent,
+    systems::{BroadPhase, PhysicsSystem},
+};
+use amethyst::{
+    core::transform::Transform,
+    ecs::prelude::{Builder, RunNow, World},
+    shrev::EventChannel,
+};
+use ncollide2d::{
+    math::Vector,
+    shape::{Ball, Cuboid},
+};
+use std::time::Instant;
+
+const BODY_COUNTS: [usize; 3] = [500, 2000, 5000];
+const FRAMES: u32 = 60;
+/// Share of bodies which are dynamic, the others are static obstacles.
+const DYNAMIC_RATIO: f32 = 0.1;
+
+/// Run the physics system headlessly on worlds filled with bodies
+/// and print the average frame time of each broad phase.
+pub fn run_physics_benchmark() {
+    for &count in BODY_COUNTS.iter() {
+        let brute_force = time_physics(count, BroadPhase::BruteForce);
+        let grid = time_physics(count, BroadPhase::default());
+        println!(
+            "{} bodies: brute force {:.3} ms/frame, grid {:.3} ms/frame",
+            count, brute_force, grid
+        );
+    }
+}
+
+/// Return the average time of a physics frame, in milliseconds.
+fn time_physics(count: usize, broad_phase: BroadPhase) -> f64 {
+    let mut world = build_world(count);
+    let mut physics = PhysicsSystem::new(broad_phase);
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        physics.run_now(&world.res);
+        world.maintain();
+    }
+    let elapsed = start.elapsed();
+
+    (elapsed.as_secs() as f64 * 1000.0 + f64::from(elapsed.subsec_nanos()) / 1_000_000.0)
+        / f64::from(FRAMES)
+}
+
+/// Build a world with `count` bodies scattered with a constant density.
+fn build_world(count: usize) -> World {
+    let mut world = World::new();
+    world.register::<Transform>();
+    world.register::<Body>();
+    world.register::<CollisionMarker>();
+    world.register::<Tilemap>();
+    world.register::<Active>();
+    world.add_resource(EventChannel::<CollisionEvent>::new());
+
+    let side = (count as f32).sqrt() * 64.0;
+    let mut seed = 0x2545_F491u32;
+    let mut random = move || {
+        // Numerical Recipes LCG, good enough to scatter bodies.
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        seed as f32 / u32::max_value() as f32
+    };
+
+    for _ in 0..count {
+        let mut transform = Transform::default();
+        transform.set_xyz(random() * side, random() * side, 0.0);
+
+        let body = if random() < DYNAMIC_RATIO {
+            Body {
+                shape: Shape::Circle {
+                    shape: Ball::new(16.0),
+                },
+                dynamic: Dynamic::Dynamic,
+                sensor: false,
+            }
+        } else {
+            Body {
+                shape: Shape::Box {
+                    shape: Cuboid::new(Vector::new(16.0, 16.0)),
+                },
+                dynamic: Dynamic::Static,
+                sensor: false,
+            }
+        };
+
+        world
+            .create_entity()
+            .with(Active)
+            .with(transform)
+            .with(body)
+            .build();
+    }
+
+    world
+}
@@ -1,8 +1,14 @@
 use crate::{
     animations::SpriteAnimation,
+    creatures::SpeciesDatabase,
+    dialogue::DialogueDatabase,
+    items::ItemDatabase,
+    maps::MapDefinition,
     systems::{
-        CameraTargetingSystem, HeroMovementSystem, MobMovementSystem, MobTargetSystem,
-        MovementSystem, PhysicsSystem, PortalTriggerSystem,
+        CameraTargetingSystem, EncounterSystem, HeroMovementSystem, InteractionSystem,
+        InterpolationSystem, LayerActivationSystem, MobMovementSystem, MobTargetSystem,
+        MovementSystem, PhysicsSystem, SimulationBeginSystem, SimulationEndSystem,
+        TilemapRenderSystem, TransitionSystem,
     },
 };
 use amethyst::{
@@ -11,15 +17,24 @@ use amethyst::{
     ecs::prelude::DispatcherBuilder,
 };
 
-pub struct RustymonBundle;
+/// Game logic, run at each simulation tick.
+pub struct SimulationBundle;
 
-impl<'a, 'b> SystemBundle<'a, 'b> for RustymonBundle {
+impl<'a, 'b> SystemBundle<'a, 'b> for SimulationBundle {
     fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
+        // Sync the active entities first, the state may have changed the layers it shows.
+        builder.add(LayerActivationSystem, "layer_activation", &[]);
+        builder.add(
+            SimulationBeginSystem,
+            "simulation_begin",
+            &["layer_activation"],
+        );
+
         // Movement
         {
-            builder.add(HeroMovementSystem, "hero_movement", &["input_system"]);
-            // Could have a dependency on 'mob_target' but it seems ok to have one frame latency before starting to follow the target.
-            builder.add(MobMovementSystem, "mob_movement", &[]);
+            builder.add(HeroMovementSystem, "hero_movement", &["simulation_begin"]);
+            // Could have a dependency on 'mob_target' but it seems ok to have one tick latency before starting to follow the target.
+            builder.add(MobMovementSystem, "mob_movement", &["simulation_begin"]);
             builder.add(
                 MovementSystem,
                 "movement",
@@ -27,13 +42,50 @@ impl<'a, 'b> SystemBundle<'a, 'b> for RustymonBundle {
             )
         }
 
-        builder.add(PhysicsSystem, "physics", &["movement"]);
-        builder.add(CameraTargetingSystem, "camera_targeting", &[]);
-        builder.add(PortalTriggerSystem, "portal", &[]);
-        builder.add(MobTargetSystem, "mob_target", &[]);
+        builder.add(PhysicsSystem::default(), "physics", &["movement"]);
+        builder.add(InteractionSystem::default(), "interaction", &["physics"]);
+        builder.add(MobTargetSystem, "mob_target", &["physics"]);
+        builder.add(
+            EncounterSystem::default(),
+            "encounter",
+            &["physics", "interaction"],
+        );
+
+        builder.add(
+            SimulationEndSystem,
+            "simulation_end",
+            &["physics", "interaction", "mob_target", "encounter"],
+        );
+        Ok(())
+    }
+}
+
+/// Systems run once per rendered frame.
+pub struct RustymonBundle;
+
+impl<'a, 'b> SystemBundle<'a, 'b> for RustymonBundle {
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
+        // Also run each frame, states pausing the simulation may show other layers.
+        builder.add(LayerActivationSystem, "layer_activation", &[]);
+        builder.add(InterpolationSystem, "interpolation", &["layer_activation"]);
+        builder.add(
+            CameraTargetingSystem,
+            "camera_targeting",
+            &["interpolation"],
+        );
+        builder.add(
+            TilemapRenderSystem::default(),
+            "tilemap_render",
+            &["camera_targeting"],
+        );
+        builder.add(TransitionSystem, "transition", &["camera_targeting"]);
 
         // Processors
         builder.add(Processor::<SpriteAnimation>::new(), "", &[]);
+        builder.add(Processor::<MapDefinition>::new(), "", &[]);
+        builder.add(Processor::<SpeciesDatabase>::new(), "", &[]);
+        builder.add(Processor::<ItemDatabase>::new(), "", &[]);
+        builder.add(Processor::<DialogueDatabase>::new(), "", &[]);
         Ok(())
     }
 }
@@ -1,17 +1,38 @@
 use crate::animations::HeroAnimationId;
 use amethyst::ecs::prelude::{Component, VecStorage};
+use ncollide2d::math::Vector;
 use specs_derive::*;
 
 #[derive(Component)]
 #[storage(VecStorage)]
 pub struct Hero {
     pub current_animation_id: Option<HeroAnimationId>,
+    /// Direction the hero is facing, one of the four axis directions in grid movement.
+    /// Used to pick the entity the hero interacts with.
+    pub facing: Vector<f32>,
+    /// Step in progress in grid movement.
+    pub step: Option<GridStep>,
+    /// For how long the facing direction has been held since the hero last turned in place, in seconds.
+    pub turn_held_seconds: f32,
+}
+
+/// A step from a tile to the next one.
+#[derive(Clone, Copy, Debug)]
+pub struct GridStep {
+    pub from: Vector<f32>,
+    pub to: Vector<f32>,
+    pub elapsed_seconds: f32,
+    /// Last position written by the step, used to detect when the hero is moved by something else.
+    pub position: Vector<f32>,
 }
 
 impl Hero {
     pub fn new() -> Self {
         Hero {
             current_animation_id: None,
+            facing: Vector::new(0.0, -1.0),
+            step: None,
+            turn_held_seconds: 0.0,
         }
     }
 }
@@ -0,0 +1,77 @@
+use crate::{creatures::Creature, states::Instance};
+use amethyst::ecs::prelude::{Component, VecStorage};
+use ncollide2d::shape::Cuboid;
+use serde_derive::*;
+use specs_derive::*;
+
+/// Something the hero interacts with by pressing "use" inside its trigger zone.
+#[derive(Component)]
+#[storage(VecStorage)]
+pub struct Interactable {
+    pub trigger_zone: Cuboid<f32>,
+    pub interaction: Interaction,
+}
+
+/// What happens when the hero interacts with an entity.
+#[derive(Clone, Debug)]
+pub enum Interaction {
+    /// Bring the hero into an instance, nested inside the current one if any.
+    EnterInstance(Instance),
+    /// Start a dialogue, turning the NPC towards the hero.
+    Talk {
+        name: String,
+        /// Id of the dialogue in the `DialogueDatabase`.
+        dialogue: String,
+        /// Sprites shown when facing each direction, the sprite doesn't change if None.
+        facing_sprites: Option<FacingSprites>,
+    },
+    /// Give items the first time, `flag` is set once they are picked up.
+    PickUp {
+        /// Ids of the items in the `ItemDatabase`, along with their count.
+        items: Vec<(String, u32)>,
+        flag: String,
+    },
+    ReadSign(String),
+    /// Fight a creature until it is defeated, `won_flag` is set once it is.
+    StartBattle {
+        species: String,
+        level: u32,
+        won_flag: String,
+    },
+}
+
+/// Sprite indices of a character in its sprite sheet, one per direction.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct FacingSprites {
+    pub down: usize,
+    pub up: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+impl FacingSprites {
+    /// The sprite facing a direction, the closest axis wins.
+    pub fn facing(&self, x: f32, y: f32) -> usize {
+        if x.abs() > y.abs() {
+            if x < 0.0 {
+                self.left
+            } else {
+                self.right
+            }
+        } else if y < 0.0 {
+            self.down
+        } else {
+            self.up
+        }
+    }
+}
+
+/// The creature fought in a battle started by a dialogue or an interaction.
+/// The entity only lives for the duration of the battle.
+#[derive(Component)]
+#[storage(VecStorage)]
+pub struct NpcBattle {
+    pub creature: Creature,
+    /// Flag set if the player wins.
+    pub won_flag: Option<String>,
+}
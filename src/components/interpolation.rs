@@ -0,0 +1,37 @@
+use amethyst::ecs::prelude::{Component, VecStorage};
+use ncollide2d::math::Vector;
+use specs_derive::*;
+
+/// Smooth the rendered position of an entity moved by the fixed rate simulation.
+/// The transform holds the simulated position during the simulation ticks,
+/// and a position interpolated between the last two ticks when rendering.
+#[derive(Component)]
+#[storage(VecStorage)]
+pub struct Interpolation {
+    /// Simulated position before the last tick.
+    pub previous: Vector<f32>,
+    /// Simulated position after the last tick.
+    pub current: Vector<f32>,
+    /// Last position written for rendering.
+    /// When the transform doesn't match it anymore, the entity has been moved outside of
+    /// the simulation (e.g. teleported) and the new position is taken as is.
+    pub rendered: Option<Vector<f32>>,
+}
+
+impl Interpolation {
+    pub fn new() -> Self {
+        Interpolation {
+            previous: Vector::new(0.0, 0.0),
+            current: Vector::new(0.0, 0.0),
+            rendered: None,
+        }
+    }
+
+    /// Take `position` as the simulated position if it has been set outside of the simulation.
+    pub fn sync(&mut self, position: Vector<f32>) {
+        if self.rendered != Some(position) {
+            self.previous = position;
+            self.current = position;
+        }
+    }
+}
@@ -1,9 +1,13 @@
+use crate::creatures::Creature;
 use amethyst::ecs::{Component, Entity, VecStorage};
 use specs_derive::*;
 
 #[derive(Component)]
 #[storage(VecStorage)]
 pub struct Mob {
+    /// Index of the mob's definition in its map, identifies the mob in saves.
+    pub map_index: usize,
+
     /// The original spawn position of the mob.
     pub spawn: (f32, f32),
 
@@ -18,16 +22,28 @@ pub struct Mob {
 
     /// The squared distance from which it starts targetting an entity.
     pub squared_target_threshold: f32,
+
+    /// The wild creature fought when touching the mob.
+    pub creature: Creature,
 }
 
 impl Mob {
-    pub fn new(x: f32, y: f32, reset_threshold: f32, target_threshold: f32) -> Self {
+    pub fn new(
+        map_index: usize,
+        x: f32,
+        y: f32,
+        reset_threshold: f32,
+        target_threshold: f32,
+        creature: Creature,
+    ) -> Self {
         Mob {
+            map_index,
             spawn: (x, y),
             squared_reset_threshold: reset_threshold * reset_threshold,
             resetting: false,
             target: None,
             squared_target_threshold: target_threshold * target_threshold,
+            creature,
         }
     }
 }
@@ -1,15 +1,21 @@
 mod camera;
 mod hero;
+mod interactable;
+mod interpolation;
 mod mob;
 mod physics;
-mod portal;
 mod states;
+mod tilemap;
+mod transition;
 
 pub use self::{
     camera::CameraTarget,
-    hero::Hero,
+    hero::{GridStep, Hero},
+    interactable::{FacingSprites, Interactable, Interaction, NpcBattle},
+    interpolation::Interpolation,
     mob::Mob,
     physics::{Body, CollisionMarker, Dynamic, Shape, Velocity},
-    portal::Portal,
-    states::{Active, InstanceCompat, OverworldCompat},
+    states::{Active, Layer, LayerId},
+    tilemap::{TileLayer, Tilemap},
+    transition::TransitionOverlay,
 };
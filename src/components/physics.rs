@@ -1,14 +1,16 @@
 use amethyst::ecs::prelude::{Component, VecStorage};
 use ncollide2d::{
     math::Vector,
-    shape::{Ball, Cuboid},
+    shape::{self, Ball, Compound, ConvexPolygon, Cuboid, ShapeHandle},
 };
+use serde_derive::*;
 use specs_derive::*;
 
 #[derive(Component)]
 #[storage(VecStorage)]
 pub struct Velocity {
     pub direction: Vector<f32>,
+    /// In world units per second.
     pub speed: f32,
 }
 
@@ -33,27 +35,105 @@ impl Velocity {
 pub struct Body {
     pub shape: Shape,
     pub dynamic: Dynamic,
+    /// A sensor only detects contacts: it neither pushes nor is pushed by other bodies.
+    pub sensor: bool,
 }
 
 pub enum Shape {
-    Circle { shape: Ball<f32> },
-    Box { shape: Cuboid<f32> },
+    Circle {
+        shape: Ball<f32>,
+    },
+    Box {
+        shape: Cuboid<f32>,
+    },
+    Polygon {
+        shape: ConvexPolygon<f32>,
+    },
+    /// Several shapes placed relatively to the body.
+    Compound {
+        shape: Compound<f32>,
+    },
 }
 
-#[derive(PartialEq)]
+impl Shape {
+    /// Get the underlying ncollide shape.
+    pub fn as_ncollide(&self) -> &dyn shape::Shape<f32> {
+        match self {
+            Shape::Circle { shape } => shape,
+            Shape::Box { shape } => shape,
+            Shape::Polygon { shape } => shape,
+            Shape::Compound { shape } => shape,
+        }
+    }
+
+    /// Convert into a shape handle, to be used as a part of a compound shape.
+    pub fn into_shape_handle(self) -> ShapeHandle<f32> {
+        match self {
+            Shape::Circle { shape } => ShapeHandle::new(shape),
+            Shape::Box { shape } => ShapeHandle::new(shape),
+            Shape::Polygon { shape } => ShapeHandle::new(shape),
+            Shape::Compound { shape } => ShapeHandle::new(shape),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Dynamic {
     Static,
     Dynamic,
 }
 
+/// Accumulate the penetrations of all the contacts of an entity during a physics step.
+/// Only the deepest penetration in each direction is kept: two contacts pushing the same way
+/// (e.g. two adjacent walls) are not summed, while contacts pushing different ways
+/// (e.g. a corner) are all resolved.
 #[derive(Component)]
 #[storage(VecStorage)]
 pub struct CollisionMarker {
-    pub penetration: Vector<f32>,
+    max: Vector<f32>,
+    min: Vector<f32>,
 }
 
 impl CollisionMarker {
     pub fn new(penetration: Vector<f32>) -> Self {
-        CollisionMarker { penetration }
+        let mut marker = CollisionMarker {
+            max: Vector::new(0.0, 0.0),
+            min: Vector::new(0.0, 0.0),
+        };
+        marker.add(penetration);
+        marker
+    }
+
+    /// Add the penetration of another contact.
+    pub fn add(&mut self, penetration: Vector<f32>) {
+        self.max = Vector::new(self.max.x.max(penetration.x), self.max.y.max(penetration.y));
+        self.min = Vector::new(self.min.x.min(penetration.x), self.min.y.min(penetration.y));
+    }
+
+    /// The translation separating the entity from all its contacts.
+    pub fn penetration(&self) -> Vector<f32> {
+        self.max + self.min
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn penetrations_are_combined_by_direction() {
+        // Two walls pushing the same way: only the deepest penetration is kept.
+        let mut marker = CollisionMarker::new(Vector::new(2.0, 0.0));
+        marker.add(Vector::new(3.0, 0.0));
+        assert_eq!(marker.penetration(), Vector::new(3.0, 0.0));
+
+        // A wall pushing the opposite way.
+        marker.add(Vector::new(-1.0, 0.0));
+        assert_eq!(marker.penetration(), Vector::new(2.0, 0.0));
+
+        // A perpendicular wall, as in a corner, is resolved along its own axis.
+        marker.add(Vector::new(0.0, -4.0));
+        marker.add(Vector::new(0.0, -1.0));
+        assert_eq!(marker.penetration(), Vector::new(2.0, -4.0));
     }
 }
@@ -1,4 +1,4 @@
-use amethyst::ecs::{Component, NullStorage};
+use amethyst::ecs::{Component, NullStorage, VecStorage};
 use specs_derive::*;
 
 /// Mark an entity as being active.
@@ -7,12 +7,34 @@ use specs_derive::*;
 #[storage(NullStorage)]
 pub struct Active;
 
-/// Mark an entity as being compatible with the `Overworld` state.
-#[derive(Component, Default)]
-#[storage(NullStorage)]
-pub struct OverworldCompat;
+/// Identify a layer of entities shown together.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LayerId(pub u32);
 
-/// Mark an entity as being compatible with the `Instance` state.
-#[derive(Component, Default)]
-#[storage(NullStorage)]
-pub struct InstanceCompat;
+impl LayerId {
+    /// The hero and the camera following him, shown in the overworld and in instances.
+    pub const HERO: LayerId = LayerId(0);
+    pub const OVERWORLD: LayerId = LayerId(1);
+    pub const BATTLE: LayerId = LayerId(2);
+
+    /// The layer of an instance, by its depth: 1 for an instance entered from the overworld...
+    pub fn instance(depth: usize) -> LayerId {
+        LayerId(LayerId::BATTLE.0 + depth as u32)
+    }
+}
+
+/// The layer an entity belongs to.
+/// It is active and shown only while its layer is one of the `ActiveLayers`,
+/// see `LayerActivationSystem`, which owns the `Active` and `Hidden` components
+/// of these entities. Entities without a layer are left alone.
+#[derive(Component, Copy, Clone, Debug)]
+#[storage(VecStorage)]
+pub struct Layer {
+    pub id: LayerId,
+}
+
+impl Layer {
+    pub fn new(id: LayerId) -> Self {
+        Layer { id }
+    }
+}
@@ -0,0 +1,156 @@
+use amethyst::{
+    ecs::prelude::{Component, VecStorage},
+    renderer::SpriteSheetHandle,
+};
+use specs_derive::*;
+
+/// A grid of tiles.
+/// The entity's transform is the position of the bottom left corner of the grid.
+/// Rows are numbered from the top of the grid, like in map editors.
+#[derive(Component)]
+#[storage(VecStorage)]
+pub struct Tilemap {
+    pub width: usize,
+    pub height: usize,
+    pub tile_width: f32,
+    pub tile_height: f32,
+    /// Layers, drawn from the first to the last.
+    pub layers: Vec<TileLayer>,
+    /// Is the tile blocking dynamic bodies. Row major, `width * height` long.
+    pub solid: Vec<bool>,
+}
+
+/// A layer of tiles sharing the same sprite sheet.
+pub struct TileLayer {
+    pub sprite_sheet: SpriteSheetHandle,
+    /// Sprite index of each tile, None for empty tiles. Row major, `width * height` long.
+    pub tiles: Vec<Option<usize>>,
+}
+
+impl Tilemap {
+    /// Is the tile at the given column and row solid.
+    /// Tiles outside of the grid are not solid.
+    pub fn is_solid(&self, column: usize, row: usize) -> bool {
+        column < self.width && row < self.height && self.solid[row * self.width + column]
+    }
+
+    /// Get the position of a tile's center, relative to the grid's origin.
+    pub fn tile_center(&self, column: usize, row: usize) -> (f32, f32) {
+        (
+            (column as f32 + 0.5) * self.tile_width,
+            ((self.height - row) as f32 - 0.5) * self.tile_height,
+        )
+    }
+
+    /// Get the columns and rows of the tiles overlapping a rectangle relative to the grid's origin.
+    /// Return ((min column, max column), (min row, max row)), bounds included,
+    /// or None if the rectangle is outside of the grid.
+    pub fn tiles_in(
+        &self,
+        min: (f32, f32),
+        max: (f32, f32),
+    ) -> Option<((usize, usize), (usize, usize))> {
+        let width = self.width as f32 * self.tile_width;
+        let height = self.height as f32 * self.tile_height;
+        if max.0 < 0.0 || max.1 < 0.0 || min.0 >= width || min.1 >= height {
+            return None;
+        }
+
+        let clamp = |v: f32, size: f32, limit: usize| {
+            (v / size).floor().max(0.0).min(limit as f32 - 1.0) as usize
+        };
+        let min_column = clamp(min.0, self.tile_width, self.width);
+        let max_column = clamp(max.0, self.tile_width, self.width);
+        let min_row = self.height - 1 - clamp(max.1, self.tile_height, self.height);
+        let max_row = self.height - 1 - clamp(min.1, self.tile_height, self.height);
+
+        Some(((min_column, max_column), (min_row, max_row)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x3 grid of 16x8 tiles, whose top left and bottom right tiles are solid.
+    fn tilemap() -> Tilemap {
+        let mut solid = vec![false; 12];
+        solid[0] = true;
+        solid[11] = true;
+        Tilemap {
+            width: 4,
+            height: 3,
+            tile_width: 16.0,
+            tile_height: 8.0,
+            layers: Vec::new(),
+            solid,
+        }
+    }
+
+    /// The tile containing a point.
+    fn tile_at(tilemap: &Tilemap, x: f32, y: f32) -> Option<(usize, usize)> {
+        tilemap
+            .tiles_in((x, y), (x, y))
+            .map(|((column, _), (row, _))| (column, row))
+    }
+
+    #[test]
+    fn rows_are_numbered_from_the_top() {
+        let tilemap = tilemap();
+        assert_eq!(tile_at(&tilemap, 1.0, 23.0), Some((0, 0)));
+        assert_eq!(tile_at(&tilemap, 63.0, 1.0), Some((3, 2)));
+        assert_eq!(tilemap.tile_center(0, 0), (8.0, 20.0));
+        assert_eq!(tilemap.tile_center(3, 2), (56.0, 4.0));
+        for &(column, row) in &[(0, 0), (1, 1), (3, 2)] {
+            let (x, y) = tilemap.tile_center(column, row);
+            assert_eq!(tile_at(&tilemap, x, y), Some((column, row)));
+        }
+    }
+
+    #[test]
+    fn points_outside_of_the_grid_have_no_tile() {
+        let tilemap = tilemap();
+        assert_eq!(tile_at(&tilemap, 0.0, 0.0), Some((0, 2)));
+        assert_eq!(tile_at(&tilemap, 64.0, 4.0), None);
+        assert_eq!(tile_at(&tilemap, 8.0, 24.0), None);
+        assert_eq!(tile_at(&tilemap, -0.1, 4.0), None);
+        assert_eq!(tile_at(&tilemap, 8.0, -0.1), None);
+    }
+
+    #[test]
+    fn rectangles_are_clamped_to_the_grid() {
+        let tilemap = tilemap();
+        assert_eq!(
+            tilemap.tiles_in((20.0, 9.0), (40.0, 15.0)),
+            Some(((1, 2), (1, 1)))
+        );
+        // Straddling the bottom left corner, then the top right one.
+        assert_eq!(
+            tilemap.tiles_in((-10.0, -10.0), (10.0, 5.0)),
+            Some(((0, 0), (2, 2)))
+        );
+        assert_eq!(
+            tilemap.tiles_in((50.0, 20.0), (100.0, 100.0)),
+            Some(((3, 3), (0, 0)))
+        );
+        assert_eq!(
+            tilemap.tiles_in((-10.0, -10.0), (100.0, 100.0)),
+            Some(((0, 3), (0, 2)))
+        );
+        assert_eq!(tilemap.tiles_in((64.0, 0.0), (80.0, 8.0)), None);
+        assert_eq!(tilemap.tiles_in((0.0, 24.0), (16.0, 30.0)), None);
+        assert_eq!(tilemap.tiles_in((-20.0, 0.0), (-1.0, 8.0)), None);
+        assert_eq!(tilemap.tiles_in((0.0, -20.0), (16.0, -1.0)), None);
+    }
+
+    #[test]
+    fn tiles_outside_of_the_grid_are_not_solid() {
+        let tilemap = tilemap();
+        assert!(tilemap.is_solid(0, 0));
+        assert!(tilemap.is_solid(3, 2));
+        assert!(!tilemap.is_solid(1, 0));
+        assert!(!tilemap.is_solid(4, 0));
+        assert!(!tilemap.is_solid(0, 3));
+        assert!(!tilemap.is_solid(usize::max_value(), 0));
+    }
+}
@@ -0,0 +1,10 @@
+use crate::transition::OverlayFrames;
+use amethyst::ecs::prelude::{Component, HashMapStorage};
+use specs_derive::*;
+
+/// The entity drawing screen transitions over the view of the camera.
+#[derive(Component)]
+#[storage(HashMapStorage)]
+pub struct TransitionOverlay {
+    pub frames: OverlayFrames,
+}
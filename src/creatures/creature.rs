@@ -0,0 +1,270 @@
+use super::{SpeciesDatabase, Stats, StatusEffect};
+use crate::rng::Rng;
+use serde_derive::*;
+
+pub const MAX_LEVEL: u32 = 100;
+/// Individual values range from 0 to this value, included.
+pub const MAX_IV: u32 = 31;
+pub const MAX_MOVES: usize = 4;
+
+/// A monster: a wild one carried by a mob or one of the player's.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Creature {
+    /// Id of the species in the `SpeciesDatabase`.
+    pub species: String,
+    /// Name given by the player, if any.
+    #[serde(default)]
+    pub nickname: Option<String>,
+    pub level: u32,
+    /// Total experience earned.
+    pub experience: u32,
+    /// Random individual values, fixed at creation.
+    pub ivs: Stats,
+    /// Effort values, earned by defeating other creatures.
+    pub evs: Stats,
+    pub hp: u32,
+    pub moves: Vec<LearnedMove>,
+    #[serde(default)]
+    pub status: Option<Status>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LearnedMove {
+    /// Id of the move in the `SpeciesDatabase`.
+    pub move_id: String,
+    /// Remaining uses.
+    pub pp: u32,
+}
+
+/// Lasting status conditions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Status {
+    Poisoned,
+    Burned,
+    Paralyzed,
+    Asleep { turns: u32 },
+    Frozen,
+}
+
+impl Status {
+    /// Is this the status inflicted by an effect.
+    pub fn is_caused_by(self, effect: StatusEffect) -> bool {
+        match (self, effect) {
+            (Status::Poisoned, StatusEffect::Poison)
+            | (Status::Burned, StatusEffect::Burn)
+            | (Status::Paralyzed, StatusEffect::Paralysis)
+            | (Status::Asleep { .. }, StatusEffect::Sleep)
+            | (Status::Frozen, StatusEffect::Freeze) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Creature {
+    /// Create a wild creature with random individual values, full HP
+    /// and the last moves its species learns up to `level`.
+    /// Panics if the species is not in the database.
+    pub fn new(species_id: &str, level: u32, database: &SpeciesDatabase, rng: &mut Rng) -> Self {
+        let species = &database.species[species_id];
+        let level = level.max(1).min(MAX_LEVEL);
+        let mut iv = || rng.range(0, MAX_IV);
+        let ivs = Stats {
+            hp: iv(),
+            attack: iv(),
+            defense: iv(),
+            special_attack: iv(),
+            special_defense: iv(),
+            speed: iv(),
+        };
+        let moves = species
+            .moves_at_level(level)
+            .into_iter()
+            .map(|move_id| LearnedMove {
+                move_id: move_id.to_owned(),
+                pp: database.moves[move_id].pp,
+            })
+            .collect();
+
+        let mut creature = Creature {
+            species: species_id.to_owned(),
+            nickname: None,
+            level,
+            experience: experience_for_level(level),
+            ivs,
+            evs: Stats::default(),
+            hp: 0,
+            moves,
+            status: None,
+        };
+        creature.hp = creature.stats(database).hp;
+        creature
+    }
+
+    /// Name to display: the nickname or the species name.
+    pub fn name<'a>(&'a self, database: &'a SpeciesDatabase) -> &'a str {
+        self.nickname
+            .as_ref()
+            .map(String::as_str)
+            .unwrap_or_else(|| database.species[&self.species].name.as_str())
+    }
+
+    /// Compute the actual stats from the species base stats, individual values, effort values and level.
+    pub fn stats(&self, database: &SpeciesDatabase) -> Stats {
+        let base = database.species[&self.species].base_stats;
+        let stat = |base: u32, iv: u32, ev: u32| (2 * base + iv + ev / 4) * self.level / 100 + 5;
+        Stats {
+            hp: (2 * base.hp + self.ivs.hp + self.evs.hp / 4) * self.level / 100 + self.level + 10,
+            attack: stat(base.attack, self.ivs.attack, self.evs.attack),
+            defense: stat(base.defense, self.ivs.defense, self.evs.defense),
+            special_attack: stat(
+                base.special_attack,
+                self.ivs.special_attack,
+                self.evs.special_attack,
+            ),
+            special_defense: stat(
+                base.special_defense,
+                self.ivs.special_defense,
+                self.evs.special_defense,
+            ),
+            speed: stat(base.speed, self.ivs.speed, self.evs.speed),
+        }
+    }
+
+    pub fn is_fainted(&self) -> bool {
+        self.hp == 0
+    }
+
+    /// Restore HP, PP and status.
+    pub fn heal(&mut self, database: &SpeciesDatabase) {
+        self.hp = self.stats(database).hp;
+        self.status = None;
+        for learned in &mut self.moves {
+            learned.pp = database.moves[&learned.move_id].pp;
+        }
+    }
+}
+
+/// Total experience needed to reach a level.
+pub fn experience_for_level(level: u32) -> u32 {
+    level * level * level
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> SpeciesDatabase {
+        ron::de::from_str(
+            r#"(
+                species: {
+                    "ferris": (
+                        name: "Ferris",
+                        types: [Normal],
+                        base_stats: (hp: 40, attack: 50, defense: 60, special_attack: 70, special_defense: 80, speed: 90),
+                        learnset: [
+                            (level: 1, move_id: "pinch"),
+                            (level: 4, move_id: "shell"),
+                            (level: 8, move_id: "bubble"),
+                            (level: 12, move_id: "claw"),
+                            (level: 20, move_id: "crush"),
+                        ],
+                        sprite: (sheet: File(texture: "ferris.png", definition: "ferris.ron"), index: 0),
+                        catch_rate: 200,
+                        base_experience: 50,
+                    ),
+                },
+                moves: {
+                    "pinch": (name: "Pinch", move_type: Normal, category: Physical, power: 40, accuracy: 100, pp: 35),
+                    "shell": (name: "Shell", move_type: Normal, category: Status, power: 0, accuracy: 100, pp: 20),
+                    "bubble": (name: "Bubble", move_type: Water, category: Special, power: 40, accuracy: 100, pp: 30),
+                    "claw": (name: "Claw", move_type: Normal, category: Physical, power: 60, accuracy: 95, pp: 25),
+                    "crush": (name: "Crush", move_type: Normal, category: Physical, power: 80, accuracy: 90, pp: 15),
+                },
+            )"#,
+        )
+        .unwrap()
+    }
+
+    fn moves(creature: &Creature) -> Vec<(&str, u32)> {
+        creature
+            .moves
+            .iter()
+            .map(|learned| (learned.move_id.as_str(), learned.pp))
+            .collect()
+    }
+
+    #[test]
+    fn new_creatures_know_their_last_moves_with_full_pp() {
+        let database = database();
+        let creature = Creature::new("ferris", 10, &database, &mut Rng::new(3));
+        assert_eq!(
+            moves(&creature),
+            [("pinch", 35), ("shell", 20), ("bubble", 30)]
+        );
+        let creature = Creature::new("ferris", 30, &database, &mut Rng::new(3));
+        assert_eq!(
+            moves(&creature),
+            [("shell", 20), ("bubble", 30), ("claw", 25), ("crush", 15)]
+        );
+        assert_eq!(creature.hp, creature.stats(&database).hp);
+        assert_eq!(creature.experience, experience_for_level(30));
+        assert_eq!(creature.status, None);
+    }
+
+    #[test]
+    fn new_creatures_have_a_valid_level_and_individual_values() {
+        let database = database();
+        let mut rng = Rng::new(5);
+        assert_eq!(Creature::new("ferris", 0, &database, &mut rng).level, 1);
+        assert_eq!(
+            Creature::new("ferris", MAX_LEVEL + 1, &database, &mut rng).level,
+            MAX_LEVEL
+        );
+        for _ in 0..100 {
+            let ivs = Creature::new("ferris", 5, &database, &mut rng).ivs;
+            for &iv in &[
+                ivs.hp,
+                ivs.attack,
+                ivs.defense,
+                ivs.special_attack,
+                ivs.special_defense,
+                ivs.speed,
+            ] {
+                assert!(iv <= MAX_IV);
+            }
+        }
+    }
+
+    #[test]
+    fn stats_grow_with_level_individual_and_effort_values() {
+        let database = database();
+        let mut creature = Creature::new("ferris", 50, &database, &mut Rng::new(1));
+        creature.ivs = Stats::default();
+        assert_eq!(
+            creature.stats(&database),
+            Stats {
+                hp: 100,
+                attack: 55,
+                defense: 65,
+                special_attack: 75,
+                special_defense: 85,
+                speed: 95,
+            }
+        );
+
+        creature.level = 100;
+        creature.ivs.attack = MAX_IV;
+        creature.evs.speed = 252;
+        let stats = creature.stats(&database);
+        assert_eq!(stats.hp, 190);
+        assert_eq!(stats.attack, 136);
+        assert_eq!(stats.speed, 248);
+    }
+
+    #[test]
+    fn experience_grows_with_the_cube_of_the_level() {
+        assert_eq!(experience_for_level(1), 1);
+        assert_eq!(experience_for_level(10), 1000);
+        assert_eq!(experience_for_level(MAX_LEVEL), 1_000_000);
+    }
+}
@@ -0,0 +1,7 @@
+mod creature;
+mod species;
+
+pub use self::{
+    creature::{Creature, LearnedMove, Status, MAX_MOVES},
+    species::{CreatureType, MoveCategory, MoveEffect, SpeciesDatabase, Stats, StatusEffect},
+};
@@ -0,0 +1,341 @@
+use super::MAX_MOVES;
+use crate::maps::SpriteSheetDefinition;
+use amethyst::{
+    assets::{Asset, Handle, ProcessingState, Result},
+    ecs::prelude::VecStorage,
+};
+use serde_derive::*;
+use std::{collections::HashMap, error::Error, fmt};
+
+/// Every species and move of the game, loaded from a RON file.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SpeciesDatabase {
+    /// Species, by id.
+    pub species: HashMap<String, Species>,
+    /// Moves the species can learn, by id.
+    pub moves: HashMap<String, MoveDefinition>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Species {
+    pub name: String,
+    /// One or two types.
+    pub types: Vec<CreatureType>,
+    pub base_stats: Stats,
+    /// Moves learnt when reaching a level, sorted by level.
+    #[serde(default)]
+    pub learnset: Vec<LearnsetEntry>,
+    #[serde(default)]
+    pub evolution: Option<Evolution>,
+    pub sprite: SpeciesSprite,
+    /// From 1 (hardest) to 255 (easiest).
+    pub catch_rate: u8,
+    /// Base experience given when defeated.
+    pub base_experience: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CreatureType {
+    Normal,
+    Fire,
+    Water,
+    Grass,
+    Electric,
+    Ground,
+    Flying,
+    Bug,
+}
+
+/// Base stats of a species, or individual stats of a creature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    pub hp: u32,
+    pub attack: u32,
+    pub defense: u32,
+    pub special_attack: u32,
+    pub special_defense: u32,
+    pub speed: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LearnsetEntry {
+    pub level: u32,
+    /// Id of the move in `SpeciesDatabase::moves`.
+    pub move_id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Evolution {
+    /// Id of the species evolved into.
+    pub species: String,
+    pub level: u32,
+}
+
+/// Sprite shown in battles and menus.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpeciesSprite {
+    pub sheet: SpriteSheetDefinition,
+    pub index: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MoveDefinition {
+    pub name: String,
+    pub move_type: CreatureType,
+    pub category: MoveCategory,
+    /// 0 for moves which do not deal damage.
+    pub power: u32,
+    /// Chance to hit, in percent.
+    pub accuracy: u32,
+    /// Number of times the move can be used before resting.
+    pub pp: u32,
+    /// Status inflicted to the target when the move hits.
+    #[serde(default)]
+    pub effect: Option<MoveEffect>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MoveEffect {
+    pub status: StatusEffect,
+    /// Chance to inflict the status, in percent.
+    pub chance: u32,
+}
+
+/// Status a move can inflict, see `Status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusEffect {
+    Poison,
+    Burn,
+    Paralysis,
+    Sleep,
+    Freeze,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MoveCategory {
+    /// Uses attack and defense.
+    Physical,
+    /// Uses special attack and special defense.
+    Special,
+    /// Does not deal damage.
+    Status,
+}
+
+/// An invalid reference inside the species database.
+#[derive(Debug)]
+pub enum SpeciesError {
+    /// The species learns a move which does not exist.
+    UnknownMove { species: String, move_id: String },
+    /// The species evolves into a species which does not exist.
+    UnknownEvolution { species: String, evolution: String },
+    /// The species does not have one or two types.
+    InvalidTypes { species: String },
+    /// The species' learnset is not sorted by level.
+    UnsortedLearnset { species: String },
+}
+
+impl SpeciesDatabase {
+    pub fn species(&self, id: &str) -> Option<&Species> {
+        self.species.get(id)
+    }
+
+    pub fn move_definition(&self, id: &str) -> Option<&MoveDefinition> {
+        self.moves.get(id)
+    }
+
+    /// Check that every move and evolution referenced by a species exists,
+    /// and that learnsets are sorted by level.
+    pub fn validate(&self) -> std::result::Result<(), Vec<SpeciesError>> {
+        let mut errors = Vec::new();
+
+        for (id, species) in &self.species {
+            if species.types.is_empty() || species.types.len() > 2 {
+                errors.push(SpeciesError::InvalidTypes {
+                    species: id.clone(),
+                });
+            }
+
+            if species
+                .learnset
+                .windows(2)
+                .any(|entries| entries[0].level > entries[1].level)
+            {
+                errors.push(SpeciesError::UnsortedLearnset {
+                    species: id.clone(),
+                });
+            }
+
+            for entry in &species.learnset {
+                if !self.moves.contains_key(&entry.move_id) {
+                    errors.push(SpeciesError::UnknownMove {
+                        species: id.clone(),
+                        move_id: entry.move_id.clone(),
+                    });
+                }
+            }
+
+            if let Some(evolution) = &species.evolution {
+                if !self.species.contains_key(&evolution.species) {
+                    errors.push(SpeciesError::UnknownEvolution {
+                        species: id.clone(),
+                        evolution: evolution.species.clone(),
+                    });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Species {
+    /// Moves known by a wild creature of this species: the last ones learnt up to `level`.
+    pub fn moves_at_level(&self, level: u32) -> Vec<&str> {
+        let learnt = self
+            .learnset
+            .iter()
+            .filter(|entry| entry.level <= level)
+            .map(|entry| entry.move_id.as_str())
+            .collect::<Vec<_>>();
+        let skip = learnt.len().saturating_sub(MAX_MOVES);
+        learnt.into_iter().skip(skip).collect()
+    }
+}
+
+impl fmt::Display for SpeciesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpeciesError::UnknownMove { species, move_id } => {
+                write!(f, "species '{}': unknown move '{}'", species, move_id)
+            }
+            SpeciesError::UnknownEvolution { species, evolution } => write!(
+                f,
+                "species '{}': evolves into unknown species '{}'",
+                species, evolution
+            ),
+            SpeciesError::InvalidTypes { species } => {
+                write!(f, "species '{}': expected one or two types", species)
+            }
+            SpeciesError::UnsortedLearnset { species } => {
+                write!(
+                    f,
+                    "species '{}': the learnset is not sorted by level",
+                    species
+                )
+            }
+        }
+    }
+}
+
+impl Error for SpeciesError {}
+
+impl Asset for SpeciesDatabase {
+    const NAME: &'static str = "rustymon::SpeciesDatabase";
+    type Data = Self;
+    type HandleStorage = VecStorage<Handle<Self>>;
+}
+
+impl From<SpeciesDatabase> for Result<ProcessingState<SpeciesDatabase>> {
+    fn from(database: SpeciesDatabase) -> Self {
+        Ok(ProcessingState::Loaded(database))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A species learning a move at each of the given levels, along with the moves.
+    fn database(levels: &[u32]) -> SpeciesDatabase {
+        let learnset = levels
+            .iter()
+            .map(|level| format!("(level: {}, move_id: \"move_{}\")", level, level))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let moves = levels
+            .iter()
+            .map(|level| {
+                format!(
+                    "\"move_{}\": (name: \"Move\", move_type: Normal, category: Physical, \
+                     power: 40, accuracy: 100, pp: {})",
+                    level, level
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let database = format!(
+            r#"(
+                species: {{
+                    "ferris": (
+                        name: "Ferris",
+                        types: [Normal],
+                        base_stats: (hp: 40, attack: 50, defense: 60, special_attack: 70, special_defense: 80, speed: 90),
+                        learnset: [{}],
+                        sprite: (sheet: File(texture: "ferris.png", definition: "ferris.ron"), index: 0),
+                        catch_rate: 200,
+                        base_experience: 50,
+                    ),
+                }},
+                moves: {{ {} }},
+            )"#,
+            learnset, moves
+        );
+        ron::de::from_str(&database).unwrap()
+    }
+
+    #[test]
+    fn wild_creatures_know_the_last_moves_learnt() {
+        let database = database(&[1, 3, 5, 7, 9, 11]);
+        let species = &database.species["ferris"];
+        assert_eq!(species.moves_at_level(1), ["move_1"]);
+        assert_eq!(
+            species.moves_at_level(8),
+            ["move_1", "move_3", "move_5", "move_7"]
+        );
+        assert_eq!(
+            species.moves_at_level(100),
+            ["move_5", "move_7", "move_9", "move_11"]
+        );
+    }
+
+    #[test]
+    fn valid_databases_are_accepted() {
+        assert!(database(&[1, 3, 3, 5]).validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_species_are_reported() {
+        let mut database = database(&[1, 5, 3]);
+        {
+            let species = database.species.get_mut("ferris").unwrap();
+            species.types.clear();
+            species.learnset.push(LearnsetEntry {
+                level: 10,
+                move_id: "splash".to_owned(),
+            });
+            species.evolution = Some(Evolution {
+                species: "crab".to_owned(),
+                level: 16,
+            });
+        }
+        let errors = database
+            .validate()
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "species 'ferris': expected one or two types",
+                "species 'ferris': the learnset is not sorted by level",
+                "species 'ferris': unknown move 'splash'",
+                "species 'ferris': evolves into unknown species 'crab'",
+            ]
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use crate::{creatures::SpeciesDatabase, items::ItemDatabase};
+use amethyst::{
+    assets::{Asset, Handle, ProcessingState, Result},
+    ecs::prelude::VecStorage,
+};
+use serde_derive::*;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+};
+
+/// Every dialogue of the game, loaded from a RON file.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct DialogueDatabase {
+    /// Dialogues, by id.
+    pub dialogues: HashMap<String, Dialogue>,
+}
+
+/// A dialogue tree. The conversation starts at the `start` node and goes from node to node
+/// until a node ends it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Dialogue {
+    pub start: String,
+    /// Nodes, by id.
+    pub nodes: HashMap<String, DialogueNode>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DialogueNode {
+    /// Said by the NPC. Nodes without text only run their actions and move on.
+    #[serde(default)]
+    pub text: Option<String>,
+    /// Run in order when entering the node.
+    #[serde(default)]
+    pub actions: Vec<DialogueAction>,
+    #[serde(default)]
+    pub next: DialogueNext,
+}
+
+/// Where the conversation goes after a node.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum DialogueNext {
+    End,
+    Goto(String),
+    /// Let the player answer. Choices whose condition is not met are hidden.
+    Choices(Vec<DialogueChoice>),
+    /// Go to the node of the first branch whose condition is met, or to `otherwise`.
+    /// The conversation ends if there is nowhere to go.
+    Branch {
+        branches: Vec<(Condition, String)>,
+        #[serde(default)]
+        otherwise: Option<String>,
+    },
+}
+
+impl Default for DialogueNext {
+    fn default() -> Self {
+        DialogueNext::End
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DialogueChoice {
+    pub text: String,
+    #[serde(default)]
+    pub condition: Option<Condition>,
+    /// The node the choice leads to, None ends the conversation.
+    #[serde(default)]
+    pub node: Option<String>,
+}
+
+/// A test on the player's progress.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Condition {
+    FlagSet(String),
+    FlagUnset(String),
+    /// The player has at least `count` of the item.
+    HasItem {
+        item: String,
+        count: u32,
+    },
+    /// Every condition is met.
+    All(Vec<Condition>),
+}
+
+/// Something happening when a node is entered.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum DialogueAction {
+    SetFlag(String),
+    UnsetFlag(String),
+    GiveItem {
+        item: String,
+        count: u32,
+    },
+    /// Take items from the player, as many as they have if they have less than `count`.
+    TakeItem {
+        item: String,
+        count: u32,
+    },
+    /// Restore the HP, PP and status of the whole party.
+    HealParty,
+    /// Fight the NPC's creature once the conversation ends. `won_flag` is set if the player wins.
+    StartBattle {
+        species: String,
+        level: u32,
+        #[serde(default)]
+        won_flag: Option<String>,
+    },
+}
+
+/// An invalid reference inside the dialogue database.
+#[derive(Debug)]
+pub struct DialogueError {
+    pub dialogue: String,
+    pub kind: DialogueErrorKind,
+}
+
+#[derive(Debug)]
+pub enum DialogueErrorKind {
+    /// A node, or the start of the dialogue, points to a node which does not exist.
+    UnknownNode(String),
+    UnknownItem(String),
+    UnknownSpecies(String),
+    /// Nodes without text lead back to this node without waiting for the player,
+    /// so the conversation could go round forever.
+    Loop(String),
+}
+
+impl DialogueDatabase {
+    pub fn dialogue(&self, id: &str) -> Option<&Dialogue> {
+        self.dialogues.get(id)
+    }
+
+    /// Check that every node, item and species referenced by the dialogues exists,
+    /// and that the nodes without text do not loop.
+    pub fn validate(
+        &self,
+        species: &SpeciesDatabase,
+        items: &ItemDatabase,
+    ) -> std::result::Result<(), Vec<DialogueError>> {
+        let mut errors = Vec::new();
+
+        for (id, dialogue) in &self.dialogues {
+            let mut error = |kind| {
+                errors.push(DialogueError {
+                    dialogue: id.clone(),
+                    kind,
+                })
+            };
+
+            let mut nodes = vec![&dialogue.start];
+            let mut items_used = Vec::new();
+            for node in dialogue.nodes.values() {
+                match &node.next {
+                    DialogueNext::End => (),
+                    DialogueNext::Goto(next) => nodes.push(next),
+                    DialogueNext::Choices(choices) => {
+                        for choice in choices {
+                            nodes.extend(&choice.node);
+                            if let Some(condition) = &choice.condition {
+                                condition.items(&mut items_used);
+                            }
+                        }
+                    }
+                    DialogueNext::Branch {
+                        branches,
+                        otherwise,
+                    } => {
+                        for (condition, next) in branches {
+                            condition.items(&mut items_used);
+                            nodes.push(next);
+                        }
+                        nodes.extend(otherwise);
+                    }
+                }
+
+                for action in &node.actions {
+                    match action {
+                        DialogueAction::GiveItem { item, .. }
+                        | DialogueAction::TakeItem { item, .. } => items_used.push(item),
+                        DialogueAction::StartBattle {
+                            species: species_id,
+                            ..
+                        } if species.species(species_id).is_none() => {
+                            error(DialogueErrorKind::UnknownSpecies(species_id.clone()))
+                        }
+                        _ => (),
+                    }
+                }
+            }
+
+            for node in nodes {
+                if !dialogue.nodes.contains_key(node) {
+                    error(DialogueErrorKind::UnknownNode(node.clone()));
+                }
+            }
+            for item in items_used {
+                if items.item(item).is_none() {
+                    error(DialogueErrorKind::UnknownItem(item.clone()));
+                }
+            }
+            for node in dialogue.loops() {
+                error(DialogueErrorKind::Loop(node.clone()));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Dialogue {
+    /// A dialogue of a single node, for what is said without being written in the database.
+    pub fn single(text: Option<String>, actions: Vec<DialogueAction>) -> Self {
+        let start = "start".to_owned();
+        let node = DialogueNode {
+            text,
+            actions,
+            next: DialogueNext::End,
+        };
+        Dialogue {
+            nodes: vec![(start.clone(), node)].into_iter().collect(),
+            start,
+        }
+    }
+
+    /// The nodes reached again by following nodes without text, one for each loop.
+    fn loops(&self) -> Vec<&String> {
+        // Nodes moving on by themselves, along with where they may go.
+        let automatic = |id: &String| -> Vec<&String> {
+            match self.nodes.get(id) {
+                Some(node) if node.text.is_none() => match &node.next {
+                    DialogueNext::Goto(next) => vec![next],
+                    DialogueNext::Branch {
+                        branches,
+                        otherwise,
+                    } => branches
+                        .iter()
+                        .map(|(_, next)| next)
+                        .chain(otherwise)
+                        .collect(),
+                    DialogueNext::End | DialogueNext::Choices(_) => Vec::new(),
+                },
+                _ => Vec::new(),
+            }
+        };
+
+        let mut ids = self.nodes.keys().collect::<Vec<_>>();
+        ids.sort();
+        let mut loops = Vec::new();
+        let mut visited = HashSet::new();
+        for start in ids {
+            if visited.contains(start) {
+                continue;
+            }
+            // Depth first search, the path holds the nodes being explored and their next nodes.
+            let mut path = vec![(start, automatic(start))];
+            visited.insert(start);
+            while let Some((_, next)) = path.last_mut() {
+                let node = match next.pop() {
+                    Some(node) => node,
+                    None => {
+                        path.pop();
+                        continue;
+                    }
+                };
+                if path.iter().any(|(id, _)| *id == node) {
+                    loops.push(node);
+                } else if visited.insert(node) {
+                    path.push((node, automatic(node)));
+                }
+            }
+        }
+        loops
+    }
+}
+
+impl Condition {
+    /// Collect the items the condition refers to.
+    fn items<'a>(&'a self, items: &mut Vec<&'a String>) {
+        match self {
+            Condition::HasItem { item, .. } => items.push(item),
+            Condition::All(conditions) => {
+                for condition in conditions {
+                    condition.items(items);
+                }
+            }
+            Condition::FlagSet(_) | Condition::FlagUnset(_) => (),
+        }
+    }
+}
+
+impl fmt::Display for DialogueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dialogue '{}': ", self.dialogue)?;
+        match &self.kind {
+            DialogueErrorKind::UnknownNode(id) => write!(f, "unknown node '{}'", id),
+            DialogueErrorKind::UnknownItem(id) => write!(f, "unknown item id '{}'", id),
+            DialogueErrorKind::UnknownSpecies(id) => write!(f, "unknown species id '{}'", id),
+            DialogueErrorKind::Loop(id) => write!(f, "nodes without text loop back to '{}'", id),
+        }
+    }
+}
+
+impl Error for DialogueError {}
+
+impl Asset for DialogueDatabase {
+    const NAME: &'static str = "rustymon::DialogueDatabase";
+    type Data = Self;
+    type HandleStorage = VecStorage<Handle<Self>>;
+}
+
+impl From<DialogueDatabase> for Result<ProcessingState<DialogueDatabase>> {
+    fn from(database: DialogueDatabase) -> Self {
+        Ok(ProcessingState::Loaded(database))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(dialogues: &str) -> std::result::Result<(), Vec<DialogueError>> {
+        let database: DialogueDatabase = ron::de::from_str(dialogues).unwrap();
+        database.validate(&SpeciesDatabase::default(), &ItemDatabase::default())
+    }
+
+    fn loops(dialogues: &str) -> Vec<String> {
+        let mut loops = validate(dialogues)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| match error.kind {
+                DialogueErrorKind::Loop(node) => node,
+                kind => panic!("unexpected error {:?}", kind),
+            })
+            .collect::<Vec<_>>();
+        loops.sort();
+        loops
+    }
+
+    #[test]
+    fn nodes_with_text_or_choices_may_loop() {
+        let dialogues = r#"(
+            dialogues: {
+                "greeting": (
+                    start: "hello",
+                    nodes: {
+                        "hello": (text: Some("Hello!"), next: Goto("check")),
+                        "check": (
+                            actions: [SetFlag("met")],
+                            next: Branch(branches: [(FlagSet("met"), "again")], otherwise: Some("hello")),
+                        ),
+                        "again": (next: Choices([(text: "Again?", node: Some("hello")), (text: "Bye")])),
+                    },
+                ),
+            },
+        )"#;
+        assert!(validate(dialogues).is_ok());
+    }
+
+    #[test]
+    fn loops_of_nodes_without_text_are_reported() {
+        let dialogues = r#"(
+            dialogues: {
+                "goto": (
+                    start: "a",
+                    nodes: {
+                        "a": (next: Goto("b")),
+                        "b": (actions: [SetFlag("b")], next: Goto("a")),
+                    },
+                ),
+                "branch": (
+                    start: "check",
+                    nodes: {
+                        "check": (
+                            next: Branch(branches: [(FlagSet("done"), "end")], otherwise: Some("again")),
+                        ),
+                        "again": (actions: [UnsetFlag("done")], next: Goto("check")),
+                        "end": (text: Some("Done.")),
+                    },
+                ),
+                "itself": (
+                    start: "start",
+                    nodes: {
+                        "start": (text: Some("Hi."), next: Goto("self")),
+                        "self": (next: Goto("self")),
+                    },
+                ),
+            },
+        )"#;
+        assert_eq!(loops(dialogues), ["a", "again", "self"]);
+    }
+
+    #[test]
+    fn single_node_dialogues_are_valid() {
+        let mut database = DialogueDatabase::default();
+        database.dialogues.insert(
+            "chest".to_owned(),
+            Dialogue::single(None, vec![DialogueAction::SetFlag("opened".to_owned())]),
+        );
+        let validation = database.validate(&SpeciesDatabase::default(), &ItemDatabase::default());
+        assert!(validation.is_ok());
+    }
+}
@@ -0,0 +1,16 @@
+mod definition;
+
+pub use self::definition::{Condition, Dialogue, DialogueAction, DialogueDatabase, DialogueNext};
+
+use crate::{items::Inventory, resources::Flags};
+
+impl Condition {
+    pub fn is_met(&self, flags: &Flags, inventory: &Inventory) -> bool {
+        match self {
+            Condition::FlagSet(flag) => flags.is_set(flag),
+            Condition::FlagUnset(flag) => !flags.is_set(flag),
+            Condition::HasItem { item, count } => inventory.count(item) >= *count,
+            Condition::All(conditions) => conditions.iter().all(|c| c.is_met(flags, inventory)),
+        }
+    }
+}
@@ -2,24 +2,20 @@ use crate::{
     animations::HeroAnimationId,
     assets,
     components::{
-        Active, Body, CameraTarget, Dynamic, Hero, InstanceCompat, Mob, OverworldCompat, Portal,
-        Shape, Velocity,
+        Active, Body, CameraTarget, Dynamic, Hero, Interactable, Interpolation, Layer, LayerId,
+        Mob, NpcBattle, Shape, Tilemap, TransitionOverlay, Velocity,
     },
-    states::Instance,
+    transition::{OverlayFrames, OVERLAY_SIZE},
 };
 
 use amethyst::{
     animation::AnimationSet,
-    assets::Handle,
     core::transform::Transform,
     ecs::prelude::*,
-    renderer::{Camera, DisplayConfig, Projection, SpriteRender, SpriteSheet},
+    renderer::{Camera, DisplayConfig, Hidden, Projection, SpriteRender, Transparent},
 };
 
-use ncollide2d::{
-    math::Vector,
-    shape::{Ball, Cuboid},
-};
+use ncollide2d::shape::Ball;
 
 pub const CAM_Z_POS: f32 = 1.0;
 
@@ -35,8 +31,7 @@ pub fn build_camera(display_config: &DisplayConfig, world: &mut World, target: E
     world
         .create_entity()
         .with(Active)
-        .with(OverworldCompat)
-        .with(InstanceCompat)
+        .with(Layer::new(LayerId::HERO))
         .with(Camera::from(Projection::orthographic(
             -half_width,
             half_width,
@@ -48,6 +43,27 @@ pub fn build_camera(display_config: &DisplayConfig, world: &mut World, target: E
         .build()
 }
 
+/// Build the overlay drawing screen transitions, stretched over the whole screen.
+/// It is not part of any scene and stays hidden until a transition is played.
+pub fn build_transition_overlay(display_config: &DisplayConfig, world: &mut World) -> Entity {
+    let (width, height) = display_config.dimensions.unwrap();
+    let frames = OverlayFrames::build(world);
+
+    let mut transform = Transform::default();
+    transform.set_scale(
+        width as f32 / f32::from(OVERLAY_SIZE),
+        height as f32 / f32::from(OVERLAY_SIZE),
+        1.0,
+    );
+    world
+        .create_entity()
+        .with(TransitionOverlay { frames })
+        .with(transform)
+        .with(Transparent)
+        .with(Hidden)
+        .build()
+}
+
 /// Build the hero.
 pub fn build_hero(
     animations: AnimationSet<HeroAnimationId, SpriteRender>,
@@ -60,8 +76,7 @@ pub fn build_hero(
     world
         .create_entity()
         .with(Active)
-        .with(OverworldCompat)
-        .with(InstanceCompat)
+        .with(Layer::new(LayerId::HERO))
         .with(Hero::new())
         .with(animations)
         .with(SpriteRender {
@@ -70,96 +85,133 @@ pub fn build_hero(
         })
         .with(Transform::default())
         .with(Velocity::new())
+        .with(Interpolation::new())
         .with(Body {
             shape: Shape::Circle {
                 shape: Ball::new(16.0),
             },
             dynamic: Dynamic::Dynamic,
+            sensor: false,
         })
         .build()
 }
 
-/// Build a ferris at a given position.
-pub fn build_ferris(
+/// Build a mob at its spawn position.
+pub fn build_mob(
+    mob: Mob,
+    sprite: Option<SpriteRender>,
+    body: Option<Body>,
+    world: &mut World,
+) -> Entity {
+    let mut transform = Transform::default();
+    transform.set_xyz(mob.spawn.0, mob.spawn.1, 0.0);
+
+    let mut builder = world
+        .create_entity()
+        .with(Active)
+        .with(Layer::new(LayerId::OVERWORLD))
+        .with(mob)
+        .with(transform)
+        .with(Velocity::new())
+        .with(Interpolation::new());
+
+    if let Some(sprite) = sprite {
+        builder = builder.with(sprite);
+    }
+    if let Some(body) = body {
+        builder = builder.with(body);
+    }
+
+    builder.build()
+}
+
+/// Build a prop (building, tree, rock...).
+/// This is a decoration which might also be an obstacle if it has a body.
+pub fn build_prop(
     x: f32,
     y: f32,
-    reset_threshold: f32,
-    target_threshold: f32,
-    sprite_sheet: Handle<SpriteSheet>,
+    sprite: Option<SpriteRender>,
+    body: Option<Body>,
     world: &mut World,
-) {
+) -> Entity {
     let mut transform = Transform::default();
     transform.set_xyz(x, y, 0.0);
 
-    world
+    let mut builder = world
         .create_entity()
         .with(Active)
-        .with(OverworldCompat)
-        .with(Mob::new(x, y, reset_threshold, target_threshold))
-        .with(SpriteRender {
-            sprite_sheet,
-            sprite_number: 0,
-        })
-        .with(transform)
-        .with(Velocity::new())
-        .with(Body {
-            shape: Shape::Circle {
-                shape: Ball::new(16.0),
-            },
-            dynamic: Dynamic::Dynamic,
-        })
-        .build();
+        .with(Layer::new(LayerId::OVERWORLD))
+        .with(transform);
+
+    if let Some(sprite) = sprite {
+        builder = builder.with(sprite);
+    }
+    if let Some(body) = body {
+        builder = builder.with(body);
+    }
+
+    builder.build()
 }
 
-/// Build a building.
-/// This is a static physical entity.
-pub fn build_building(x: f32, y: f32, sprite_sheet: Handle<SpriteSheet>, world: &mut World) {
+/// Build the sprite of a creature fighting in a battle.
+/// The battle state deletes it when the battle ends.
+pub fn build_battle_sprite(x: f32, y: f32, sprite: SpriteRender, world: &mut World) -> Entity {
     let mut transform = Transform::default();
     transform.set_xyz(x, y, 0.0);
 
     world
         .create_entity()
-        .with(Active)
-        .with(OverworldCompat)
-        .with(SpriteRender {
-            sprite_sheet,
-            sprite_number: 0,
-        })
+        .with(Layer::new(LayerId::BATTLE))
         .with(transform)
-        .with(Body {
-            shape: Shape::Box {
-                shape: Cuboid::new(Vector::new(32.0, 16.0)),
-            },
-            dynamic: Dynamic::Static,
-        })
-        .build();
+        .with(sprite)
+        .build()
 }
 
-/// Build a portal.
-/// When triggered a portal will bring the player into the pointed instance.
-pub fn build_portal(
+/// Build an entity the hero can interact with (portal, NPC, chest, sign...).
+pub fn build_interactable(
     x: f32,
     y: f32,
-    instance: Instance,
-    trigger_zone: Cuboid<f32>,
-    sprite_sheet: Handle<SpriteSheet>,
+    interactable: Interactable,
+    sprite: Option<SpriteRender>,
+    body: Option<Body>,
     world: &mut World,
-) {
+) -> Entity {
+    let mut transform = Transform::default();
+    transform.set_xyz(x, y, 0.0);
+
+    let mut builder = world
+        .create_entity()
+        .with(Active)
+        .with(Layer::new(LayerId::OVERWORLD))
+        .with(transform)
+        .with(interactable);
+
+    if let Some(sprite) = sprite {
+        builder = builder.with(sprite);
+    }
+    if let Some(body) = body {
+        builder = builder.with(body);
+    }
+
+    builder.build()
+}
+
+/// Build the opponent of a battle started by a dialogue.
+/// It is not part of any scene, the battle state deletes it when the battle ends.
+pub fn build_npc_battle(battle: NpcBattle, world: &mut World) -> Entity {
+    world.create_entity().with(battle).build()
+}
+
+/// Build a tilemap whose bottom left corner is at the given position.
+pub fn build_tilemap(x: f32, y: f32, tilemap: Tilemap, world: &mut World) -> Entity {
     let mut transform = Transform::default();
     transform.set_xyz(x, y, 0.0);
 
     world
         .create_entity()
         .with(Active)
-        .with(OverworldCompat)
-        .with(SpriteRender {
-            sprite_sheet,
-            sprite_number: 1,
-        })
+        .with(Layer::new(LayerId::OVERWORLD))
         .with(transform)
-        .with(Portal {
-            instance,
-            trigger_zone,
-        })
-        .build();
+        .with(tilemap)
+        .build()
 }
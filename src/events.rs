@@ -0,0 +1,20 @@
+use amethyst::ecs::prelude::Entity;
+
+/// Published by the `PhysicsSystem` into an `EventChannel<CollisionEvent>`
+/// each time two bodies, sensors included, touch each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CollisionEvent {
+    pub kind: CollisionEventKind,
+    /// The colliding entities, the lowest id first.
+    pub entities: (Entity, Entity),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionEventKind {
+    /// The entities started touching during this frame.
+    Started,
+    /// The entities were already touching during the previous frame.
+    Ongoing,
+    /// The entities stopped touching during this frame.
+    Stopped,
+}
@@ -0,0 +1,77 @@
+use crate::resources::SimulationTime;
+use amethyst::{
+    core::{bundle::SystemBundle, timing::Time, ArcThreadPool},
+    ecs::prelude::{Dispatcher, DispatcherBuilder, World},
+    DataInit, Error, Result, StateEvent, Trans,
+};
+
+pub type RustymonTrans<'a, 'b> = Trans<RustymonGameData<'a, 'b>, StateEvent>;
+
+/// Game data running the game logic at a fixed rate, independently of the rendering.
+/// Each frame, the simulation dispatcher runs as many ticks as needed to catch up with time,
+/// then the frame dispatcher runs once (input, interpolation, rendering...).
+pub struct RustymonGameData<'a, 'b> {
+    simulation: Dispatcher<'a, 'b>,
+    frame: Dispatcher<'a, 'b>,
+}
+
+impl<'a, 'b> RustymonGameData<'a, 'b> {
+    /// Run the simulation ticks due since the last frame, then the frame systems.
+    pub fn update(&mut self, world: &World) {
+        let delta = world.read_resource::<Time>().delta_seconds();
+        let ticks = world.write_resource::<SimulationTime>().advance(delta);
+        for _ in 0..ticks {
+            self.simulation.dispatch(&world.res);
+        }
+        self.frame.dispatch(&world.res);
+    }
+
+    /// Run only the frame systems, the simulation stays paused.
+    pub fn update_frame(&mut self, world: &World) {
+        self.frame.dispatch(&world.res);
+    }
+}
+
+#[derive(Default)]
+pub struct RustymonGameDataBuilder<'a, 'b> {
+    simulation: DispatcherBuilder<'a, 'b>,
+    frame: DispatcherBuilder<'a, 'b>,
+}
+
+impl<'a, 'b> RustymonGameDataBuilder<'a, 'b> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a bundle whose systems run at each simulation tick.
+    pub fn with_simulation_bundle<B>(mut self, bundle: B) -> Result<Self>
+    where
+        B: SystemBundle<'a, 'b>,
+    {
+        bundle.build(&mut self.simulation).map_err(Error::Core)?;
+        Ok(self)
+    }
+
+    /// Add a bundle whose systems run once per rendered frame.
+    pub fn with_frame_bundle<B>(mut self, bundle: B) -> Result<Self>
+    where
+        B: SystemBundle<'a, 'b>,
+    {
+        bundle.build(&mut self.frame).map_err(Error::Core)?;
+        Ok(self)
+    }
+}
+
+impl<'a, 'b> DataInit<RustymonGameData<'a, 'b>> for RustymonGameDataBuilder<'a, 'b> {
+    fn build(self, world: &mut World) -> RustymonGameData<'a, 'b> {
+        world.add_resource(SimulationTime::default());
+
+        let pool = world.read_resource::<ArcThreadPool>().clone();
+        let mut simulation = self.simulation.with_pool(pool.clone()).build();
+        let mut frame = self.frame.with_pool(pool).build();
+        simulation.setup(&mut world.res);
+        frame.setup(&mut world.res);
+
+        RustymonGameData { simulation, frame }
+    }
+}
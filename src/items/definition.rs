@@ -0,0 +1,422 @@
+use crate::creatures::{Creature, LearnedMove, SpeciesDatabase, StatusEffect, MAX_MOVES};
+use amethyst::{
+    assets::{Asset, Handle, ProcessingState, Result},
+    ecs::prelude::VecStorage,
+};
+use serde_derive::*;
+use std::{collections::HashMap, error::Error, fmt};
+
+/// Every item of the game, loaded from a RON file.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ItemDatabase {
+    /// Items, by id.
+    pub items: HashMap<String, ItemDefinition>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ItemDefinition {
+    pub name: String,
+    pub description: String,
+    pub pocket: Pocket,
+    /// Applied in order when the item is used. Items without effects can't be used.
+    #[serde(default)]
+    pub effects: Vec<ItemEffect>,
+}
+
+/// Where an item is kept in the inventory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Pocket {
+    Medicine,
+    CaptureDevices,
+    /// Machines teaching a move.
+    Machines,
+    /// Items needed to progress, they are never consumed.
+    KeyItems,
+}
+
+impl Pocket {
+    pub const ALL: [Pocket; 4] = [
+        Pocket::Medicine,
+        Pocket::CaptureDevices,
+        Pocket::Machines,
+        Pocket::KeyItems,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pocket::Medicine => "Medicine",
+            Pocket::CaptureDevices => "Capture devices",
+            Pocket::Machines => "Machines",
+            Pocket::KeyItems => "Key items",
+        }
+    }
+}
+
+/// What an item does when used.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ItemEffect {
+    /// Restore HP, without going over the maximum. Fainted creatures can't be healed.
+    Heal { hp: u32 },
+    /// Cure a status, or any status if None.
+    CureStatus { status: Option<StatusEffect> },
+    /// Restore the PP of every move, without going over their maximum.
+    RestorePp { pp: u32 },
+    /// Try to capture the wild creature, multiplying its species catch rate by `bonus`.
+    Capture { bonus: f32 },
+    /// Teach a move to a creature which knows less than `MAX_MOVES` moves.
+    TeachMove { move_id: String },
+}
+
+/// Where an item is used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UseContext {
+    Battle,
+    Overworld,
+}
+
+/// What an effect changed on a creature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EffectOutcome {
+    Healed(u32),
+    StatusCured,
+    PpRestored(u32),
+    MoveLearned(String),
+}
+
+impl ItemEffect {
+    pub fn usable_in(&self, context: UseContext) -> bool {
+        match self {
+            ItemEffect::Heal { .. }
+            | ItemEffect::CureStatus { .. }
+            | ItemEffect::RestorePp { .. } => true,
+            ItemEffect::Capture { .. } => context == UseContext::Battle,
+            ItemEffect::TeachMove { .. } => context == UseContext::Overworld,
+        }
+    }
+
+    /// Apply the effect on a creature. Return None if it changed nothing.
+    /// Capture effects are resolved by the battle and never apply to a creature.
+    pub fn apply(
+        &self,
+        creature: &mut Creature,
+        database: &SpeciesDatabase,
+    ) -> Option<EffectOutcome> {
+        match self {
+            ItemEffect::Heal { hp } => {
+                let max_hp = creature.stats(database).hp;
+                if creature.is_fainted() || creature.hp >= max_hp {
+                    return None;
+                }
+                let healed = (*hp).min(max_hp - creature.hp);
+                creature.hp += healed;
+                Some(EffectOutcome::Healed(healed))
+            }
+            ItemEffect::CureStatus { status } => {
+                let cured = match (creature.status, status) {
+                    (Some(_), None) => true,
+                    (Some(current), Some(effect)) => current.is_caused_by(*effect),
+                    (None, _) => false,
+                };
+                if !cured || creature.is_fainted() {
+                    return None;
+                }
+                creature.status = None;
+                Some(EffectOutcome::StatusCured)
+            }
+            ItemEffect::RestorePp { pp } => {
+                let mut restored = 0;
+                for learned in &mut creature.moves {
+                    let max_pp = database.moves[&learned.move_id].pp;
+                    let gained = (*pp).min(max_pp.saturating_sub(learned.pp));
+                    learned.pp += gained;
+                    restored += gained;
+                }
+                if restored == 0 {
+                    None
+                } else {
+                    Some(EffectOutcome::PpRestored(restored))
+                }
+            }
+            ItemEffect::Capture { .. } => None,
+            ItemEffect::TeachMove { move_id } => {
+                let known = creature.moves.iter().any(|m| &m.move_id == move_id);
+                if known || creature.moves.len() >= MAX_MOVES {
+                    return None;
+                }
+                creature.moves.push(LearnedMove {
+                    move_id: move_id.clone(),
+                    pp: database.moves[move_id].pp,
+                });
+                Some(EffectOutcome::MoveLearned(move_id.clone()))
+            }
+        }
+    }
+}
+
+impl ItemDefinition {
+    /// Can the item be used in this context. Items without effects are never usable.
+    pub fn usable_in(&self, context: UseContext) -> bool {
+        !self.effects.is_empty() && self.effects.iter().all(|e| e.usable_in(context))
+    }
+
+    /// The catch rate multiplier if the item is thrown at wild creatures.
+    pub fn capture_bonus(&self) -> Option<f32> {
+        self.effects.iter().find_map(|effect| match effect {
+            ItemEffect::Capture { bonus } => Some(*bonus),
+            _ => None,
+        })
+    }
+
+    /// Is the item removed from the inventory when used.
+    pub fn is_consumed(&self) -> bool {
+        self.pocket != Pocket::KeyItems
+    }
+
+    /// Apply every effect on a creature and return what changed.
+    pub fn apply(&self, creature: &mut Creature, database: &SpeciesDatabase) -> Vec<EffectOutcome> {
+        self.effects
+            .iter()
+            .filter_map(|effect| effect.apply(creature, database))
+            .collect()
+    }
+
+    /// Would using the item on the creature change anything.
+    pub fn affects(&self, creature: &Creature, database: &SpeciesDatabase) -> bool {
+        !self.apply(&mut creature.clone(), database).is_empty()
+    }
+}
+
+/// An invalid reference inside the item database.
+#[derive(Debug)]
+pub enum ItemError {
+    /// The item teaches a move which does not exist.
+    UnknownMove { item: String, move_id: String },
+}
+
+impl ItemDatabase {
+    pub fn item(&self, id: &str) -> Option<&ItemDefinition> {
+        self.items.get(id)
+    }
+
+    /// Check that every move taught by an item exists.
+    pub fn validate(&self, species: &SpeciesDatabase) -> std::result::Result<(), Vec<ItemError>> {
+        let mut errors = Vec::new();
+
+        for (id, item) in &self.items {
+            for effect in &item.effects {
+                if let ItemEffect::TeachMove { move_id } = effect {
+                    if species.move_definition(move_id).is_none() {
+                        errors.push(ItemError::UnknownMove {
+                            item: id.clone(),
+                            move_id: move_id.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl fmt::Display for ItemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ItemError::UnknownMove { item, move_id } => {
+                write!(f, "item '{}': teaches unknown move '{}'", item, move_id)
+            }
+        }
+    }
+}
+
+impl Error for ItemError {}
+
+impl Asset for ItemDatabase {
+    const NAME: &'static str = "rustymon::ItemDatabase";
+    type Data = Self;
+    type HandleStorage = VecStorage<Handle<Self>>;
+}
+
+impl From<ItemDatabase> for Result<ProcessingState<ItemDatabase>> {
+    fn from(database: ItemDatabase) -> Self {
+        Ok(ProcessingState::Loaded(database))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{creatures::Status, rng::Rng};
+
+    fn database() -> SpeciesDatabase {
+        ron::de::from_str(
+            r#"(
+                species: {
+                    "ferris": (
+                        name: "Ferris",
+                        types: [Normal],
+                        base_stats: (hp: 40, attack: 50, defense: 60, special_attack: 70, special_defense: 80, speed: 90),
+                        learnset: [
+                            (level: 1, move_id: "pinch"),
+                            (level: 4, move_id: "shell"),
+                            (level: 8, move_id: "bubble"),
+                            (level: 12, move_id: "claw"),
+                        ],
+                        sprite: (sheet: File(texture: "ferris.png", definition: "ferris.ron"), index: 0),
+                        catch_rate: 200,
+                        base_experience: 50,
+                    ),
+                },
+                moves: {
+                    "pinch": (name: "Pinch", move_type: Normal, category: Physical, power: 40, accuracy: 100, pp: 35),
+                    "shell": (name: "Shell", move_type: Normal, category: Status, power: 0, accuracy: 100, pp: 20),
+                    "bubble": (name: "Bubble", move_type: Water, category: Special, power: 40, accuracy: 100, pp: 30),
+                    "claw": (name: "Claw", move_type: Normal, category: Physical, power: 60, accuracy: 95, pp: 25),
+                    "crush": (name: "Crush", move_type: Normal, category: Physical, power: 80, accuracy: 90, pp: 15),
+                },
+            )"#,
+        )
+        .unwrap()
+    }
+
+    fn creature(level: u32, database: &SpeciesDatabase) -> Creature {
+        Creature::new("ferris", level, database, &mut Rng::new(1))
+    }
+
+    fn item(pocket: Pocket, effects: Vec<ItemEffect>) -> ItemDefinition {
+        ItemDefinition {
+            name: "Item".to_string(),
+            description: String::new(),
+            pocket,
+            effects,
+        }
+    }
+
+    #[test]
+    fn healing_is_capped_at_the_maximum_hp() {
+        let database = database();
+        let mut creature = creature(10, &database);
+        let max_hp = creature.stats(&database).hp;
+        let heal = ItemEffect::Heal { hp: 20 };
+
+        creature.hp = max_hp - 5;
+        assert_eq!(
+            heal.apply(&mut creature, &database),
+            Some(EffectOutcome::Healed(5))
+        );
+        assert_eq!(creature.hp, max_hp);
+        assert_eq!(heal.apply(&mut creature, &database), None);
+
+        creature.hp = 0;
+        assert_eq!(heal.apply(&mut creature, &database), None);
+        assert_eq!(creature.hp, 0);
+    }
+
+    #[test]
+    fn statuses_are_cured_only_by_the_matching_effect() {
+        let database = database();
+        let mut creature = creature(10, &database);
+        let antidote = ItemEffect::CureStatus {
+            status: Some(StatusEffect::Poison),
+        };
+        let burn_heal = ItemEffect::CureStatus {
+            status: Some(StatusEffect::Burn),
+        };
+        let full_heal = ItemEffect::CureStatus { status: None };
+
+        assert_eq!(full_heal.apply(&mut creature, &database), None);
+
+        creature.status = Some(Status::Poisoned);
+        assert_eq!(burn_heal.apply(&mut creature, &database), None);
+        assert_eq!(creature.status, Some(Status::Poisoned));
+        assert_eq!(
+            antidote.apply(&mut creature, &database),
+            Some(EffectOutcome::StatusCured)
+        );
+        assert_eq!(creature.status, None);
+
+        creature.status = Some(Status::Asleep { turns: 2 });
+        assert_eq!(
+            full_heal.apply(&mut creature, &database),
+            Some(EffectOutcome::StatusCured)
+        );
+        assert_eq!(creature.status, None);
+    }
+
+    #[test]
+    fn pp_are_restored_up_to_the_maximum_of_each_move() {
+        let database = database();
+        let mut creature = creature(10, &database);
+        let ether = ItemEffect::RestorePp { pp: 10 };
+
+        creature.moves[0].pp = 30;
+        creature.moves[2].pp = 0;
+        assert_eq!(
+            ether.apply(&mut creature, &database),
+            Some(EffectOutcome::PpRestored(15))
+        );
+        let pp: Vec<u32> = creature.moves.iter().map(|m| m.pp).collect();
+        assert_eq!(pp, vec![35, 20, 10]);
+
+        creature.moves[2].pp = 30;
+        assert_eq!(ether.apply(&mut creature, &database), None);
+    }
+
+    #[test]
+    fn moves_are_taught_only_to_creatures_with_a_free_slot() {
+        let database = database();
+        let mut creature = creature(1, &database);
+        let teach = |move_id: &str| ItemEffect::TeachMove {
+            move_id: move_id.to_string(),
+        };
+
+        assert_eq!(
+            teach("crush").apply(&mut creature, &database),
+            Some(EffectOutcome::MoveLearned("crush".to_string()))
+        );
+        let learned = creature.moves.last().unwrap();
+        assert_eq!((learned.move_id.as_str(), learned.pp), ("crush", 15));
+        assert_eq!(teach("crush").apply(&mut creature, &database), None);
+
+        let mut full = self::creature(12, &database);
+        assert_eq!(full.moves.len(), MAX_MOVES);
+        assert_eq!(teach("crush").apply(&mut full, &database), None);
+        assert_eq!(full.moves.len(), MAX_MOVES);
+    }
+
+    #[test]
+    fn key_items_are_never_consumed() {
+        let heal = vec![ItemEffect::Heal { hp: 20 }];
+        assert!(item(Pocket::Medicine, heal.clone()).is_consumed());
+        assert!(!item(Pocket::KeyItems, heal).is_consumed());
+    }
+
+    #[test]
+    fn items_are_usable_where_every_effect_is() {
+        let potion = item(Pocket::Medicine, vec![ItemEffect::Heal { hp: 20 }]);
+        let ball = item(
+            Pocket::CaptureDevices,
+            vec![ItemEffect::Capture { bonus: 1.0 }],
+        );
+        let machine = item(
+            Pocket::Machines,
+            vec![ItemEffect::TeachMove {
+                move_id: "crush".to_string(),
+            }],
+        );
+        let keepsake = item(Pocket::KeyItems, Vec::new());
+
+        assert!(potion.usable_in(UseContext::Battle));
+        assert!(potion.usable_in(UseContext::Overworld));
+        assert!(ball.usable_in(UseContext::Battle));
+        assert!(!ball.usable_in(UseContext::Overworld));
+        assert!(!machine.usable_in(UseContext::Battle));
+        assert!(machine.usable_in(UseContext::Overworld));
+        assert!(!keepsake.usable_in(UseContext::Battle));
+        assert!(!keepsake.usable_in(UseContext::Overworld));
+    }
+}
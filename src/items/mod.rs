@@ -0,0 +1,256 @@
+mod definition;
+
+pub use self::definition::{EffectOutcome, ItemDatabase, ItemDefinition, Pocket, UseContext};
+
+use crate::creatures::{Creature, SpeciesDatabase};
+use serde_derive::*;
+use std::{collections::BTreeMap, error::Error, fmt};
+
+/// Items carried by the player, by id in the `ItemDatabase`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Inventory {
+    items: BTreeMap<String, u32>,
+}
+
+impl Inventory {
+    pub fn count(&self, item: &str) -> u32 {
+        self.items.get(item).cloned().unwrap_or(0)
+    }
+
+    pub fn add<S: Into<String>>(&mut self, item: S, count: u32) {
+        *self.items.entry(item.into()).or_insert(0) += count;
+    }
+
+    /// Remove one item. Return false if there was none.
+    pub fn take(&mut self, item: &str) -> bool {
+        match self.items.get_mut(item) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                true
+            }
+            Some(_) => {
+                self.items.remove(item);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The items of a pocket along with their count, sorted by id.
+    pub fn pocket<'a>(&'a self, pocket: Pocket, items: &ItemDatabase) -> Vec<(&'a str, u32)> {
+        self.items
+            .iter()
+            .filter(|(id, _)| items.item(id).map_or(false, |item| item.pocket == pocket))
+            .map(|(id, count)| (id.as_str(), *count))
+            .collect()
+    }
+}
+
+/// An item which can not be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemUseError {
+    UnknownItem,
+    NoneLeft,
+    NotUsableHere,
+    /// Using the item would not change anything.
+    NoEffect,
+}
+
+impl fmt::Display for ItemUseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ItemUseError::UnknownItem => write!(f, "There is no such item."),
+            ItemUseError::NoneLeft => write!(f, "You don't have any left."),
+            ItemUseError::NotUsableHere => write!(f, "This can't be used now."),
+            ItemUseError::NoEffect => write!(f, "It won't have any effect."),
+        }
+    }
+}
+
+impl Error for ItemUseError {}
+
+/// Check that an item of the inventory can be used in a context, and return its definition.
+pub fn check_usable<'a>(
+    item: &str,
+    context: UseContext,
+    inventory: &Inventory,
+    items: &'a ItemDatabase,
+) -> Result<&'a ItemDefinition, ItemUseError> {
+    let definition = items.item(item).ok_or(ItemUseError::UnknownItem)?;
+    if inventory.count(item) == 0 {
+        Err(ItemUseError::NoneLeft)
+    } else if !definition.usable_in(context) {
+        Err(ItemUseError::NotUsableHere)
+    } else {
+        Ok(definition)
+    }
+}
+
+/// Use an item of the inventory on a creature outside of battles.
+/// The item is consumed only if it changed something.
+pub fn use_on_creature(
+    item: &str,
+    creature: &mut Creature,
+    inventory: &mut Inventory,
+    items: &ItemDatabase,
+    database: &SpeciesDatabase,
+) -> Result<Vec<EffectOutcome>, ItemUseError> {
+    let definition = check_usable(item, UseContext::Overworld, inventory, items)?;
+    let outcomes = definition.apply(creature, database);
+    if outcomes.is_empty() {
+        return Err(ItemUseError::NoEffect);
+    }
+    if definition.is_consumed() {
+        inventory.take(item);
+    }
+    Ok(outcomes)
+}
+
+/// Describe what an item did to a creature.
+pub fn describe_outcome(outcome: &EffectOutcome, name: &str, database: &SpeciesDatabase) -> String {
+    match outcome {
+        EffectOutcome::Healed(hp) => format!("{} regained {} HP.", name, hp),
+        EffectOutcome::StatusCured => format!("{} is back in shape!", name),
+        EffectOutcome::PpRestored(pp) => format!("{}'s moves regained {} PP.", name, pp),
+        EffectOutcome::MoveLearned(move_id) => {
+            format!("{} learned {}!", name, database.moves[move_id].name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn items() -> ItemDatabase {
+        ron::de::from_str(
+            r#"(
+                items: {
+                    "potion": (name: "Potion", description: "", pocket: Medicine, effects: [Heal(hp: 20)]),
+                    "antidote": (name: "Antidote", description: "", pocket: Medicine, effects: [CureStatus(status: Some(Poison))]),
+                    "ball": (name: "Ball", description: "", pocket: CaptureDevices, effects: [Capture(bonus: 1.0)]),
+                    "tm_crush": (name: "TM Crush", description: "", pocket: Machines, effects: [TeachMove(move_id: "crush")]),
+                    "flute": (name: "Flute", description: "", pocket: KeyItems, effects: [CureStatus(status: Some(Sleep))]),
+                },
+            )"#,
+        )
+        .unwrap()
+    }
+
+    fn database() -> SpeciesDatabase {
+        ron::de::from_str(
+            r#"(
+                species: {
+                    "ferris": (
+                        name: "Ferris",
+                        types: [Normal],
+                        base_stats: (hp: 40, attack: 50, defense: 60, special_attack: 70, special_defense: 80, speed: 90),
+                        learnset: [(level: 1, move_id: "pinch")],
+                        sprite: (sheet: File(texture: "ferris.png", definition: "ferris.ron"), index: 0),
+                        catch_rate: 200,
+                        base_experience: 50,
+                    ),
+                },
+                moves: {
+                    "pinch": (name: "Pinch", move_type: Normal, category: Physical, power: 40, accuracy: 100, pp: 35),
+                    "crush": (name: "Crush", move_type: Normal, category: Physical, power: 80, accuracy: 90, pp: 15),
+                },
+            )"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn items_are_counted_added_and_taken_one_at_a_time() {
+        let mut inventory = Inventory::default();
+        assert_eq!(inventory.count("potion"), 0);
+        assert!(!inventory.take("potion"));
+
+        inventory.add("potion", 2);
+        inventory.add("potion", 1);
+        assert_eq!(inventory.count("potion"), 3);
+
+        assert!(inventory.take("potion"));
+        assert!(inventory.take("potion"));
+        assert!(inventory.take("potion"));
+        assert_eq!(inventory.count("potion"), 0);
+        assert!(!inventory.take("potion"));
+        assert!(inventory.pocket(Pocket::Medicine, &items()).is_empty());
+    }
+
+    #[test]
+    fn pockets_list_their_items_sorted_by_id() {
+        let items = items();
+        let mut inventory = Inventory::default();
+        inventory.add("potion", 3);
+        inventory.add("ball", 5);
+        inventory.add("antidote", 1);
+        inventory.add("unknown", 1);
+
+        assert_eq!(
+            inventory.pocket(Pocket::Medicine, &items),
+            vec![("antidote", 1), ("potion", 3)]
+        );
+        assert_eq!(
+            inventory.pocket(Pocket::CaptureDevices, &items),
+            vec![("ball", 5)]
+        );
+        assert!(inventory.pocket(Pocket::KeyItems, &items).is_empty());
+    }
+
+    #[test]
+    fn items_are_usable_only_when_owned_and_in_the_right_context() {
+        let items = items();
+        let mut inventory = Inventory::default();
+        inventory.add("potion", 1);
+        inventory.add("ball", 1);
+        inventory.add("tm_crush", 1);
+
+        let usable = |item, context| check_usable(item, context, &inventory, &items).map(|_| ());
+        assert_eq!(
+            usable("unknown", UseContext::Battle),
+            Err(ItemUseError::UnknownItem)
+        );
+        assert_eq!(
+            usable("antidote", UseContext::Battle),
+            Err(ItemUseError::NoneLeft)
+        );
+        assert_eq!(usable("potion", UseContext::Battle), Ok(()));
+        assert_eq!(usable("potion", UseContext::Overworld), Ok(()));
+        assert_eq!(usable("ball", UseContext::Battle), Ok(()));
+        assert_eq!(
+            usable("ball", UseContext::Overworld),
+            Err(ItemUseError::NotUsableHere)
+        );
+        assert_eq!(
+            usable("tm_crush", UseContext::Battle),
+            Err(ItemUseError::NotUsableHere)
+        );
+        assert_eq!(usable("tm_crush", UseContext::Overworld), Ok(()));
+    }
+
+    #[test]
+    fn items_are_consumed_only_when_they_change_something() {
+        let items = items();
+        let database = database();
+        let mut creature = Creature::new("ferris", 10, &database, &mut Rng::new(1));
+        let mut inventory = Inventory::default();
+        inventory.add("potion", 2);
+        inventory.add("flute", 1);
+
+        let result = use_on_creature("potion", &mut creature, &mut inventory, &items, &database);
+        assert_eq!(result, Err(ItemUseError::NoEffect));
+        assert_eq!(inventory.count("potion"), 2);
+
+        creature.hp -= 1;
+        let result = use_on_creature("potion", &mut creature, &mut inventory, &items, &database);
+        assert_eq!(result, Ok(vec![EffectOutcome::Healed(1)]));
+        assert_eq!(inventory.count("potion"), 1);
+
+        creature.status = Some(crate::creatures::Status::Asleep { turns: 2 });
+        let result = use_on_creature("flute", &mut creature, &mut inventory, &items, &database);
+        assert_eq!(result, Ok(vec![EffectOutcome::StatusCured]));
+        assert_eq!(inventory.count("flute"), 1);
+    }
+}
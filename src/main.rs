@@ -1,17 +1,31 @@
 mod animations;
 mod assets;
+mod battle;
+mod bench;
 mod bundle;
 mod components;
+mod creatures;
+mod dialogue;
 mod entities;
+mod events;
+mod game_data;
+mod items;
+mod maps;
 mod resources;
+mod rng;
+mod save;
 mod states;
 mod systems;
+mod transition;
 
 use crate::{
     animations::HeroAnimationId,
-    bundle::RustymonBundle,
-    components::{InstanceCompat, OverworldCompat},
+    bundle::{RustymonBundle, SimulationBundle},
+    components::Layer,
+    game_data::RustymonGameDataBuilder,
+    rng::Rng,
     states::{GameState, OverworldState},
+    transition::ScreenTransition,
 };
 
 use amethyst::{
@@ -25,6 +39,11 @@ use amethyst::{
 };
 
 fn main() -> amethyst::Result<()> {
+    if std::env::args().any(|arg| arg == "--bench-physics") {
+        bench::run_physics_benchmark();
+        return Ok(());
+    }
+
     amethyst::Logger::from_config(Default::default())
         .level_for("gfx_device_gl", amethyst::LogLevelFilter::Warn)
         .start();
@@ -37,23 +56,32 @@ fn main() -> amethyst::Result<()> {
             .with_pass(DrawFlat2D::new().with_transparency(ColorMask::all(), ALPHA, None)),
     );
 
-    let game_data = GameDataBuilder::default()
-        .with_bundle(TransformBundle::new())?
-        .with_bundle(
+    let game_data = RustymonGameDataBuilder::new()
+        .with_simulation_bundle(SimulationBundle)?
+        .with_frame_bundle(RustymonBundle)?
+        .with_frame_bundle(TransformBundle::new().with_dep(&[
+            "interpolation",
+            "camera_targeting",
+            "transition",
+        ]))?
+        .with_frame_bundle(
             InputBundle::<String, String>::new().with_bindings_from_file("configs/bindings.ron")?,
         )?
-        .with_bundle(AnimationBundle::<HeroAnimationId, SpriteRender>::new(
+        .with_frame_bundle(AnimationBundle::<HeroAnimationId, SpriteRender>::new(
             "control", "sampler",
         ))?
-        .with_bundle(
-            RenderBundle::new(pipe, Some(display_config.clone())).with_sprite_sheet_processor(),
-        )?
-        .with_bundle(RustymonBundle)?;
+        .with_frame_bundle(
+            // Transparent sprites (the transition overlay) are drawn after the others, ordered by depth.
+            RenderBundle::new(pipe, Some(display_config.clone()))
+                .with_sprite_sheet_processor()
+                .with_sprite_visibility_sorting(&["transform_system"]),
+        )?;
 
     let mut game = Application::build("assets/", OverworldState::new(display_config))?
         .with_resource(GameState::default())
-        .register::<OverworldCompat>()
-        .register::<InstanceCompat>()
+        .with_resource(Rng::from_time())
+        .with_resource(ScreenTransition::default())
+        .register::<Layer>()
         .build(game_data)?;
     game.run();
     Ok(())
@@ -0,0 +1,399 @@
+//! Streaming of the overworld's entities by chunks.
+//!
+//! The overworld is split into square chunks, each entity belonging to the chunk of its position
+//! in the map. Only the entities of the chunks around the camera exist in the world: the
+//! overworld state loads the chunks coming into view and unloads the ones going out of it,
+//! so the number of entities doesn't depend on the size of the map.
+//!
+//! Unloaded mobs are kept as records and spawned again where they were, with their creature.
+//! Mobs belong to the chunk they were last seen in, rather than the one they were placed in.
+//! Mobs which have been defeated or captured are neither loaded nor recorded.
+
+use super::{spawn, EntityKind, MapDefinition};
+use crate::{components::Mob, creatures::Creature};
+use amethyst::{
+    core::transform::Transform,
+    ecs::prelude::{Entity, World},
+    renderer::SpriteSheetHandle,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+/// Width and height of a chunk, in world units.
+pub const CHUNK_SIZE: f32 = 256.0;
+/// Chunks kept loaded around the ones seen by the camera, so entities don't appear in view
+/// and mobs chasing the hero don't disappear as soon as their chunk is out of the screen.
+const CHUNK_MARGIN: i32 = 1;
+
+/// Coordinates of a chunk, the chunk (0, 0) starts at the origin.
+pub type ChunkKey = (i32, i32);
+
+/// The chunk containing a position.
+pub fn chunk_of((x, y): (f32, f32)) -> ChunkKey {
+    (
+        (x / CHUNK_SIZE).floor() as i32,
+        (y / CHUNK_SIZE).floor() as i32,
+    )
+}
+
+/// The chunks to keep loaded for a camera, from its position and the half extents of its view.
+pub fn chunks_around(
+    (x, y): (f32, f32),
+    (half_width, half_height): (f32, f32),
+) -> HashSet<ChunkKey> {
+    let (left, bottom) = chunk_of((x - half_width, y - half_height));
+    let (right, top) = chunk_of((x + half_width, y + half_height));
+    let mut chunks = HashSet::new();
+    for column in left - CHUNK_MARGIN..=right + CHUNK_MARGIN {
+        for row in bottom - CHUNK_MARGIN..=top + CHUNK_MARGIN {
+            chunks.insert((column, row));
+        }
+    }
+    chunks
+}
+
+/// State of a mob whose chunk is not loaded.
+#[derive(Clone, Debug)]
+pub struct MobRecord {
+    pub position: (f32, f32),
+    pub creature: Creature,
+}
+
+/// The overworld split into chunks, and the entities of the loaded ones.
+pub struct OverworldChunks {
+    map: Arc<MapDefinition>,
+    sprite_sheets: Arc<HashMap<String, SpriteSheetHandle>>,
+    /// Indices of the map's entities, by chunk. Mobs are moved to the chunk they wander in.
+    chunks: HashMap<ChunkKey, Vec<usize>>,
+    /// Entities of the loaded chunks.
+    loaded: HashMap<ChunkKey, Vec<Entity>>,
+    /// Mobs which are not spawned, by index in the map.
+    mobs: HashMap<usize, MobRecord>,
+}
+
+impl OverworldChunks {
+    /// Load the sprite sheets of the map, spawn its tilemap, which is streamed by
+    /// `TilemapRenderSystem`, and split its entities into chunks. No chunk is loaded yet.
+    /// The map is expected to be valid (see `MapDefinition::validate`)
+    /// and the `SpeciesDatabase` resource to be loaded.
+    pub fn new(map: MapDefinition, world: &mut World) -> Self {
+        world.add_resource(map.movement);
+        let sprite_sheets = spawn::load_sprite_sheets(&map, world);
+        spawn::spawn_tilemap(&map, &sprite_sheets, world);
+
+        let mut chunks = HashMap::new();
+        let mut mobs = HashMap::new();
+        for (index, entity) in map.entities.iter().enumerate() {
+            chunks
+                .entry(chunk_of(entity.position))
+                .or_insert_with(Vec::new)
+                .push(index);
+            if let EntityKind::Mob { species, level, .. } = &entity.kind {
+                let creature = spawn::new_mob_creature(species, *level, world);
+                mobs.insert(
+                    index,
+                    MobRecord {
+                        position: entity.position,
+                        creature,
+                    },
+                );
+            }
+        }
+
+        OverworldChunks {
+            map: Arc::new(map),
+            sprite_sheets: Arc::new(sprite_sheets),
+            chunks,
+            loaded: HashMap::new(),
+            mobs,
+        }
+    }
+
+    /// The mobs which are not spawned, along with their index in the map.
+    pub fn unloaded_mobs(&self) -> impl Iterator<Item = (usize, &MobRecord)> {
+        self.mobs.iter().map(|(&index, record)| (index, record))
+    }
+
+    /// Keep the unloaded mobs for which `keep` returns true, it may update them.
+    /// Mobs given a position in another chunk now belong to that chunk.
+    pub fn retain_mobs<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(usize, &mut MobRecord) -> bool,
+    {
+        let mut moved = Vec::new();
+        self.mobs.retain(|&index, record| {
+            let from = chunk_of(record.position);
+            let kept = keep(index, record);
+            if kept && chunk_of(record.position) != from {
+                moved.push((index, from, chunk_of(record.position)));
+            }
+            kept
+        });
+        for (index, from, to) in moved {
+            self.move_mob(index, from, to);
+        }
+    }
+
+    /// Make a mob belong to another chunk.
+    fn move_mob(&mut self, index: usize, from: ChunkKey, to: ChunkKey) {
+        if let Some(indices) = self.chunks.get_mut(&from) {
+            indices.retain(|&other| other != index);
+        }
+        self.chunks.entry(to).or_insert_with(Vec::new).push(index);
+    }
+}
+
+/// Load the chunks around the camera and unload the others.
+/// The `OverworldChunks` resource must have been added.
+pub fn stream_chunks(camera: (f32, f32), half_extents: (f32, f32), world: &mut World) {
+    let wanted = chunks_around(camera, half_extents);
+    let (to_unload, to_load, map, sprite_sheets) = {
+        let chunks = world.read_resource::<OverworldChunks>();
+        let to_unload = chunks
+            .loaded
+            .keys()
+            .filter(|key| !wanted.contains(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        let to_load = wanted
+            .iter()
+            .filter(|key| chunks.chunks.contains_key(key) && !chunks.loaded.contains_key(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        (
+            to_unload,
+            to_load,
+            chunks.map.clone(),
+            chunks.sprite_sheets.clone(),
+        )
+    };
+
+    for key in to_unload {
+        unload_chunk(key, world);
+    }
+    for key in to_load {
+        load_chunk(key, &map, &sprite_sheets, world);
+    }
+}
+
+/// Spawn the entities of a chunk. Mobs are spawned from their record, if they still have one.
+fn load_chunk(
+    key: ChunkKey,
+    map: &MapDefinition,
+    sprite_sheets: &HashMap<String, SpriteSheetHandle>,
+    world: &mut World,
+) {
+    let indices = world.read_resource::<OverworldChunks>().chunks[&key].clone();
+    let mut spawned = Vec::with_capacity(indices.len());
+    for index in indices {
+        let record = match &map.entities[index].kind {
+            EntityKind::Mob { .. } => {
+                match world
+                    .write_resource::<OverworldChunks>()
+                    .mobs
+                    .remove(&index)
+                {
+                    Some(record) => Some(record),
+                    None => continue,
+                }
+            }
+            _ => None,
+        };
+
+        let position = record.as_ref().map(|record| record.position);
+        let entity = spawn::spawn_entity(
+            map,
+            index,
+            sprite_sheets,
+            record.map(|record| record.creature),
+            world,
+        );
+        if let Some((x, y)) = position {
+            if let Some(transform) = world.write_storage::<Transform>().get_mut(entity) {
+                transform.set_x(x);
+                transform.set_y(y);
+            }
+        }
+        spawned.push(entity);
+    }
+    world
+        .write_resource::<OverworldChunks>()
+        .loaded
+        .insert(key, spawned);
+}
+
+/// Delete the entities of a chunk, recording the state of its mobs.
+/// Mobs which wandered into another loaded chunk are handed over to it instead.
+fn unload_chunk(key: ChunkKey, world: &mut World) {
+    let entities = match world
+        .write_resource::<OverworldChunks>()
+        .loaded
+        .remove(&key)
+    {
+        Some(entities) => entities,
+        None => return,
+    };
+    for entity in entities {
+        // Defeated and captured mobs have already been deleted.
+        if !world.is_alive(entity) {
+            continue;
+        }
+        let record = {
+            let mobs = world.read_storage::<Mob>();
+            let transforms = world.read_storage::<Transform>();
+            match (mobs.get(entity), transforms.get(entity)) {
+                (Some(mob), Some(transform)) => Some((
+                    mob.map_index,
+                    MobRecord {
+                        position: (transform.translation().x, transform.translation().y),
+                        creature: mob.creature.clone(),
+                    },
+                )),
+                _ => None,
+            }
+        };
+        if let Some((index, record)) = record {
+            let mut chunks = world.write_resource::<OverworldChunks>();
+            let current = chunk_of(record.position);
+            if current != key {
+                chunks.move_mob(index, key, current);
+                if let Some(entities) = chunks.loaded.get_mut(&current) {
+                    entities.push(entity);
+                    continue;
+                }
+            }
+            chunks.mobs.insert(index, record);
+        }
+        world.delete_entity(entity).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creatures::Stats;
+    use amethyst::ecs::prelude::Builder;
+
+    #[test]
+    fn positions_belong_to_the_chunk_below_and_left_of_them() {
+        assert_eq!(chunk_of((0.0, 0.0)), (0, 0));
+        assert_eq!(chunk_of((CHUNK_SIZE - 1.0, 10.0)), (0, 0));
+        assert_eq!(chunk_of((CHUNK_SIZE, -1.0)), (1, -1));
+        assert_eq!(chunk_of((-CHUNK_SIZE - 1.0, 0.0)), (-2, 0));
+    }
+
+    #[test]
+    fn chunks_around_a_camera_cover_its_view_and_the_margin() {
+        let chunks = chunks_around((10.0, 10.0), (200.0, 200.0));
+        // The view spans the chunks -1 to 0 on both axes.
+        let side = (2 + 2 * CHUNK_MARGIN) as usize;
+        assert_eq!(chunks.len(), side * side);
+        assert!(chunks.contains(&(-1 - CHUNK_MARGIN, CHUNK_MARGIN)));
+        assert!(!chunks.contains(&(1 + CHUNK_MARGIN, 0)));
+    }
+
+    /// A world with the chunks (0, 0) and (1, 0) loaded, and the mob placed in the first one
+    /// wandered into the second one.
+    fn wandering_mob() -> (World, Entity) {
+        let map: MapDefinition = ron::de::from_str(
+            r#"(
+                sprite_sheets: {},
+                entities: [
+                    (
+                        position: (10.0, 10.0),
+                        kind: Mob(
+                            reset_threshold: 500.0,
+                            target_threshold: 100.0,
+                            species: "ferris",
+                            level: 5,
+                        ),
+                    ),
+                    (position: (300.0, 10.0), kind: Prop),
+                ],
+            )"#,
+        )
+        .unwrap();
+        let creature = Creature {
+            species: "ferris".to_owned(),
+            nickname: None,
+            level: 5,
+            experience: 0,
+            ivs: Stats::default(),
+            evs: Stats::default(),
+            hp: 20,
+            moves: Vec::new(),
+            status: None,
+        };
+
+        let mut world = World::new();
+        world.register::<Mob>();
+        world.register::<Transform>();
+        let mut transform = Transform::default();
+        transform.set_xyz(CHUNK_SIZE + 20.0, 10.0, 0.0);
+        let mob = world
+            .create_entity()
+            .with(Mob::new(0, 10.0, 10.0, 500.0, 100.0, creature))
+            .with(transform)
+            .build();
+        let prop = world.create_entity().build();
+
+        let mut chunks = HashMap::new();
+        chunks.insert((0, 0), vec![0]);
+        chunks.insert((1, 0), vec![1]);
+        let mut loaded = HashMap::new();
+        loaded.insert((0, 0), vec![mob]);
+        loaded.insert((1, 0), vec![prop]);
+        world.add_resource(OverworldChunks {
+            map: Arc::new(map),
+            sprite_sheets: Arc::new(HashMap::new()),
+            chunks,
+            loaded,
+            mobs: HashMap::new(),
+        });
+        (world, mob)
+    }
+
+    #[test]
+    fn mobs_in_a_loaded_chunk_are_handed_over_to_it() {
+        let (mut world, mob) = wandering_mob();
+        unload_chunk((0, 0), &mut world);
+
+        assert!(world.is_alive(mob));
+        let chunks = world.read_resource::<OverworldChunks>();
+        assert!(chunks.loaded[&(1, 0)].contains(&mob));
+        assert!(chunks.chunks[&(0, 0)].is_empty());
+        assert!(chunks.chunks[&(1, 0)].contains(&0));
+        assert_eq!(chunks.unloaded_mobs().count(), 0);
+    }
+
+    #[test]
+    fn unloaded_mobs_belong_to_the_chunk_they_were_in() {
+        let (mut world, mob) = wandering_mob();
+        unload_chunk((1, 0), &mut world);
+        unload_chunk((0, 0), &mut world);
+
+        assert!(!world.is_alive(mob));
+        let chunks = world.read_resource::<OverworldChunks>();
+        assert!(chunks.loaded.is_empty());
+        assert!(chunks.chunks[&(0, 0)].is_empty());
+        assert!(chunks.chunks[&(1, 0)].contains(&0));
+        assert_eq!(chunks.mobs[&0].position, (CHUNK_SIZE + 20.0, 10.0));
+    }
+
+    #[test]
+    fn restored_mobs_belong_to_the_chunk_of_their_position() {
+        let (mut world, _) = wandering_mob();
+        unload_chunk((1, 0), &mut world);
+        unload_chunk((0, 0), &mut world);
+
+        let mut chunks = world.write_resource::<OverworldChunks>();
+        chunks.retain_mobs(|_, record| {
+            record.position = (-20.0, -20.0);
+            true
+        });
+        assert_eq!(chunks.chunks[&(1, 0)], [1]);
+        assert_eq!(chunks.chunks[&(-1, -1)], [0]);
+    }
+}
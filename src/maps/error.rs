@@ -0,0 +1,76 @@
+use std::{error::Error, fmt};
+
+/// An invalid entry inside a map file.
+#[derive(Debug)]
+pub struct MapError {
+    /// Path of the map file.
+    pub path: String,
+    pub entry: MapEntry,
+    pub kind: MapErrorKind,
+}
+
+/// Position of a faulty entry inside a map.
+#[derive(Debug, Clone, Copy)]
+pub enum MapEntry {
+    /// Index in the map's entity list.
+    Entity(usize),
+    /// Index in the map's tile layer list.
+    TileLayer(usize),
+    /// The map's tile collision grid.
+    CollisionGrid,
+}
+
+#[derive(Debug)]
+pub enum MapErrorKind {
+    /// The entity or tile layer uses a sprite sheet which is not declared by the map.
+    MissingSpriteSheet(String),
+    /// The portal leads to an instance which is not declared by the map.
+    UnknownInstance(String),
+    /// The mob's creature is of a species which is not in the species database.
+    UnknownSpecies(String),
+    /// The chest contains an item which is not in the item database.
+    UnknownItem(String),
+    /// The NPC's dialogue is not in the dialogue database.
+    UnknownDialogue(String),
+    /// The body's shape has a size which is not a positive number,
+    /// or it is a degenerate polygon or an empty compound.
+    InvalidShape,
+    /// The trigger zone has a half extent which is not a positive number.
+    InvalidTriggerZone,
+    /// The tile layer or collision grid does not have `width * height` tiles.
+    InvalidTileCount { expected: usize, actual: usize },
+}
+
+impl MapError {
+    pub fn new(path: &str, entry: MapEntry, kind: MapErrorKind) -> Self {
+        MapError {
+            path: path.to_owned(),
+            entry,
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.entry {
+            MapEntry::Entity(index) => write!(f, "{}: entity #{}: ", self.path, index)?,
+            MapEntry::TileLayer(index) => write!(f, "{}: tile layer #{}: ", self.path, index)?,
+            MapEntry::CollisionGrid => write!(f, "{}: collision grid: ", self.path)?,
+        }
+        match &self.kind {
+            MapErrorKind::MissingSpriteSheet(name) => write!(f, "missing sprite sheet '{}'", name),
+            MapErrorKind::UnknownInstance(id) => write!(f, "unknown instance id '{}'", id),
+            MapErrorKind::UnknownSpecies(id) => write!(f, "unknown species id '{}'", id),
+            MapErrorKind::UnknownItem(id) => write!(f, "unknown item id '{}'", id),
+            MapErrorKind::UnknownDialogue(id) => write!(f, "unknown dialogue id '{}'", id),
+            MapErrorKind::InvalidShape => write!(f, "invalid body shape"),
+            MapErrorKind::InvalidTriggerZone => write!(f, "invalid trigger zone"),
+            MapErrorKind::InvalidTileCount { expected, actual } => {
+                write!(f, "expected {} tiles, found {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl Error for MapError {}
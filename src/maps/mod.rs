@@ -0,0 +1,581 @@
+mod chunks;
+mod error;
+mod spawn;
+mod tiled;
+
+use crate::{
+    components::{Body, Dynamic, FacingSprites, Shape},
+    creatures::SpeciesDatabase,
+    dialogue::DialogueDatabase,
+    items::ItemDatabase,
+    resources::{MovementMode, WorldBounds},
+    states::Instance,
+    transition::TransitionEffect,
+};
+use amethyst::{
+    assets::{Asset, Handle, ProcessingState, Result},
+    ecs::prelude::VecStorage,
+};
+use ncollide2d::{
+    math::{Isometry, Point, Vector},
+    shape::{Ball, Compound, ConvexPolygon, Cuboid, ShapeHandle},
+};
+use serde_derive::*;
+use std::collections::HashMap;
+
+pub use self::{
+    chunks::{stream_chunks, OverworldChunks},
+    error::{MapEntry, MapError, MapErrorKind},
+    spawn::spawn_map,
+    tiled::TiledFormat,
+};
+
+/// Describe a whole map: the sprite sheets it uses, the instances its portals lead to
+/// and every entity placed on it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MapDefinition {
+    /// Sprite sheets referenced by entities, by name.
+    pub sprite_sheets: HashMap<String, SpriteSheetDefinition>,
+    /// Instances referenced by portals, by id.
+    #[serde(default)]
+    pub instances: HashMap<String, Instance>,
+    /// Entities placed on the map.
+    pub entities: Vec<EntityDefinition>,
+    /// Terrain of the map.
+    #[serde(default)]
+    pub tilemap: Option<TilemapDefinition>,
+    /// How the hero moves on this map.
+    #[serde(default)]
+    pub movement: MovementMode,
+    /// Limits of the map, see `MapDefinition::bounds`.
+    #[serde(default)]
+    pub bounds: Option<WorldBounds>,
+}
+
+/// The maps of every instance reachable from the overworld, by path.
+/// They are loaded along with the overworld map and spawned when entering their instance.
+#[derive(Clone, Default)]
+pub struct InstanceMaps {
+    maps: HashMap<String, MapDefinition>,
+}
+
+impl InstanceMaps {
+    pub fn get(&self, path: &str) -> Option<&MapDefinition> {
+        self.maps.get(path)
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.maps.contains_key(path)
+    }
+
+    pub fn insert(&mut self, path: String, map: MapDefinition) {
+        self.maps.insert(path, map);
+    }
+}
+
+/// A grid of tiles, see the `Tilemap` component.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TilemapDefinition {
+    /// Position of the bottom left corner of the grid.
+    pub origin: (f32, f32),
+    pub width: usize,
+    pub height: usize,
+    pub tile_width: f32,
+    pub tile_height: f32,
+    pub layers: Vec<TileLayerDefinition>,
+    /// Solid tiles, row major starting from the top row. Empty if no tile is solid.
+    #[serde(default)]
+    pub solid: Vec<bool>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TileLayerDefinition {
+    /// Name of the sprite sheet in `MapDefinition::sprite_sheets`.
+    pub sheet: String,
+    /// Sprite index of each tile, row major starting from the top row.
+    pub tiles: Vec<Option<usize>>,
+}
+
+/// Where to find a sprite sheet. Paths are relative to the asset directory.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum SpriteSheetDefinition {
+    /// A texture along with its sprite sheet definition file.
+    File { texture: String, definition: String },
+    /// A texture cut into a grid of same sized sprites.
+    Grid { texture: String, grid: SpriteGrid },
+}
+
+/// Layout of a sprite sheet made of same sized sprites, numbered row by row.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SpriteGrid {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub sprite_width: u32,
+    pub sprite_height: u32,
+    pub columns: u32,
+    pub count: u32,
+    #[serde(default)]
+    pub margin: u32,
+    #[serde(default)]
+    pub spacing: u32,
+}
+
+/// An entity placed on a map.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EntityDefinition {
+    pub position: (f32, f32),
+    /// Counter-clockwise rotation, in degrees.
+    #[serde(default)]
+    pub angle: f32,
+    #[serde(default)]
+    pub sprite: Option<SpriteDefinition>,
+    #[serde(default)]
+    pub body: Option<BodyDefinition>,
+    pub kind: EntityKind,
+}
+
+/// A sprite inside one of the map's sprite sheets.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpriteDefinition {
+    /// Name of the sprite sheet in `MapDefinition::sprite_sheets`.
+    pub sheet: String,
+    pub index: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BodyDefinition {
+    pub shape: ShapeDefinition,
+    pub dynamic: Dynamic,
+    #[serde(default)]
+    pub sensor: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ShapeDefinition {
+    Circle {
+        radius: f32,
+    },
+    Box {
+        half_width: f32,
+        half_height: f32,
+    },
+    /// A convex polygon. Points are relative to the entity's position.
+    Polygon {
+        points: Vec<(f32, f32)>,
+    },
+    /// A capsule aligned with the y axis.
+    Capsule {
+        half_height: f32,
+        radius: f32,
+    },
+    Compound {
+        parts: Vec<CompoundPartDefinition>,
+    },
+}
+
+/// A shape placed relatively to the body.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompoundPartDefinition {
+    pub offset: (f32, f32),
+    /// Counter-clockwise rotation, in degrees.
+    #[serde(default)]
+    pub angle: f32,
+    pub shape: ShapeDefinition,
+}
+
+/// What an entity is, along with its kind specific data.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum EntityKind {
+    /// A decoration or obstacle without any behaviour.
+    Prop,
+    Mob {
+        reset_threshold: f32,
+        target_threshold: f32,
+        /// Id of the creature's species in the `SpeciesDatabase`.
+        species: String,
+        level: u32,
+    },
+    Portal {
+        /// Id of the instance in `MapDefinition::instances`.
+        instance: String,
+        /// Half extents of the trigger zone.
+        trigger_zone: (f32, f32),
+        /// The effect played when entering the instance and leaving it.
+        #[serde(default)]
+        transition: TransitionEffect,
+    },
+    /// A chest giving items the first time it is opened.
+    Chest {
+        /// Ids of the items in the `ItemDatabase`, along with their count.
+        items: Vec<(String, u32)>,
+        /// Flag set once the chest is opened, it must be unique to the chest.
+        flag: String,
+        /// Half extents of the zone from which the chest can be opened.
+        trigger_zone: (f32, f32),
+    },
+    Npc {
+        name: String,
+        /// Id of the dialogue in the `DialogueDatabase`.
+        dialogue: String,
+        /// Sprites of the entity's sprite sheet shown when the NPC turns towards the hero.
+        #[serde(default)]
+        facing_sprites: Option<FacingSprites>,
+        /// Half extents of the zone from which the hero can talk to the NPC.
+        trigger_zone: (f32, f32),
+    },
+    /// A sign showing a text when read.
+    Sign {
+        text: String,
+        /// Half extents of the zone from which the sign can be read.
+        trigger_zone: (f32, f32),
+    },
+    /// A creature starting a battle when used, until it is defeated.
+    Battle {
+        /// Id of the creature's species in the `SpeciesDatabase`.
+        species: String,
+        level: u32,
+        /// Flag set once the creature is defeated, it must be unique to the entity.
+        won_flag: String,
+        /// Half extents of the zone from which the battle can be started.
+        trigger_zone: (f32, f32),
+    },
+}
+
+impl EntityKind {
+    /// Half extents of the zone from which the entity is used, None if it can't be.
+    pub fn trigger_zone(&self) -> Option<(f32, f32)> {
+        match self {
+            EntityKind::Prop | EntityKind::Mob { .. } => None,
+            EntityKind::Portal { trigger_zone, .. }
+            | EntityKind::Chest { trigger_zone, .. }
+            | EntityKind::Npc { trigger_zone, .. }
+            | EntityKind::Sign { trigger_zone, .. }
+            | EntityKind::Battle { trigger_zone, .. } => Some(*trigger_zone),
+        }
+    }
+}
+
+impl BodyDefinition {
+    /// Build the body component. Return None if the shape is invalid.
+    pub fn to_body(&self) -> Option<Body> {
+        self.shape.to_shape().map(|shape| Body {
+            shape,
+            dynamic: self.dynamic,
+            sensor: self.sensor,
+        })
+    }
+}
+
+impl ShapeDefinition {
+    /// Build the shape. Return None for sizes which are not positive numbers,
+    /// degenerate polygons and empty compounds.
+    pub fn to_shape(&self) -> Option<Shape> {
+        // ncollide's shapes assert that their sizes are positive.
+        let valid = |size: f32| size.is_finite() && size > 0.0;
+        let shape = match self {
+            ShapeDefinition::Circle { radius } if valid(*radius) => Shape::Circle {
+                shape: Ball::new(*radius),
+            },
+            ShapeDefinition::Box {
+                half_width,
+                half_height,
+            } if valid(*half_width) && valid(*half_height) => Shape::Box {
+                shape: Cuboid::new(Vector::new(*half_width, *half_height)),
+            },
+            ShapeDefinition::Polygon { points }
+                if points.iter().all(|(x, y)| x.is_finite() && y.is_finite()) =>
+            {
+                let points = points
+                    .iter()
+                    .map(|&(x, y)| Point::new(x, y))
+                    .collect::<Vec<_>>();
+                let shape = ConvexPolygon::try_from_points(&points)?;
+                // The hull of aligned points has no area.
+                let hull = shape.points();
+                let area = (0..hull.len())
+                    .map(|i| {
+                        let (a, b) = (hull[i], hull[(i + 1) % hull.len()]);
+                        a.x * b.y - b.x * a.y
+                    })
+                    .sum::<f32>()
+                    / 2.0;
+                if !valid(area.abs()) {
+                    return None;
+                }
+                Shape::Polygon { shape }
+            }
+            // ncollide can't compute the contacts of its capsules,
+            // so they are made of a box between two balls.
+            ShapeDefinition::Capsule {
+                half_height,
+                radius,
+            } if valid(*half_height) && valid(*radius) => {
+                let ball = |y| {
+                    let position = Isometry::new(Vector::new(0.0, y), 0.0);
+                    (position, ShapeHandle::new(Ball::new(*radius)))
+                };
+                let body = ShapeHandle::new(Cuboid::new(Vector::new(*radius, *half_height)));
+                Shape::Compound {
+                    shape: Compound::new(vec![
+                        ball(*half_height),
+                        ball(-half_height),
+                        (Isometry::identity(), body),
+                    ]),
+                }
+            }
+            ShapeDefinition::Compound { parts } if !parts.is_empty() => {
+                let mut shapes = Vec::new();
+                for part in parts {
+                    let (x, y) = part.offset;
+                    if !x.is_finite() || !y.is_finite() || !part.angle.is_finite() {
+                        return None;
+                    }
+                    let position = Isometry::new(Vector::new(x, y), part.angle.to_radians());
+                    shapes.push((position, part.shape.to_shape()?.into_shape_handle()));
+                }
+                Shape::Compound {
+                    shape: Compound::new(shapes),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(shape)
+    }
+}
+
+impl MapDefinition {
+    /// Limits of the map. Maps without bounds span 10000 units around the origin.
+    pub fn bounds(&self) -> WorldBounds {
+        self.bounds
+            .unwrap_or_else(|| WorldBounds::new_around_origin(10000.0, 10000.0))
+    }
+
+    /// Check that every reference inside the map points to something declared in the map
+    /// or in the species, item and dialogue databases, and that shapes and trigger zones have
+    /// positive sizes. `path` is only used to report errors.
+    pub fn validate(
+        &self,
+        path: &str,
+        species: &SpeciesDatabase,
+        items: &ItemDatabase,
+        dialogues: &DialogueDatabase,
+    ) -> std::result::Result<(), Vec<MapError>> {
+        let mut errors = Vec::new();
+
+        for (index, entity) in self.entities.iter().enumerate() {
+            if let Some(sprite) = &entity.sprite {
+                if !self.sprite_sheets.contains_key(&sprite.sheet) {
+                    errors.push(MapError::new(
+                        path,
+                        MapEntry::Entity(index),
+                        MapErrorKind::MissingSpriteSheet(sprite.sheet.clone()),
+                    ));
+                }
+            }
+
+            if let Some(body) = &entity.body {
+                if body.shape.to_shape().is_none() {
+                    errors.push(MapError::new(
+                        path,
+                        MapEntry::Entity(index),
+                        MapErrorKind::InvalidShape,
+                    ));
+                }
+            }
+
+            if let Some((half_width, half_height)) = entity.kind.trigger_zone() {
+                let valid = |size: f32| size.is_finite() && size > 0.0;
+                if !valid(half_width) || !valid(half_height) {
+                    errors.push(MapError::new(
+                        path,
+                        MapEntry::Entity(index),
+                        MapErrorKind::InvalidTriggerZone,
+                    ));
+                }
+            }
+
+            match &entity.kind {
+                EntityKind::Portal { instance, .. } if !self.instances.contains_key(instance) => {
+                    errors.push(MapError::new(
+                        path,
+                        MapEntry::Entity(index),
+                        MapErrorKind::UnknownInstance(instance.clone()),
+                    ));
+                }
+                EntityKind::Mob { species: id, .. } | EntityKind::Battle { species: id, .. }
+                    if species.species(id).is_none() =>
+                {
+                    errors.push(MapError::new(
+                        path,
+                        MapEntry::Entity(index),
+                        MapErrorKind::UnknownSpecies(id.clone()),
+                    ));
+                }
+                EntityKind::Npc { dialogue, .. } if dialogues.dialogue(dialogue).is_none() => {
+                    errors.push(MapError::new(
+                        path,
+                        MapEntry::Entity(index),
+                        MapErrorKind::UnknownDialogue(dialogue.clone()),
+                    ));
+                }
+                EntityKind::Chest { items: content, .. } => {
+                    for (id, _) in content {
+                        if items.item(id).is_none() {
+                            errors.push(MapError::new(
+                                path,
+                                MapEntry::Entity(index),
+                                MapErrorKind::UnknownItem(id.clone()),
+                            ));
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(tilemap) = &self.tilemap {
+            let expected = tilemap.width * tilemap.height;
+            for (index, layer) in tilemap.layers.iter().enumerate() {
+                if !self.sprite_sheets.contains_key(&layer.sheet) {
+                    errors.push(MapError::new(
+                        path,
+                        MapEntry::TileLayer(index),
+                        MapErrorKind::MissingSpriteSheet(layer.sheet.clone()),
+                    ));
+                }
+                if layer.tiles.len() != expected {
+                    errors.push(MapError::new(
+                        path,
+                        MapEntry::TileLayer(index),
+                        MapErrorKind::InvalidTileCount {
+                            expected,
+                            actual: layer.tiles.len(),
+                        },
+                    ));
+                }
+            }
+
+            if !tilemap.solid.is_empty() && tilemap.solid.len() != expected {
+                errors.push(MapError::new(
+                    path,
+                    MapEntry::CollisionGrid,
+                    MapErrorKind::InvalidTileCount {
+                        expected,
+                        actual: tilemap.solid.len(),
+                    },
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Asset for MapDefinition {
+    const NAME: &'static str = "rustymon::MapDefinition";
+    type Data = Self;
+    type HandleStorage = VecStorage<Handle<Self>>;
+}
+
+impl From<MapDefinition> for Result<ProcessingState<MapDefinition>> {
+    fn from(map: MapDefinition) -> Self {
+        Ok(ProcessingState::Loaded(map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_with_body(shape: &str) -> MapDefinition {
+        let map = format!(
+            "(sprite_sheets: {{}}, entities: [
+                (position: (0.0, 0.0), kind: Prop),
+                (position: (0.0, 0.0), body: Some((shape: {}, dynamic: Static)), kind: Prop),
+            ])",
+            shape
+        );
+        ron::de::from_str(&map).unwrap()
+    }
+
+    fn validate(map: &MapDefinition) -> std::result::Result<(), Vec<MapError>> {
+        map.validate(
+            "test.ron",
+            &SpeciesDatabase::default(),
+            &ItemDatabase::default(),
+            &DialogueDatabase::default(),
+        )
+    }
+
+    #[test]
+    fn valid_shapes_are_accepted() {
+        for shape in &[
+            "Circle(radius: 8.0)",
+            "Box(half_width: 8.0, half_height: 4.0)",
+            "Polygon(points: [(0.0, 0.0), (8.0, 0.0), (0.0, 8.0)])",
+            "Capsule(half_height: 8.0, radius: 4.0)",
+            "Compound(parts: [(offset: (4.0, 0.0), angle: 45.0, shape: Circle(radius: 2.0))])",
+        ] {
+            assert!(validate(&map_with_body(shape)).is_ok(), "{}", shape);
+        }
+    }
+
+    #[test]
+    fn invalid_shapes_are_reported_with_their_entity() {
+        for shape in &[
+            "Circle(radius: 0.0)",
+            "Circle(radius: -2.0)",
+            "Box(half_width: -8.0, half_height: 4.0)",
+            "Box(half_width: 8.0, half_height: 0.0)",
+            "Polygon(points: [(0.0, 0.0), (8.0, 0.0), (16.0, 0.0)])",
+            "Capsule(half_height: 0.0, radius: 4.0)",
+            "Capsule(half_height: 8.0, radius: -4.0)",
+            "Compound(parts: [])",
+            "Compound(parts: [(offset: (4.0, 0.0), shape: Circle(radius: 0.0))])",
+        ] {
+            match validate(&map_with_body(shape)) {
+                Err(ref errors) => match errors.as_slice() {
+                    [MapError {
+                        entry: MapEntry::Entity(1),
+                        kind: MapErrorKind::InvalidShape,
+                        ..
+                    }] => (),
+                    errors => panic!("{}: unexpected errors {:?}", shape, errors),
+                },
+                Ok(()) => panic!("{}: accepted", shape),
+            }
+        }
+    }
+
+    #[test]
+    fn trigger_zones_must_have_a_positive_size() {
+        let map = |trigger_zone: &str| -> MapDefinition {
+            let map = format!(
+                r#"(sprite_sheets: {{}}, entities: [
+                    (position: (0.0, 0.0), kind: Sign(text: "Town", trigger_zone: {})),
+                ])"#,
+                trigger_zone
+            );
+            ron::de::from_str(&map).unwrap()
+        };
+        assert!(validate(&map("(8.0, 4.0)")).is_ok());
+        for trigger_zone in &["(0.0, 4.0)", "(8.0, -4.0)"] {
+            match validate(&map(trigger_zone)) {
+                Err(ref errors) => match errors.as_slice() {
+                    [MapError {
+                        entry: MapEntry::Entity(0),
+                        kind: MapErrorKind::InvalidTriggerZone,
+                        ..
+                    }] => (),
+                    errors => panic!("{}: unexpected errors {:?}", trigger_zone, errors),
+                },
+                Ok(()) => panic!("{}: accepted", trigger_zone),
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use super::{EntityKind, MapDefinition};
+use crate::{
+    assets,
+    components::{Interactable, Interaction, Mob, TileLayer, Tilemap},
+    creatures::{Creature, SpeciesDatabase},
+    entities,
+    rng::Rng,
+    states::Instance,
+};
+use amethyst::{
+    core::transform::Transform,
+    ecs::prelude::{Entity, World},
+    renderer::{SpriteRender, SpriteSheetHandle},
+};
+use ncollide2d::{math::Vector, shape::Cuboid};
+use std::collections::HashMap;
+
+/// Load the sprite sheets of a map and create all its entities, which are returned.
+/// The map is expected to be valid (see `MapDefinition::validate`)
+/// and the `SpeciesDatabase` resource to be loaded.
+pub fn spawn_map(map: &MapDefinition, world: &mut World) -> Vec<Entity> {
+    world.add_resource(map.movement);
+    let sprite_sheets = load_sprite_sheets(map, world);
+
+    let mut spawned = Vec::new();
+    spawned.extend(spawn_tilemap(map, &sprite_sheets, world));
+    for index in 0..map.entities.len() {
+        spawned.push(spawn_entity(map, index, &sprite_sheets, None, world));
+    }
+    spawned
+}
+
+/// Load the sprite sheets of a map, by name.
+pub fn load_sprite_sheets(
+    map: &MapDefinition,
+    world: &World,
+) -> HashMap<String, SpriteSheetHandle> {
+    map.sprite_sheets
+        .iter()
+        .map(|(name, definition)| {
+            (
+                name.clone(),
+                assets::load_sprite_sheet_definition(definition, world),
+            )
+        })
+        .collect()
+}
+
+/// Create the tilemap of a map, if it has one.
+pub fn spawn_tilemap(
+    map: &MapDefinition,
+    sprite_sheets: &HashMap<String, SpriteSheetHandle>,
+    world: &mut World,
+) -> Option<Entity> {
+    let definition = map.tilemap.as_ref()?;
+    let layers = definition
+        .layers
+        .iter()
+        .map(|layer| TileLayer {
+            sprite_sheet: sprite_sheets[&layer.sheet].clone(),
+            tiles: layer.tiles.clone(),
+        })
+        .collect();
+    let solid = if definition.solid.is_empty() {
+        vec![false; definition.width * definition.height]
+    } else {
+        definition.solid.clone()
+    };
+
+    let (x, y) = definition.origin;
+    Some(entities::build_tilemap(
+        x,
+        y,
+        Tilemap {
+            width: definition.width,
+            height: definition.height,
+            tile_width: definition.tile_width,
+            tile_height: definition.tile_height,
+            layers,
+            solid,
+        },
+        world,
+    ))
+}
+
+/// Create the creature of a mob of the map.
+pub fn new_mob_creature(species: &str, level: u32, world: &World) -> Creature {
+    Creature::new(
+        species,
+        level,
+        &world.read_resource::<SpeciesDatabase>(),
+        &mut world.write_resource::<Rng>(),
+    )
+}
+
+/// Create the entity at an index of the map's entities.
+/// A mob fights with the given creature, or a new one if none is given.
+pub fn spawn_entity(
+    map: &MapDefinition,
+    index: usize,
+    sprite_sheets: &HashMap<String, SpriteSheetHandle>,
+    creature: Option<Creature>,
+    world: &mut World,
+) -> Entity {
+    let entity = &map.entities[index];
+    let (x, y) = entity.position;
+    let sprite = entity.sprite.as_ref().map(|s| SpriteRender {
+        sprite_sheet: sprite_sheets[&s.sheet].clone(),
+        sprite_number: s.index,
+    });
+    let body = entity.body.as_ref().and_then(|b| b.to_body());
+
+    let built = match &entity.kind {
+        EntityKind::Prop => entities::build_prop(x, y, sprite, body, world),
+        EntityKind::Mob {
+            reset_threshold,
+            target_threshold,
+            species,
+            level,
+        } => {
+            let creature = creature.unwrap_or_else(|| new_mob_creature(species, *level, world));
+            let mob = Mob::new(index, x, y, *reset_threshold, *target_threshold, creature);
+            entities::build_mob(mob, sprite, body, world)
+        }
+        EntityKind::Portal {
+            instance,
+            trigger_zone,
+            transition,
+        } => entities::build_interactable(
+            x,
+            y,
+            interactable(
+                *trigger_zone,
+                Interaction::EnterInstance(Instance {
+                    transition: *transition,
+                    ..map.instances[instance].clone()
+                }),
+            ),
+            sprite,
+            body,
+            world,
+        ),
+        EntityKind::Npc {
+            name,
+            dialogue,
+            facing_sprites,
+            trigger_zone,
+        } => entities::build_interactable(
+            x,
+            y,
+            interactable(
+                *trigger_zone,
+                Interaction::Talk {
+                    name: name.clone(),
+                    dialogue: dialogue.clone(),
+                    facing_sprites: *facing_sprites,
+                },
+            ),
+            sprite,
+            body,
+            world,
+        ),
+        EntityKind::Chest {
+            items,
+            flag,
+            trigger_zone,
+        } => entities::build_interactable(
+            x,
+            y,
+            interactable(
+                *trigger_zone,
+                Interaction::PickUp {
+                    items: items.clone(),
+                    flag: flag.clone(),
+                },
+            ),
+            sprite,
+            body,
+            world,
+        ),
+        EntityKind::Sign { text, trigger_zone } => entities::build_interactable(
+            x,
+            y,
+            interactable(*trigger_zone, Interaction::ReadSign(text.clone())),
+            sprite,
+            body,
+            world,
+        ),
+        EntityKind::Battle {
+            species,
+            level,
+            won_flag,
+            trigger_zone,
+        } => entities::build_interactable(
+            x,
+            y,
+            interactable(
+                *trigger_zone,
+                Interaction::StartBattle {
+                    species: species.clone(),
+                    level: *level,
+                    won_flag: won_flag.clone(),
+                },
+            ),
+            sprite,
+            body,
+            world,
+        ),
+    };
+
+    if entity.angle != 0.0 {
+        if let Some(transform) = world.write_storage::<Transform>().get_mut(built) {
+            transform.set_rotation_euler(0.0, 0.0, entity.angle.to_radians());
+        }
+    }
+    built
+}
+
+/// Build an interactable from the half extents of its trigger zone.
+fn interactable((half_width, half_height): (f32, f32), interaction: Interaction) -> Interactable {
+    Interactable {
+        trigger_zone: Cuboid::new(Vector::new(half_width, half_height)),
+        interaction,
+    }
+}
@@ -0,0 +1,848 @@
+//! Import of maps authored with the Tiled editor (https://www.mapeditor.org).
+//! Only the JSON export format with embedded tilesets is supported,
+//! `.tmx` maps have to be exported as JSON first.
+//!
+//! Tile layers become layers of the map's tilemap. Tile layers having a `collision`
+//! property set to true make their non empty tiles solid.
+//! Object layers become entities according to the object type:
+//! - `mob`: a dynamic mob. Properties: `reset_threshold`, `target_threshold`,
+//!   `species` and `level` of its creature, a positive integer.
+//! - `portal`: a portal whose trigger zone is the object's rectangle.
+//!   Properties: `instance`, the `map` of the instance, `spawn_x`, `spawn_y` in that map
+//!   and optionally its `name` (defaults to `instance`), `exit_x`, `exit_y`
+//!   (defaults to the portal position), `transition` (`fade`, `iris` or `battle_swirl`,
+//!   defaults to `fade`).
+//! - `chest`: a static chest. Properties: `items` as comma separated `id` or `id:count`,
+//!   the `flag` set once opened, `trigger_width` and `trigger_height`.
+//! - `npc`: a static NPC. Properties: `name`, `dialogue`, `trigger_width`, `trigger_height`
+//!   and optionally `sprite_down`, `sprite_up`, `sprite_left`, `sprite_right`, the tile ids
+//!   in the object's tileset shown when turning towards the hero.
+//! - `sign`: a static sign. Properties: `text`, `trigger_width` and `trigger_height`.
+//! - `battle`: a static creature fought when used. Properties: `species`, `level`,
+//!   the `won_flag` set once defeated, `trigger_width` and `trigger_height`.
+//! - anything else: a static prop. A `sensor` property set to true makes its body a sensor.
+//!
+//! Rectangle objects get a `Box` body, circle objects a `Circle` body
+//! and polygon objects a `Polygon` body. Other ellipses get the `Capsule` body fitting
+//! inside them, lying along their longest axis. Object rotations are kept.
+//!
+//! A map `step_seconds` property switches the hero to grid movement,
+//! one tile (of the map's tile width) per step.
+//!
+//! The bounds of the map are the extent of its tile grid.
+
+use super::{
+    BodyDefinition, CompoundPartDefinition, EntityDefinition, EntityKind, MapDefinition,
+    ShapeDefinition, SpriteDefinition, SpriteGrid, SpriteSheetDefinition, TileLayerDefinition,
+    TilemapDefinition,
+};
+use crate::{
+    components::{Dynamic, FacingSprites},
+    resources::{MovementMode, WorldBounds},
+    states::Instance,
+    transition::TransitionEffect,
+};
+use amethyst::assets::{Result, ResultExt, SimpleFormat};
+use serde_derive::*;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Tile ids keep their flip flags in their highest bits.
+const TILE_ID_MASK: u32 = 0x1FFF_FFFF;
+/// Ellipses whose width and height differ by less than this are circles.
+const CIRCLE_TOLERANCE: f32 = 0.01;
+
+/// Format of a Tiled JSON export.
+/// The options are the directory of the map, relative to the asset directory,
+/// used to resolve tileset image paths.
+#[derive(Clone)]
+pub struct TiledFormat;
+
+impl SimpleFormat<MapDefinition> for TiledFormat {
+    const NAME: &'static str = "TILED_JSON";
+
+    type Options = String;
+
+    fn import(&self, bytes: Vec<u8>, directory: String) -> Result<MapDefinition> {
+        let map: TiledMap =
+            serde_json::from_slice(&bytes).chain_err(|| "Failed to parse Tiled map")?;
+        map.into_map_definition(&directory).map_err(Into::into)
+    }
+}
+
+#[derive(Deserialize)]
+struct TiledMap {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    layers: Vec<TiledLayer>,
+    tilesets: Vec<TiledTileset>,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum TiledLayer {
+    #[serde(rename = "tilelayer")]
+    Tiles {
+        name: String,
+        width: u32,
+        data: Vec<u32>,
+        #[serde(default)]
+        properties: Vec<TiledProperty>,
+    },
+    #[serde(rename = "objectgroup")]
+    Objects { objects: Vec<TiledObject> },
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Deserialize)]
+struct TiledTileset {
+    firstgid: u32,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    image: String,
+    #[serde(default)]
+    imagewidth: u32,
+    #[serde(default)]
+    imageheight: u32,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    columns: u32,
+    #[serde(default)]
+    tilecount: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    spacing: u32,
+}
+
+#[derive(Deserialize)]
+struct TiledObject {
+    id: u32,
+    /// Called `class` since Tiled 1.9.
+    #[serde(default, rename = "type", alias = "class")]
+    kind: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    ellipse: bool,
+    #[serde(default)]
+    polygon: Option<Vec<TiledPoint>>,
+    /// Clockwise, in degrees, around the object's anchor.
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default)]
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Deserialize)]
+struct TiledPoint {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+struct TiledProperty {
+    name: String,
+    value: Value,
+}
+
+impl TiledMap {
+    fn into_map_definition(self, directory: &str) -> std::result::Result<MapDefinition, String> {
+        let mut sprite_sheets = HashMap::new();
+        for tileset in &self.tilesets {
+            if tileset.source.is_some() {
+                return Err(format!(
+                    "Tileset starting at {} is external, only embedded tilesets are supported",
+                    tileset.firstgid
+                ));
+            }
+            sprite_sheets.insert(tileset_name(tileset), tileset.to_sprite_sheet(directory));
+        }
+
+        let mut instances = HashMap::new();
+        let mut entities = Vec::new();
+        let mut tile_layers = Vec::new();
+        let tile_count = (self.width * self.height) as usize;
+        let mut solid = vec![false; tile_count];
+        let map_height = (self.height * self.tileheight) as f32;
+
+        for layer in &self.layers {
+            match layer {
+                TiledLayer::Tiles {
+                    name,
+                    width,
+                    data,
+                    properties,
+                } => {
+                    if *width != self.width || data.len() != tile_count {
+                        return Err(format!(
+                            "Tile layer '{}' does not have the size of the map",
+                            name
+                        ));
+                    }
+
+                    let collision = properties
+                        .iter()
+                        .any(|p| p.name == "collision" && p.value.as_bool() == Some(true));
+
+                    // A tilemap layer uses a single sprite sheet, so split the layer by tileset.
+                    let mut layers_by_sheet = HashMap::<String, Vec<Option<usize>>>::new();
+                    for (index, gid) in data.iter().enumerate() {
+                        let sprite = match self.sprite(*gid) {
+                            Some(sprite) => sprite,
+                            None => continue,
+                        };
+                        if collision {
+                            solid[index] = true;
+                        }
+                        layers_by_sheet
+                            .entry(sprite.sheet)
+                            .or_insert_with(|| vec![None; tile_count])[index] = Some(sprite.index);
+                    }
+
+                    let mut layers = layers_by_sheet
+                        .into_iter()
+                        .map(|(sheet, tiles)| TileLayerDefinition { sheet, tiles })
+                        .collect::<Vec<_>>();
+                    layers.sort_by(|a, b| a.sheet.cmp(&b.sheet));
+                    tile_layers.extend(layers);
+                }
+                TiledLayer::Objects { objects } => {
+                    for object in objects {
+                        let entity = self.object_to_entity(object, map_height, &mut instances)?;
+                        entities.push(entity);
+                    }
+                }
+                TiledLayer::Unsupported => (),
+            }
+        }
+
+        let tilemap = if tile_layers.is_empty() {
+            None
+        } else {
+            Some(TilemapDefinition {
+                origin: (0.0, 0.0),
+                width: self.width as usize,
+                height: self.height as usize,
+                tile_width: self.tilewidth as f32,
+                tile_height: self.tileheight as f32,
+                layers: tile_layers,
+                solid,
+            })
+        };
+
+        let movement = self
+            .properties
+            .iter()
+            .find(|p| p.name == "step_seconds")
+            .and_then(|p| p.value.as_f64())
+            .map_or(MovementMode::Free, |step_seconds| MovementMode::Grid {
+                tile_size: self.tilewidth as f32,
+                step_seconds: step_seconds as f32,
+            });
+
+        let bounds = WorldBounds::new(0.0, (self.width * self.tilewidth) as f32, 0.0, map_height);
+
+        Ok(MapDefinition {
+            sprite_sheets,
+            instances,
+            entities,
+            tilemap,
+            movement,
+            bounds: Some(bounds),
+        })
+    }
+
+    /// Find the sprite matching a global tile id. Return None for empty tiles.
+    fn sprite(&self, gid: u32) -> Option<SpriteDefinition> {
+        let gid = gid & TILE_ID_MASK;
+        if gid == 0 {
+            return None;
+        }
+
+        self.tilesets
+            .iter()
+            .filter(|t| t.firstgid <= gid)
+            .max_by_key(|t| t.firstgid)
+            .map(|tileset| SpriteDefinition {
+                sheet: tileset_name(tileset),
+                index: (gid - tileset.firstgid) as usize,
+            })
+    }
+
+    fn object_to_entity(
+        &self,
+        object: &TiledObject,
+        map_height: f32,
+        instances: &mut HashMap<String, Instance>,
+    ) -> std::result::Result<EntityDefinition, String> {
+        let properties = object
+            .properties
+            .iter()
+            .map(|p| (p.name.as_str(), &p.value))
+            .collect::<HashMap<&str, &Value>>();
+        let float = |name: &str| -> std::result::Result<f32, String> {
+            properties
+                .get(name)
+                .and_then(|v| v.as_f64())
+                .map(|v| v as f32)
+                .ok_or_else(|| format!("Object {}: missing float property '{}'", object.id, name))
+        };
+
+        // Tiled's y axis points down and rotations are clockwise.
+        // Objects are anchored at their top left corner, tile objects at their bottom left corner
+        // and polygon points are relative to the anchor.
+        let angle = -object.rotation;
+        let offset = match (&object.polygon, object.gid) {
+            (Some(_), _) => (0.0, 0.0),
+            (None, Some(_)) => (object.width * 0.5, object.height * 0.5),
+            (None, None) => (object.width * 0.5, -object.height * 0.5),
+        };
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let x = object.x + offset.0 * cos - offset.1 * sin;
+        let y = map_height - object.y + offset.0 * sin + offset.1 * cos;
+        let sprite = object.gid.and_then(|gid| self.sprite(gid));
+
+        let shape = if let Some(points) = &object.polygon {
+            Some(ShapeDefinition::Polygon {
+                points: points.iter().map(|p| (p.x, -p.y)).collect(),
+            })
+        } else if object.width <= 0.0 || object.height <= 0.0 {
+            None
+        } else if object.ellipse {
+            Some(ellipse_shape(object.width, object.height))
+        } else {
+            Some(ShapeDefinition::Box {
+                half_width: object.width * 0.5,
+                half_height: object.height * 0.5,
+            })
+        };
+
+        let sensor = properties
+            .get("sensor")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let string = |name: &str| {
+            properties
+                .get(name)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| format!("Object {}: missing string property '{}'", object.id, name))
+        };
+
+        let level = |name: &str| -> std::result::Result<u32, String> {
+            let value = properties.get(name).ok_or_else(|| {
+                format!("Object {}: missing integer property '{}'", object.id, name)
+            })?;
+            match value.as_u64() {
+                Some(level) if level > 0 && level <= u64::from(u32::max_value()) => {
+                    Ok(level as u32)
+                }
+                _ => Err(format!(
+                    "Object {}: property '{}' must be a positive integer, found {}",
+                    object.id, name, value
+                )),
+            }
+        };
+
+        // Trigger zones are boxes, ncollide asserts that their sizes are positive.
+        let trigger_zone = |width: f32, height: f32| {
+            if width.is_finite() && height.is_finite() && width > 0.0 && height > 0.0 {
+                Ok((width * 0.5, height * 0.5))
+            } else {
+                Err(format!(
+                    "Object {}: the trigger zone must have a positive size, found {}x{}",
+                    object.id, width, height
+                ))
+            }
+        };
+
+        let (kind, body) = match object.kind.as_str() {
+            "mob" => {
+                let kind = EntityKind::Mob {
+                    reset_threshold: float("reset_threshold")?,
+                    target_threshold: float("target_threshold")?,
+                    species: string("species")?.to_owned(),
+                    level: level("level")?,
+                };
+                let body = shape.map(|shape| BodyDefinition {
+                    shape,
+                    dynamic: Dynamic::Dynamic,
+                    sensor: false,
+                });
+                (kind, body)
+            }
+            "portal" => {
+                let id = string("instance")?.to_owned();
+                let exit = (float("exit_x").unwrap_or(x), float("exit_y").unwrap_or(y));
+                instances.insert(
+                    id.clone(),
+                    Instance {
+                        name: string("name").unwrap_or(&id).to_owned(),
+                        map: string("map")?.to_owned(),
+                        spawn: (float("spawn_x")?, float("spawn_y")?),
+                        exit,
+                        transition: TransitionEffect::default(),
+                    },
+                );
+
+                let transition = match string("transition") {
+                    Ok(name) => parse_transition(name).ok_or_else(|| {
+                        format!("Object {}: unknown transition '{}'", object.id, name)
+                    })?,
+                    Err(_) => TransitionEffect::default(),
+                };
+                let kind = EntityKind::Portal {
+                    instance: id,
+                    trigger_zone: trigger_zone(object.width, object.height)?,
+                    transition,
+                };
+                (kind, None)
+            }
+            "chest" => {
+                let kind = EntityKind::Chest {
+                    items: parse_chest_items(string("items")?)
+                        .ok_or_else(|| format!("Object {}: invalid chest items", object.id))?,
+                    flag: string("flag")?.to_owned(),
+                    trigger_zone: trigger_zone(float("trigger_width")?, float("trigger_height")?)?,
+                };
+                let body = shape.map(|shape| BodyDefinition {
+                    shape,
+                    dynamic: Dynamic::Static,
+                    sensor: false,
+                });
+                (kind, body)
+            }
+            "npc" => {
+                let facing = |name: &str| float(name).ok().map(|index| index as usize);
+                let facing_sprites = match (
+                    facing("sprite_down"),
+                    facing("sprite_up"),
+                    facing("sprite_left"),
+                    facing("sprite_right"),
+                ) {
+                    (Some(down), Some(up), Some(left), Some(right)) => Some(FacingSprites {
+                        down,
+                        up,
+                        left,
+                        right,
+                    }),
+                    _ => None,
+                };
+                let kind = EntityKind::Npc {
+                    name: string("name")?.to_owned(),
+                    dialogue: string("dialogue")?.to_owned(),
+                    facing_sprites,
+                    trigger_zone: trigger_zone(float("trigger_width")?, float("trigger_height")?)?,
+                };
+                let body = shape.map(|shape| BodyDefinition {
+                    shape,
+                    dynamic: Dynamic::Static,
+                    sensor: false,
+                });
+                (kind, body)
+            }
+            "sign" => {
+                let kind = EntityKind::Sign {
+                    text: string("text")?.to_owned(),
+                    trigger_zone: trigger_zone(float("trigger_width")?, float("trigger_height")?)?,
+                };
+                let body = shape.map(|shape| BodyDefinition {
+                    shape,
+                    dynamic: Dynamic::Static,
+                    sensor: false,
+                });
+                (kind, body)
+            }
+            "battle" => {
+                let kind = EntityKind::Battle {
+                    species: string("species")?.to_owned(),
+                    level: level("level")?,
+                    won_flag: string("won_flag")?.to_owned(),
+                    trigger_zone: trigger_zone(float("trigger_width")?, float("trigger_height")?)?,
+                };
+                let body = shape.map(|shape| BodyDefinition {
+                    shape,
+                    dynamic: Dynamic::Static,
+                    sensor: false,
+                });
+                (kind, body)
+            }
+            _ => {
+                let body = shape.map(|shape| BodyDefinition {
+                    shape,
+                    dynamic: Dynamic::Static,
+                    sensor,
+                });
+                (EntityKind::Prop, body)
+            }
+        };
+
+        Ok(EntityDefinition {
+            position: (x, y),
+            angle,
+            sprite,
+            body,
+            kind,
+        })
+    }
+}
+
+impl TiledTileset {
+    fn to_sprite_sheet(&self, directory: &str) -> SpriteSheetDefinition {
+        SpriteSheetDefinition::Grid {
+            texture: resolve_path(directory, &self.image),
+            grid: SpriteGrid {
+                image_width: self.imagewidth,
+                image_height: self.imageheight,
+                sprite_width: self.tilewidth,
+                sprite_height: self.tileheight,
+                columns: self.columns,
+                count: self.tilecount,
+                margin: self.margin,
+                spacing: self.spacing,
+            },
+        }
+    }
+}
+
+/// A circle for round ellipses, otherwise a capsule along the longest axis of the ellipse.
+fn ellipse_shape(width: f32, height: f32) -> ShapeDefinition {
+    if (width - height).abs() < CIRCLE_TOLERANCE {
+        return ShapeDefinition::Circle {
+            radius: width * 0.5,
+        };
+    }
+
+    let radius = width.min(height) * 0.5;
+    let capsule = ShapeDefinition::Capsule {
+        half_height: width.max(height) * 0.5 - radius,
+        radius,
+    };
+    if height > width {
+        capsule
+    } else {
+        ShapeDefinition::Compound {
+            parts: vec![CompoundPartDefinition {
+                offset: (0.0, 0.0),
+                angle: 90.0,
+                shape: capsule,
+            }],
+        }
+    }
+}
+
+/// Tilesets are referenced by their first global id, which is unique inside a map.
+fn tileset_name(tileset: &TiledTileset) -> String {
+    format!("tileset_{}", tileset.firstgid)
+}
+
+/// Parse chest items written as `potion:2,rustball`. Return None if a count is invalid.
+fn parse_chest_items(items: &str) -> Option<Vec<(String, u32)>> {
+    items
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| match item.find(':') {
+            Some(colon) => {
+                let count = item[colon + 1..].trim().parse().ok()?;
+                Some((item[..colon].trim().to_owned(), count))
+            }
+            None => Some((item.to_owned(), 1)),
+        })
+        .collect()
+}
+
+/// Parse the name of a transition effect, as written in a portal's `transition` property.
+fn parse_transition(name: &str) -> Option<TransitionEffect> {
+    match name {
+        "fade" => Some(TransitionEffect::Fade),
+        "iris" => Some(TransitionEffect::Iris),
+        "battle_swirl" => Some(TransitionEffect::BattleSwirl),
+        _ => None,
+    }
+}
+
+/// Resolve a path relative to `directory`, handling `..` components.
+fn resolve_path(directory: &str, path: &str) -> String {
+    let mut components = directory
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<&str>>();
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            }
+            c => components.push(c),
+        }
+    }
+
+    components.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A 4x3 map of 16x16 tiles, 48 units high, with a tileset and the given layers.
+    fn import(layers: Value) -> std::result::Result<MapDefinition, String> {
+        let map = json!({
+            "width": 4,
+            "height": 3,
+            "tilewidth": 16,
+            "tileheight": 16,
+            "layers": layers,
+            "tilesets": [{
+                "firstgid": 1,
+                "image": "../tilesets/grass.png",
+                "imagewidth": 64,
+                "imageheight": 64,
+                "tilewidth": 16,
+                "tileheight": 16,
+                "columns": 4,
+                "tilecount": 16,
+            }],
+        });
+        serde_json::from_value::<TiledMap>(map)
+            .unwrap()
+            .into_map_definition("maps/town")
+    }
+
+    fn import_objects(objects: Value) -> std::result::Result<Vec<EntityDefinition>, String> {
+        import(json!([{ "type": "objectgroup", "objects": objects }])).map(|map| map.entities)
+    }
+
+    fn assert_close((x, y): (f32, f32), (expected_x, expected_y): (f32, f32)) {
+        assert!(
+            (x - expected_x).abs() < 1e-4 && (y - expected_y).abs() < 1e-4,
+            "({}, {}) is not ({}, {})",
+            x,
+            y,
+            expected_x,
+            expected_y
+        );
+    }
+
+    fn shape(entity: &EntityDefinition) -> Value {
+        serde_json::to_value(&entity.body.as_ref().unwrap().shape).unwrap()
+    }
+
+    #[test]
+    fn objects_are_flipped_and_rotated_around_their_anchor() {
+        let entities = import_objects(json!([
+            { "id": 1, "x": 16, "y": 8, "width": 32, "height": 16 },
+            { "id": 2, "x": 16, "y": 8, "width": 32, "height": 16, "rotation": 90 },
+            { "id": 3, "x": 0, "y": 48, "width": 16, "height": 16, "gid": 2 },
+            {
+                "id": 4, "x": 32, "y": 16,
+                "polygon": [{ "x": 0, "y": 0 }, { "x": 16, "y": 0 }, { "x": 0, "y": 8 }],
+            },
+        ]))
+        .unwrap();
+
+        // The center of the rectangle, 40 units above the bottom of the map.
+        assert_close(entities[0].position, (32.0, 32.0));
+        assert_eq!(entities[0].angle, 0.0);
+        assert_eq!(
+            shape(&entities[0]),
+            json!({ "Box": { "half_width": 16.0, "half_height": 8.0 } })
+        );
+        // Turned clockwise around its top left corner, the rectangle now hangs below it
+        // and to its left.
+        assert_close(entities[1].position, (8.0, 24.0));
+        assert_eq!(entities[1].angle, -90.0);
+        // Tile objects are anchored at their bottom left corner.
+        assert_close(entities[2].position, (8.0, 8.0));
+        // Polygons keep their anchor, their points are flipped.
+        assert_close(entities[3].position, (32.0, 32.0));
+        assert_eq!(
+            shape(&entities[3]),
+            json!({ "Polygon": { "points": [[0.0, 0.0], [16.0, 0.0], [0.0, -8.0]] } })
+        );
+    }
+
+    #[test]
+    fn flip_flags_are_ignored() {
+        let flipped = |gid: u32| gid | 0x8000_0000;
+        let map = import(json!([{
+            "type": "tilelayer",
+            "name": "ground",
+            "width": 4,
+            "data": [0, 1, flipped(2), 0x4000_0003, 0, 0, 0, 0, 0, 0, 0, 0x2000_0010],
+        }]))
+        .unwrap();
+        let tiles = &map.tilemap.unwrap().layers[0].tiles;
+        assert_eq!(tiles[..4], [None, Some(0), Some(1), Some(2)]);
+        assert_eq!(tiles[11], Some(15));
+
+        let entities = import_objects(json!([
+            { "id": 1, "x": 0, "y": 16, "width": 16, "height": 16, "gid": flipped(5) },
+        ]))
+        .unwrap();
+        assert_eq!(entities[0].sprite.as_ref().unwrap().index, 4);
+    }
+
+    #[test]
+    fn ellipses_become_circles_or_capsules() {
+        let entities = import_objects(json!([
+            { "id": 1, "x": 0, "y": 16, "width": 16, "height": 16, "ellipse": true },
+            { "id": 2, "x": 0, "y": 16, "width": 16, "height": 40, "ellipse": true },
+            { "id": 3, "x": 0, "y": 16, "width": 40, "height": 16, "ellipse": true },
+        ]))
+        .unwrap();
+        assert_eq!(shape(&entities[0]), json!({ "Circle": { "radius": 8.0 } }));
+        let capsule = json!({ "Capsule": { "half_height": 12.0, "radius": 8.0 } });
+        assert_eq!(shape(&entities[1]), capsule);
+        assert_eq!(
+            shape(&entities[2]),
+            json!({
+                "Compound": {
+                    "parts": [{ "offset": [0.0, 0.0], "angle": 90.0, "shape": capsule }],
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn levels_are_positive_integers() {
+        let mob = |level: Value| {
+            import_objects(json!([{
+                "id": 7,
+                "type": "mob",
+                "x": 0,
+                "y": 16,
+                "width": 16,
+                "height": 16,
+                "properties": [
+                    { "name": "reset_threshold", "type": "float", "value": 100.0 },
+                    { "name": "target_threshold", "type": "float", "value": 50.0 },
+                    { "name": "species", "type": "string", "value": "ferris" },
+                    { "name": "level", "type": "int", "value": level },
+                ],
+            }]))
+        };
+
+        match &mob(json!(12)).unwrap()[0].kind {
+            EntityKind::Mob { species, level, .. } => {
+                assert_eq!(species, "ferris");
+                assert_eq!(*level, 12);
+            }
+            _ => panic!("not a mob"),
+        }
+        for level in &[json!(-1), json!(2.5), json!(0), json!("5")] {
+            match mob(level.clone()) {
+                Err(error) => assert!(error.contains("'level'"), "{}", error),
+                Ok(_) => panic!("level {} accepted", level),
+            }
+        }
+    }
+
+    #[test]
+    fn trigger_zones_have_a_positive_size() {
+        let sign = |width: f32, height: f32| {
+            import_objects(json!([{
+                "id": 3,
+                "type": "sign",
+                "x": 0,
+                "y": 16,
+                "properties": [
+                    { "name": "text", "type": "string", "value": "Town" },
+                    { "name": "trigger_width", "type": "float", "value": width },
+                    { "name": "trigger_height", "type": "float", "value": height },
+                ],
+            }]))
+        };
+        let portal = |width: f32, height: f32| {
+            import_objects(json!([{
+                "id": 4,
+                "type": "portal",
+                "x": 0,
+                "y": 16,
+                "width": width,
+                "height": height,
+                "properties": [
+                    { "name": "instance", "type": "string", "value": "house" },
+                    { "name": "map", "type": "string", "value": "house.json" },
+                    { "name": "spawn_x", "type": "float", "value": 0.0 },
+                    { "name": "spawn_y", "type": "float", "value": 0.0 },
+                ],
+            }]))
+        };
+
+        match &sign(16.0, 8.0).unwrap()[0].kind {
+            EntityKind::Sign { trigger_zone, .. } => assert_eq!(*trigger_zone, (8.0, 4.0)),
+            _ => panic!("not a sign"),
+        }
+        match &portal(16.0, 8.0).unwrap()[0].kind {
+            EntityKind::Portal { trigger_zone, .. } => assert_eq!(*trigger_zone, (8.0, 4.0)),
+            _ => panic!("not a portal"),
+        }
+        for &(width, height) in &[(0.0, 8.0), (16.0, -8.0)] {
+            for result in vec![sign(width, height), portal(width, height)] {
+                match result {
+                    Err(error) => assert!(error.contains("trigger zone"), "{}", error),
+                    Ok(_) => panic!("{}x{} accepted", width, height),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chest_items_are_counted() {
+        assert_eq!(
+            parse_chest_items("potion:2, rustball ,ether : 3,"),
+            Some(vec![
+                ("potion".to_owned(), 2),
+                ("rustball".to_owned(), 1),
+                ("ether".to_owned(), 3),
+            ])
+        );
+        assert_eq!(parse_chest_items(""), Some(Vec::new()));
+        assert_eq!(parse_chest_items("potion:two"), None);
+        assert_eq!(parse_chest_items("potion:-1"), None);
+    }
+
+    #[test]
+    fn paths_are_resolved_from_the_map_directory() {
+        assert_eq!(
+            resolve_path("maps/town", "tiles.png"),
+            "maps/town/tiles.png"
+        );
+        assert_eq!(
+            resolve_path("maps/town/", "../tilesets/./grass.png"),
+            "maps/tilesets/grass.png"
+        );
+        assert_eq!(resolve_path("maps", "../../grass.png"), "grass.png");
+        assert_eq!(
+            resolve_path("", "tilesets//grass.png"),
+            "tilesets/grass.png"
+        );
+
+        let map = import(json!([])).unwrap();
+        match &map.sprite_sheets["tileset_1"] {
+            SpriteSheetDefinition::Grid { texture, .. } => {
+                assert_eq!(texture, "maps/tilesets/grass.png")
+            }
+            _ => panic!("not a grid"),
+        }
+    }
+}
@@ -1,4 +1,12 @@
-#[derive(Copy, Clone, Debug)]
+mod party;
+
+pub use self::party::{BoxSlot, Party, PartyError, Storage};
+
+use crate::components::LayerId;
+use serde_derive::*;
+use std::collections::HashSet;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct WorldBounds {
     pub left: f32,
     pub right: f32,
@@ -26,3 +34,165 @@ impl WorldBounds {
         }
     }
 }
+
+/// How the hero moves on the current map.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum MovementMode {
+    /// Analog movement in any direction.
+    Free,
+    /// Tile locked movement: the hero moves one tile at a time, in four directions.
+    Grid {
+        /// Size of a tile, tiles are centered on `(n + 0.5) * tile_size`.
+        tile_size: f32,
+        /// Duration of a step from a tile to the next one.
+        step_seconds: f32,
+    },
+}
+
+impl Default for MovementMode {
+    fn default() -> Self {
+        MovementMode::Free
+    }
+}
+
+/// Default duration of a simulation tick, 60 ticks per second.
+const DEFAULT_TICK_SECONDS: f32 = 1.0 / 60.0;
+/// Drop the time which could not be simulated rather than trying to catch up forever.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+
+/// Clock of the fixed rate simulation.
+#[derive(Copy, Clone, Debug)]
+pub struct SimulationTime {
+    tick_seconds: f32,
+    /// Time not simulated yet.
+    accumulator: f32,
+}
+
+impl Default for SimulationTime {
+    fn default() -> Self {
+        SimulationTime {
+            tick_seconds: DEFAULT_TICK_SECONDS,
+            accumulator: 0.0,
+        }
+    }
+}
+
+impl SimulationTime {
+    /// Duration of a simulation tick, in seconds.
+    pub fn tick_seconds(&self) -> f32 {
+        self.tick_seconds
+    }
+
+    /// Progression between the last two ticks, from 0 to 1.
+    /// Used to interpolate what is rendered between the simulated states.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.tick_seconds
+    }
+
+    /// Add the time elapsed since the last frame and return the number of ticks to simulate.
+    pub fn advance(&mut self, delta_seconds: f32) -> u32 {
+        self.accumulator += delta_seconds;
+        let ticks = (self.accumulator / self.tick_seconds).floor() as u32;
+        if ticks > MAX_TICKS_PER_FRAME {
+            self.accumulator = 0.0;
+            MAX_TICKS_PER_FRAME
+        } else {
+            self.accumulator -= ticks as f32 * self.tick_seconds;
+            ticks
+        }
+    }
+}
+
+/// Story progression flags, set by the events of the game and kept in saves.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Flags {
+    flags: HashSet<String>,
+}
+
+impl Flags {
+    pub fn is_set(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+
+    pub fn set<S: Into<String>>(&mut self, flag: S) {
+        self.flags.insert(flag.into());
+    }
+
+    pub fn unset(&mut self, flag: &str) {
+        self.flags.remove(flag);
+    }
+}
+
+/// The layers whose entities are active and shown, see the `Layer` component.
+/// Each state sets the layers it shows when it starts or resumes.
+#[derive(Clone, Debug, Default)]
+pub struct ActiveLayers {
+    layers: HashSet<LayerId>,
+}
+
+impl ActiveLayers {
+    pub fn contains(&self, id: LayerId) -> bool {
+        self.layers.contains(&id)
+    }
+
+    /// Show these layers only.
+    pub fn set(&mut self, layers: &[LayerId]) {
+        self.layers = layers.iter().cloned().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: f32 = DEFAULT_TICK_SECONDS;
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 1e-4,
+            "{} is not {}",
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn frames_shorter_than_a_tick_are_not_simulated() {
+        let mut time = SimulationTime::default();
+        assert_eq!(time.advance(TICK * 0.4), 0);
+        assert_close(time.alpha(), 0.4);
+    }
+
+    #[test]
+    fn leftover_time_is_carried_to_the_next_frame() {
+        let mut time = SimulationTime::default();
+        assert_eq!(time.advance(TICK * 1.6), 1);
+        assert_close(time.alpha(), 0.6);
+        assert_eq!(time.advance(TICK * 0.6), 1);
+        assert_close(time.alpha(), 0.2);
+        // 10 frames of 0.25 ticks.
+        let ticks = (0..10).map(|_| time.advance(TICK * 0.25)).sum::<u32>();
+        assert_eq!(ticks, 2);
+        assert_close(time.alpha(), 0.7);
+    }
+
+    #[test]
+    fn long_frames_drop_the_time_past_the_tick_limit() {
+        let mut time = SimulationTime::default();
+        assert_eq!(time.advance(TICK * 5.5), MAX_TICKS_PER_FRAME);
+        assert_close(time.alpha(), 0.5);
+        assert_eq!(time.advance(TICK * 100.5), MAX_TICKS_PER_FRAME);
+        assert_close(time.alpha(), 0.0);
+        assert_eq!(time.advance(TICK * 0.5), 0);
+    }
+
+    #[test]
+    fn alpha_stays_below_one() {
+        let mut time = SimulationTime::default();
+        for frame in 0..1000 {
+            time.advance(TICK * (frame % 7) as f32 * 0.37);
+            let alpha = time.alpha();
+            assert!(0.0 <= alpha && alpha < 1.0, "frame {}: {}", frame, alpha);
+        }
+    }
+}
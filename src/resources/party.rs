@@ -0,0 +1,545 @@
+use crate::creatures::Creature;
+use serde_derive::*;
+use std::{convert::TryFrom, error::Error, fmt};
+
+/// Maximum number of creatures in the party.
+pub const MAX_PARTY_SIZE: usize = 6;
+/// Number of boxes in the storage.
+pub const BOX_COUNT: usize = 8;
+/// Number of creatures each box can hold.
+pub const BOX_SIZE: usize = 30;
+
+/// An operation on the party or the storage which can not be done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartyError {
+    PartyFull,
+    BoxFull,
+    /// The party must keep at least one creature.
+    LastCreature,
+    /// There is no creature at this position.
+    NoSuchCreature,
+}
+
+impl fmt::Display for PartyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartyError::PartyFull => write!(f, "The party is full."),
+            PartyError::BoxFull => write!(f, "This box is full."),
+            PartyError::LastCreature => write!(f, "The party can't be left empty."),
+            PartyError::NoSuchCreature => write!(f, "There is no creature here."),
+        }
+    }
+}
+
+impl Error for PartyError {}
+
+/// Creatures owned by the player and carried around, the first one goes first in battles.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(try_from = "PartyData")]
+pub struct Party {
+    creatures: Vec<Creature>,
+}
+
+/// A party as saved, checked by `Party::new` when loaded.
+#[derive(Deserialize)]
+struct PartyData {
+    creatures: Vec<Creature>,
+}
+
+impl TryFrom<PartyData> for Party {
+    type Error = PartyError;
+
+    fn try_from(data: PartyData) -> Result<Self, PartyError> {
+        Party::new(data.creatures)
+    }
+}
+
+impl Party {
+    /// Fail if there are more creatures than the party can hold.
+    pub fn new(creatures: Vec<Creature>) -> Result<Self, PartyError> {
+        if creatures.len() > MAX_PARTY_SIZE {
+            return Err(PartyError::PartyFull);
+        }
+        Ok(Party { creatures })
+    }
+
+    pub fn creatures(&self) -> &[Creature] {
+        &self.creatures
+    }
+
+    pub fn creatures_mut(&mut self) -> &mut [Creature] {
+        &mut self.creatures
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.creatures.len() >= MAX_PARTY_SIZE
+    }
+
+    /// Add a creature at the end of the party. Give it back if the party is full.
+    pub fn add(&mut self, creature: Creature) -> Result<(), Creature> {
+        if self.is_full() {
+            return Err(creature);
+        }
+        self.creatures.push(creature);
+        Ok(())
+    }
+
+    /// Remove a creature from the party, it is gone for good.
+    pub fn release(&mut self, index: usize) -> Result<Creature, PartyError> {
+        self.check_removable(index)?;
+        Ok(self.creatures.remove(index))
+    }
+
+    /// Exchange the positions of two creatures.
+    pub fn swap(&mut self, first: usize, second: usize) -> Result<(), PartyError> {
+        if first >= self.creatures.len() || second >= self.creatures.len() {
+            return Err(PartyError::NoSuchCreature);
+        }
+        self.creatures.swap(first, second);
+        Ok(())
+    }
+
+    /// Move a creature to another position, shifting the creatures in between.
+    pub fn reorder(&mut self, from: usize, to: usize) -> Result<(), PartyError> {
+        if from >= self.creatures.len() || to >= self.creatures.len() {
+            return Err(PartyError::NoSuchCreature);
+        }
+        let creature = self.creatures.remove(from);
+        self.creatures.insert(to, creature);
+        Ok(())
+    }
+
+    fn check_removable(&self, index: usize) -> Result<(), PartyError> {
+        if index >= self.creatures.len() {
+            Err(PartyError::NoSuchCreature)
+        } else if self.creatures.len() == 1 {
+            Err(PartyError::LastCreature)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A box of the storage, with a fixed number of slots.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "StorageBoxData")]
+pub struct StorageBox {
+    pub name: String,
+    slots: Vec<Option<Creature>>,
+}
+
+/// A box as saved. Missing slots are empty, a box with too many slots is rejected.
+#[derive(Deserialize)]
+struct StorageBoxData {
+    name: String,
+    slots: Vec<Option<Creature>>,
+}
+
+impl TryFrom<StorageBoxData> for StorageBox {
+    type Error = PartyError;
+
+    fn try_from(mut data: StorageBoxData) -> Result<Self, PartyError> {
+        if data.slots.len() > BOX_SIZE {
+            return Err(PartyError::BoxFull);
+        }
+        data.slots.resize(BOX_SIZE, None);
+        Ok(StorageBox {
+            name: data.name,
+            slots: data.slots,
+        })
+    }
+}
+
+impl StorageBox {
+    fn new(name: String) -> Self {
+        StorageBox {
+            name,
+            slots: vec![None; BOX_SIZE],
+        }
+    }
+
+    pub fn slots(&self) -> &[Option<Creature>] {
+        &self.slots
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&Creature> {
+        self.slots.get(slot).and_then(Option::as_ref)
+    }
+
+    /// Put a creature in the first free slot, return the slot or give the creature back.
+    fn store(&mut self, creature: Creature) -> Result<usize, Creature> {
+        match self.slots.iter().position(Option::is_none) {
+            Some(slot) => {
+                self.slots[slot] = Some(creature);
+                Ok(slot)
+            }
+            None => Err(creature),
+        }
+    }
+
+    fn take(&mut self, slot: usize) -> Result<Creature, PartyError> {
+        self.slots
+            .get_mut(slot)
+            .and_then(Option::take)
+            .ok_or(PartyError::NoSuchCreature)
+    }
+}
+
+/// Where a creature is kept in the storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoxSlot {
+    pub storage_box: usize,
+    pub slot: usize,
+}
+
+/// Creatures owned by the player which are not in the party, sorted in boxes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Storage {
+    boxes: Vec<StorageBox>,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage {
+            boxes: (1..=BOX_COUNT)
+                .map(|index| StorageBox::new(format!("Box {}", index)))
+                .collect(),
+        }
+    }
+}
+
+impl Storage {
+    pub fn boxes(&self) -> &[StorageBox] {
+        &self.boxes
+    }
+
+    pub fn get(&self, location: BoxSlot) -> Option<&Creature> {
+        self.boxes.get(location.storage_box)?.get(location.slot)
+    }
+
+    /// Put a creature in the first free slot of any box, used for captures when the party is full.
+    /// Give the creature back if every box is full.
+    pub fn add(&mut self, mut creature: Creature) -> Result<BoxSlot, Creature> {
+        for (index, storage_box) in self.boxes.iter_mut().enumerate() {
+            match storage_box.store(creature) {
+                Ok(slot) => {
+                    return Ok(BoxSlot {
+                        storage_box: index,
+                        slot,
+                    })
+                }
+                Err(rejected) => creature = rejected,
+            }
+        }
+        Err(creature)
+    }
+
+    /// Move a creature of the party to a box.
+    pub fn deposit(
+        &mut self,
+        party: &mut Party,
+        index: usize,
+        storage_box: usize,
+    ) -> Result<BoxSlot, PartyError> {
+        party.check_removable(index)?;
+        let target = self
+            .boxes
+            .get_mut(storage_box)
+            .ok_or(PartyError::NoSuchCreature)?;
+        let slot = target
+            .slots
+            .iter()
+            .position(Option::is_none)
+            .ok_or(PartyError::BoxFull)?;
+        target.slots[slot] = Some(party.creatures.remove(index));
+        Ok(BoxSlot { storage_box, slot })
+    }
+
+    /// Move a creature of a box to the end of the party.
+    pub fn withdraw(&mut self, party: &mut Party, location: BoxSlot) -> Result<(), PartyError> {
+        if party.is_full() {
+            return Err(PartyError::PartyFull);
+        }
+        let creature = self.take(location)?;
+        party.creatures.push(creature);
+        Ok(())
+    }
+
+    /// Exchange a creature of the party with a creature of a box.
+    pub fn swap(
+        &mut self,
+        party: &mut Party,
+        index: usize,
+        location: BoxSlot,
+    ) -> Result<(), PartyError> {
+        if self.get(location).is_none() || index >= party.creatures.len() {
+            return Err(PartyError::NoSuchCreature);
+        }
+        let slot = &mut self.boxes[location.storage_box].slots[location.slot];
+        std::mem::swap(slot.as_mut().unwrap(), &mut party.creatures[index]);
+        Ok(())
+    }
+
+    /// Move a creature to another slot, exchanging it with the creature already there if any.
+    pub fn move_creature(&mut self, from: BoxSlot, to: BoxSlot) -> Result<(), PartyError> {
+        if self.get(from).is_none()
+            || self.boxes.get(to.storage_box).is_none()
+            || to.slot >= BOX_SIZE
+        {
+            return Err(PartyError::NoSuchCreature);
+        }
+        let creature = self.take(from)?;
+        let previous = self.boxes[to.storage_box].slots[to.slot].replace(creature);
+        self.boxes[from.storage_box].slots[from.slot] = previous;
+        Ok(())
+    }
+
+    /// Remove a creature from the storage, it is gone for good.
+    pub fn release(&mut self, location: BoxSlot) -> Result<Creature, PartyError> {
+        self.take(location)
+    }
+
+    fn take(&mut self, location: BoxSlot) -> Result<Creature, PartyError> {
+        self.boxes
+            .get_mut(location.storage_box)
+            .ok_or(PartyError::NoSuchCreature)?
+            .take(location.slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creatures::Stats;
+
+    fn creature(species: &str) -> Creature {
+        Creature {
+            species: species.to_owned(),
+            nickname: None,
+            level: 5,
+            experience: 0,
+            ivs: Stats::default(),
+            evs: Stats::default(),
+            hp: 20,
+            moves: Vec::new(),
+            status: None,
+        }
+    }
+
+    fn party(species: &[&str]) -> Party {
+        Party::new(species.iter().map(|id| creature(id)).collect()).unwrap()
+    }
+
+    fn species(creatures: &[Creature]) -> Vec<&str> {
+        creatures.iter().map(|c| c.species.as_str()).collect()
+    }
+
+    fn slot(storage_box: usize, slot: usize) -> BoxSlot {
+        BoxSlot { storage_box, slot }
+    }
+
+    #[test]
+    fn a_party_can_not_be_created_over_its_size() {
+        let creatures = vec![creature("ferris"); MAX_PARTY_SIZE + 1];
+        assert_eq!(Party::new(creatures).unwrap_err(), PartyError::PartyFull);
+        assert!(Party::new(vec![creature("ferris"); MAX_PARTY_SIZE])
+            .unwrap()
+            .is_full());
+    }
+
+    #[test]
+    fn creatures_are_added_until_the_party_is_full() {
+        let mut party = party(&["ferris"; MAX_PARTY_SIZE - 1]);
+        assert!(party.add(creature("crab")).is_ok());
+        let rejected = party.add(creature("gopher")).unwrap_err();
+        assert_eq!(rejected.species, "gopher");
+        assert_eq!(party.creatures().len(), MAX_PARTY_SIZE);
+    }
+
+    #[test]
+    fn the_last_creature_can_not_be_released() {
+        let mut party = party(&["ferris", "crab"]);
+        assert_eq!(party.release(2).unwrap_err(), PartyError::NoSuchCreature);
+        assert_eq!(party.release(0).unwrap().species, "ferris");
+        assert_eq!(party.release(0).unwrap_err(), PartyError::LastCreature);
+        assert_eq!(species(party.creatures()), ["crab"]);
+    }
+
+    #[test]
+    fn party_creatures_are_swapped_and_reordered() {
+        let mut party = party(&["a", "b", "c"]);
+        party.swap(0, 2).unwrap();
+        assert_eq!(species(party.creatures()), ["c", "b", "a"]);
+        party.reorder(0, 2).unwrap();
+        assert_eq!(species(party.creatures()), ["b", "a", "c"]);
+
+        assert_eq!(party.swap(0, 3).unwrap_err(), PartyError::NoSuchCreature);
+        assert_eq!(party.reorder(3, 0).unwrap_err(), PartyError::NoSuchCreature);
+        assert_eq!(party.reorder(0, 3).unwrap_err(), PartyError::NoSuchCreature);
+        assert_eq!(species(party.creatures()), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn creatures_are_deposited_and_withdrawn() {
+        let mut party = party(&["ferris", "crab"]);
+        let mut storage = Storage::default();
+
+        let location = storage.deposit(&mut party, 1, 2).unwrap();
+        assert_eq!(location, slot(2, 0));
+        assert_eq!(storage.get(location).unwrap().species, "crab");
+        assert_eq!(
+            storage.deposit(&mut party, 0, 2).unwrap_err(),
+            PartyError::LastCreature
+        );
+        assert_eq!(
+            storage.deposit(&mut party, 1, 2).unwrap_err(),
+            PartyError::NoSuchCreature
+        );
+
+        storage.withdraw(&mut party, location).unwrap();
+        assert_eq!(species(party.creatures()), ["ferris", "crab"]);
+        assert!(storage.get(location).is_none());
+        assert_eq!(
+            storage.withdraw(&mut party, location).unwrap_err(),
+            PartyError::NoSuchCreature
+        );
+    }
+
+    #[test]
+    fn deposits_fail_on_full_or_missing_boxes() {
+        let mut party = party(&["ferris", "crab"]);
+        let mut storage = Storage::default();
+        for _ in 0..BOX_SIZE {
+            storage.add(creature("gopher")).unwrap();
+        }
+        assert_eq!(
+            storage.deposit(&mut party, 1, 0).unwrap_err(),
+            PartyError::BoxFull
+        );
+        assert_eq!(
+            storage.deposit(&mut party, 1, BOX_COUNT).unwrap_err(),
+            PartyError::NoSuchCreature
+        );
+        assert_eq!(party.creatures().len(), 2);
+    }
+
+    #[test]
+    fn withdrawing_into_a_full_party_fails() {
+        let mut party = party(&["ferris"; MAX_PARTY_SIZE]);
+        let mut storage = Storage::default();
+        let location = storage.add(creature("crab")).unwrap();
+        assert_eq!(
+            storage.withdraw(&mut party, location).unwrap_err(),
+            PartyError::PartyFull
+        );
+        assert_eq!(storage.get(location).unwrap().species, "crab");
+    }
+
+    #[test]
+    fn added_creatures_fill_the_boxes_in_order() {
+        let mut storage = Storage::default();
+        for index in 0..BOX_COUNT * BOX_SIZE {
+            let location = storage.add(creature("gopher")).unwrap();
+            assert_eq!(location, slot(index / BOX_SIZE, index % BOX_SIZE));
+        }
+        assert_eq!(storage.add(creature("crab")).unwrap_err().species, "crab");
+    }
+
+    #[test]
+    fn party_and_box_creatures_are_swapped() {
+        let mut party = party(&["ferris", "crab"]);
+        let mut storage = Storage::default();
+        let location = storage.add(creature("gopher")).unwrap();
+
+        storage.swap(&mut party, 0, location).unwrap();
+        assert_eq!(species(party.creatures()), ["gopher", "crab"]);
+        assert_eq!(storage.get(location).unwrap().species, "ferris");
+
+        assert_eq!(
+            storage.swap(&mut party, 2, location).unwrap_err(),
+            PartyError::NoSuchCreature
+        );
+        assert_eq!(
+            storage.swap(&mut party, 0, slot(0, 1)).unwrap_err(),
+            PartyError::NoSuchCreature
+        );
+    }
+
+    #[test]
+    fn box_creatures_are_moved_and_exchanged() {
+        let mut storage = Storage::default();
+        let first = storage.add(creature("ferris")).unwrap();
+        let second = storage.add(creature("crab")).unwrap();
+
+        // To an empty slot of another box.
+        storage.move_creature(first, slot(1, 4)).unwrap();
+        assert!(storage.get(first).is_none());
+        assert_eq!(storage.get(slot(1, 4)).unwrap().species, "ferris");
+
+        // To an occupied slot, the creatures are exchanged.
+        storage.move_creature(second, slot(1, 4)).unwrap();
+        assert_eq!(storage.get(second).unwrap().species, "ferris");
+        assert_eq!(storage.get(slot(1, 4)).unwrap().species, "crab");
+
+        for (from, to) in &[
+            (first, second),
+            (second, slot(BOX_COUNT, 0)),
+            (second, slot(0, BOX_SIZE)),
+        ] {
+            assert_eq!(
+                storage.move_creature(*from, *to).unwrap_err(),
+                PartyError::NoSuchCreature
+            );
+        }
+    }
+
+    #[test]
+    fn box_creatures_are_released() {
+        let mut storage = Storage::default();
+        let location = storage.add(creature("ferris")).unwrap();
+        assert_eq!(storage.release(location).unwrap().species, "ferris");
+        assert_eq!(
+            storage.release(location).unwrap_err(),
+            PartyError::NoSuchCreature
+        );
+        assert_eq!(
+            storage.release(slot(BOX_COUNT, 0)).unwrap_err(),
+            PartyError::NoSuchCreature
+        );
+    }
+
+    #[test]
+    fn saved_parties_are_checked_when_loaded() {
+        let saved = |count| {
+            let creatures = vec![creature("sparky"); count];
+            ron::ser::to_string(&Party { creatures }).unwrap()
+        };
+        let loaded = ron::de::from_str::<Party>(&saved(MAX_PARTY_SIZE)).unwrap();
+        assert_eq!(loaded.creatures().len(), MAX_PARTY_SIZE);
+        assert!(ron::de::from_str::<Party>(&saved(MAX_PARTY_SIZE + 1)).is_err());
+    }
+
+    #[test]
+    fn saved_boxes_are_padded_or_rejected() {
+        let saved = |slots| {
+            let storage_box = StorageBox {
+                name: "Box 1".to_owned(),
+                slots: vec![Some(creature("sparky")); slots],
+            };
+            ron::ser::to_string(&Storage {
+                boxes: vec![storage_box],
+            })
+            .unwrap()
+        };
+        let mut storage = ron::de::from_str::<Storage>(&saved(2)).unwrap();
+        assert_eq!(storage.boxes()[0].slots().len(), BOX_SIZE);
+        storage
+            .move_creature(slot(0, 1), slot(0, BOX_SIZE - 1))
+            .unwrap();
+        assert_eq!(
+            storage.get(slot(0, BOX_SIZE - 1)).unwrap().species,
+            "sparky"
+        );
+        assert!(ron::de::from_str::<Storage>(&saved(BOX_SIZE + 1)).is_err());
+    }
+}
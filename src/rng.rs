@@ -0,0 +1,59 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Small deterministic pseudo random number generator (xorshift64*).
+/// Used as a resource so that a seed fully determines the game's randomness.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(0x9E37_79B9_7F4A_7C15)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // The state must never be zero.
+        Rng {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// Seed the generator from the current time.
+    pub fn from_time() -> Self {
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Rng::new(elapsed.as_secs() ^ (u64::from(elapsed.subsec_nanos()) << 32))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform float in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform integer in [min, max], bounds included.
+    pub fn range(&mut self, min: u32, max: u32) -> u32 {
+        debug_assert!(min <= max);
+        let span = u64::from(max - min) + 1;
+        min + (self.next_u64() % span) as u32
+    }
+
+    /// Return true with the given probability, between 0 and 1.
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+}
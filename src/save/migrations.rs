@@ -0,0 +1,281 @@
+use super::{SaveError, SavedMob};
+use crate::resources::{Flags, Party, Storage};
+use serde_derive::*;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Upgrade a save from a version to the next one, or explain why it can't be done.
+pub type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// Migrations between consecutive versions, the first one upgrades version 1 to version 2.
+/// There must be exactly `SAVE_VERSION - 1` of them.
+pub const MIGRATIONS: &[Migration] = &[v1_item_ids, v2_instance_maps, v3_nested_instances];
+
+/// Upgrade a save to `SAVE_VERSION`.
+pub fn migrate(save: Value) -> Result<Value, SaveError> {
+    migrate_with(save, MIGRATIONS)
+}
+
+/// Upgrade a save to the version following the last of `migrations`.
+fn migrate_with(mut save: Value, migrations: &[Migration]) -> Result<Value, SaveError> {
+    let latest = migrations.len() as u64 + 1;
+    let mut version = save
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(SaveError::MissingVersion)?;
+    if version == 0 || version > latest {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+
+    while version < latest {
+        migrations[version as usize - 1](&mut save).map_err(|reason| SaveError::Migration {
+            from: version as u32,
+            reason,
+        })?;
+        version += 1;
+        save["version"] = Value::from(version);
+    }
+    Ok(save)
+}
+
+/// A save written by version 1, which saved the game in RON.
+/// The party, the storage, the flags and the mobs haven't changed since.
+#[derive(Serialize, Deserialize)]
+struct SaveV1 {
+    version: u32,
+    hero_position: (f32, f32),
+    instance: Option<InstanceV1>,
+    party: Party,
+    storage: Storage,
+    inventory: InventoryV1,
+    flags: Flags,
+    mobs: Vec<SavedMob>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InstanceV1 {
+    spawn: (f32, f32),
+    bounds: BoundsV1,
+    exit: (f32, f32),
+}
+
+#[derive(Serialize, Deserialize)]
+struct BoundsV1 {
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InventoryV1 {
+    capture_items: HashMap<CaptureItemV1, u32>,
+}
+
+#[derive(PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum CaptureItemV1 {
+    Rustball,
+    GreatRustball,
+    UltraRustball,
+}
+
+/// Read a version 1 save into the JSON it would have been, so it can be upgraded.
+pub fn read_v1(text: &str) -> Result<Value, SaveError> {
+    let save = ron::de::from_str::<SaveV1>(text)?;
+    Ok(serde_json::to_value(save)?)
+}
+
+/// Version 2 replaced the fixed capture items by items of the item database.
+fn v1_item_ids(save: &mut Value) -> Result<(), String> {
+    let capture_items = save["inventory"]["capture_items"]
+        .as_object()
+        .ok_or("missing capture items")?;
+
+    let mut items = Map::new();
+    for (item, count) in capture_items {
+        let id = match item.as_str() {
+            "Rustball" => "rustball",
+            "GreatRustball" => "great_rustball",
+            "UltraRustball" => "ultra_rustball",
+            _ => return Err(format!("unknown capture item '{}'", item)),
+        };
+        items.insert(id.to_owned(), count.clone());
+    }
+    save["inventory"] = json!({ "items": items });
+    Ok(())
+}
+
+/// Version 3 moved instances into their own maps. Older instances lived in the overworld
+/// and can't be found anymore, so a hero saved inside one is put back at its exit.
+fn v2_instance_maps(save: &mut Value) -> Result<(), String> {
+    if save["instance"].is_null() {
+        return Ok(());
+    }
+
+    let exit = save["instance"]["exit"].clone();
+    if !exit.is_array() {
+        return Err("missing instance exit".to_owned());
+    }
+    save["hero_position"] = exit;
+    save["instance"] = Value::Null;
+    Ok(())
+}
+
+/// Version 4 allowed entering instances from other instances, the save lists all of them.
+fn v3_nested_instances(save: &mut Value) -> Result<(), String> {
+    let save = save.as_object_mut().ok_or("the save is not an object")?;
+    let instances = match save.remove("instance") {
+        None | Some(Value::Null) => json!([]),
+        Some(instance) => json!([instance]),
+    };
+    save.insert("instances".to_owned(), instances);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::{parse_save, SAVE_VERSION};
+
+    /// A save written by each version of the game, the first one is version 1, in RON.
+    const FIXTURES: &[&str] = &[
+        include_str!("../../tests/fixtures/saves/v1.ron"),
+        include_str!("../../tests/fixtures/saves/v2.json"),
+        include_str!("../../tests/fixtures/saves/v3.json"),
+        include_str!("../../tests/fixtures/saves/v4.json"),
+    ];
+
+    #[test]
+    fn every_version_has_a_migration_and_a_fixture() {
+        assert_eq!(MIGRATIONS.len() + 1, SAVE_VERSION as usize);
+        assert_eq!(FIXTURES.len(), SAVE_VERSION as usize);
+    }
+
+    #[test]
+    fn every_fixture_loads() {
+        for (index, fixture) in FIXTURES.iter().enumerate() {
+            let save = parse_save(fixture)
+                .unwrap_or_else(|error| panic!("fixture v{}: {}", index + 1, error));
+            assert_eq!(save.version, SAVE_VERSION);
+        }
+    }
+
+    #[test]
+    fn fixture_v1_content() {
+        let save = parse_save(FIXTURES[0]).unwrap();
+        assert_eq!(save.hero_position, (-120.5, 64.0));
+        assert!(save.instances.is_empty());
+        assert_eq!(save.party.creatures().len(), 1);
+        assert_eq!(
+            save.party.creatures()[0].nickname.as_ref().unwrap(),
+            "Crabby"
+        );
+        assert!(save.storage.boxes()[0].get(0).is_some());
+        assert!(save.flags.is_set("met_professor"));
+        assert_eq!(save.inventory.count("rustball"), 3);
+        assert_eq!(save.inventory.count("great_rustball"), 1);
+        assert_eq!(save.mobs.len(), 1);
+        assert_eq!(save.mobs[0].map_index, 4);
+    }
+
+    #[test]
+    fn invalid_v1_saves_are_reported() {
+        match parse_save("(version: 1, hero_position: (0.0, 0.0))") {
+            Err(SaveError::LegacyFormat(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fixture_v2_content() {
+        let save = parse_save(FIXTURES[1]).unwrap();
+        assert_eq!(save.inventory.count("potion"), 2);
+        assert_eq!(save.inventory.count("tm_bubble"), 1);
+        assert!(save.flags.is_set("overworld_chest_opened"));
+    }
+
+    #[test]
+    fn fixture_v3_content() {
+        let save = parse_save(FIXTURES[2]).unwrap();
+        assert_eq!(save.instances.len(), 1);
+        let instance = &save.instances[0];
+        assert_eq!(instance.name, "Professor's house");
+        assert_eq!(instance.map, "maps/house.ron");
+        assert_eq!(instance.exit, (100.0, 56.0));
+        assert_eq!(save.hero_position, (0.0, -20.0));
+    }
+
+    #[test]
+    fn fixture_v4_content() {
+        let save = parse_save(FIXTURES[3]).unwrap();
+        let names = save
+            .instances
+            .iter()
+            .map(|instance| instance.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Professor's house", "Cellar"]);
+        assert_eq!(save.instances[1].exit, (60.0, -16.0));
+        assert_eq!(save.hero_position, (12.0, 8.0));
+    }
+
+    #[test]
+    fn v2_instance_is_left_through_its_exit() {
+        let mut save = json!({
+            "hero_position": [1000.0, 1000.0],
+            "instance": {
+                "spawn": [1000.0, 1000.0],
+                "bounds": { "left": 975.0, "right": 1025.0, "bottom": 975.0, "top": 1025.0 },
+                "exit": [100.0, 76.0],
+            },
+        });
+        v2_instance_maps(&mut save).unwrap();
+        assert_eq!(save["hero_position"], json!([100.0, 76.0]));
+        assert!(save["instance"].is_null());
+    }
+
+    #[test]
+    fn migrations_run_in_order() {
+        fn first(save: &mut Value) -> Result<(), String> {
+            save["steps"] = json!(["first"]);
+            Ok(())
+        }
+        fn second(save: &mut Value) -> Result<(), String> {
+            save["steps"].as_array_mut().unwrap().push(json!("second"));
+            Ok(())
+        }
+
+        let save = migrate_with(json!({ "version": 1 }), &[first, second]).unwrap();
+        assert_eq!(save, json!({ "version": 3, "steps": ["first", "second"] }));
+
+        let save = migrate_with(json!({ "version": 2, "steps": [] }), &[first, second]).unwrap();
+        assert_eq!(save, json!({ "version": 3, "steps": ["second"] }));
+    }
+
+    #[test]
+    fn failed_migration_reports_its_version() {
+        fn fail(_: &mut Value) -> Result<(), String> {
+            Err("broken".to_owned())
+        }
+
+        match migrate_with(json!({ "version": 1 }), &[fail]) {
+            Err(SaveError::Migration { from: 1, reason }) => assert_eq!(reason, "broken"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        match migrate(json!({ "version": SAVE_VERSION + 1 })) {
+            Err(SaveError::UnsupportedVersion(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match migrate(json!({ "version": 0 })) {
+            Err(SaveError::UnsupportedVersion(0)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match migrate(json!({ "hero_position": [0.0, 0.0] })) {
+            Err(SaveError::MissingVersion) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
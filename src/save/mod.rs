@@ -0,0 +1,275 @@
+//! Saving the player's progress to disk and restoring it.
+//!
+//! A save holds what the map definition can't tell: where the hero is, what the player owns
+//! and the state of the mobs. The map itself is spawned from its definition when loading,
+//! then the save is applied on top of it.
+//!
+//! Saves are JSON files carrying the version of their format. Older saves are upgraded
+//! step by step by the functions registered in `migrations::MIGRATIONS` before being read.
+//! Version 1 saves were RON files, they are converted to JSON before being upgraded.
+
+mod migrations;
+
+use crate::{
+    components::{Hero, Layer, LayerId, Mob},
+    creatures::Creature,
+    entities,
+    items::Inventory,
+    maps::{InstanceMaps, OverworldChunks},
+    resources::{Flags, Party, Storage},
+    states::{GameState, Instance},
+};
+use amethyst::{
+    core::transform::Transform,
+    ecs::{Entity, Join, World},
+};
+use serde_derive::*;
+use serde_json::Value;
+use std::{collections::HashMap, error::Error, fmt, fs, io, path::Path};
+
+/// Version of the save format. Whenever it changes, increase it, register a migration
+/// from the previous version and add a fixture saved with the new version.
+pub const SAVE_VERSION: u32 = 4;
+/// Where the game is saved, relative to the working directory.
+pub const SAVE_PATH: &str = "saves/save.json";
+/// Where version 1 saved the game, it is continued from there if there is no save at `SAVE_PATH`.
+pub const LEGACY_SAVE_PATH: &str = "saves/save.ron";
+
+/// Everything needed to restore the player's progress.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SaveData {
+    /// Version of the save format, see `SAVE_VERSION`.
+    pub version: u32,
+    pub hero_position: (f32, f32),
+    /// The nested instances the hero is inside, each entered from the previous one.
+    /// Empty in the overworld.
+    pub instances: Vec<Instance>,
+    pub party: Party,
+    pub storage: Storage,
+    pub inventory: Inventory,
+    pub flags: Flags,
+    /// Mobs still on the overworld map, loaded or not, the others have been defeated or captured.
+    /// Instances are spawned again each time the hero enters them, so their mobs are not saved.
+    pub mobs: Vec<SavedMob>,
+}
+
+/// State of a mob, identified by the index of its definition in the map.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedMob {
+    pub map_index: usize,
+    pub position: (f32, f32),
+    pub creature: Creature,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    /// A version 1 save could not be read.
+    LegacyFormat(ron::de::Error),
+    /// The save doesn't say which version of the format it uses.
+    MissingVersion,
+    /// The save was written by an unknown version of the game.
+    UnsupportedVersion(u64),
+    /// A save could not be upgraded from a version to the next one.
+    Migration {
+        from: u32,
+        reason: String,
+    },
+    /// The game can only be saved while walking around, not during a battle or a dialogue.
+    NotSaveable,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{}", error),
+            SaveError::Format(error) => write!(f, "invalid save: {}", error),
+            SaveError::LegacyFormat(error) => write!(f, "invalid version 1 save: {}", error),
+            SaveError::MissingVersion => write!(f, "the save has no version"),
+            SaveError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save version {}", version)
+            }
+            SaveError::Migration { from, reason } => write!(
+                f,
+                "failed to upgrade the save from version {}: {}",
+                from, reason
+            ),
+            SaveError::NotSaveable => write!(f, "the game can't be saved right now"),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(error: serde_json::Error) -> Self {
+        SaveError::Format(error)
+    }
+}
+
+impl From<ron::de::Error> for SaveError {
+    fn from(error: ron::de::Error) -> Self {
+        SaveError::LegacyFormat(error)
+    }
+}
+
+impl SaveData {
+    /// Gather the player's progress from the world.
+    pub fn capture(world: &World) -> Result<Self, SaveError> {
+        let instances = match &*world.read_resource::<GameState>() {
+            GameState::Overworld(_) => Vec::new(),
+            GameState::Instance(instances) => instances.clone(),
+            GameState::Battle(_) | GameState::Dialogue(_) => return Err(SaveError::NotSaveable),
+        };
+
+        let heroes = world.read_storage::<Hero>();
+        let mobs = world.read_storage::<Mob>();
+        let layers = world.read_storage::<Layer>();
+        let transforms = world.read_storage::<Transform>();
+        let hero_position = (&heroes, &transforms)
+            .join()
+            .next()
+            .map(|(_, transform)| position(transform))
+            .ok_or(SaveError::NotSaveable)?;
+        let mut mobs = (&mobs, &transforms, &layers)
+            .join()
+            .filter(|(_, _, layer)| layer.id == LayerId::OVERWORLD)
+            .map(|(mob, transform, _)| SavedMob {
+                map_index: mob.map_index,
+                position: position(transform),
+                creature: mob.creature.clone(),
+            })
+            .collect::<Vec<_>>();
+        // Mobs of the chunks which are not loaded.
+        mobs.extend(
+            world
+                .read_resource::<OverworldChunks>()
+                .unloaded_mobs()
+                .map(|(map_index, record)| SavedMob {
+                    map_index,
+                    position: record.position,
+                    creature: record.creature.clone(),
+                }),
+        );
+        mobs.sort_by_key(|mob| mob.map_index);
+
+        Ok(SaveData {
+            version: SAVE_VERSION,
+            hero_position,
+            instances,
+            party: world.read_resource::<Party>().clone(),
+            storage: world.read_resource::<Storage>().clone(),
+            inventory: world.read_resource::<Inventory>().clone(),
+            flags: world.read_resource::<Flags>().clone(),
+            mobs,
+        })
+    }
+
+    /// Apply the save to a freshly split overworld, before any of its chunks is loaded:
+    /// add the player's resources, update or remove the mobs
+    /// and put the hero and the camera back where they were.
+    /// The `GameState` is set so the overworld pushes the saved instances if needed.
+    /// If the map of a saved instance doesn't exist anymore, the hero is put at its exit.
+    pub fn restore(&self, world: &mut World, hero: Entity, camera: Entity) {
+        world.add_resource(self.party.clone());
+        world.add_resource(self.storage.clone());
+        world.add_resource(self.inventory.clone());
+        world.add_resource(self.flags.clone());
+
+        let saved = self
+            .mobs
+            .iter()
+            .map(|mob| (mob.map_index, mob))
+            .collect::<HashMap<_, _>>();
+        world
+            .write_resource::<OverworldChunks>()
+            .retain_mobs(|map_index, record| match saved.get(&map_index) {
+                Some(saved) => {
+                    record.creature = saved.creature.clone();
+                    record.position = saved.position;
+                    true
+                }
+                None => false,
+            });
+
+        let missing = {
+            let maps = world.read_resource::<InstanceMaps>();
+            self.instances
+                .iter()
+                .position(|instance| !maps.contains(&instance.map))
+        };
+        // The instance states place the hero at the spawn of the last instance.
+        let state = match missing {
+            Some(index) => GameState::inside(&self.instances[..index], self.instances[index].exit),
+            None => GameState::inside(&self.instances, self.hero_position),
+        };
+        *world.write_resource::<GameState>() = match state {
+            GameState::Overworld((x, y)) => {
+                let mut transforms = world.write_storage::<Transform>();
+                let mut hero_transform = Transform::default();
+                hero_transform.set_xyz(x, y, 0.0);
+                let mut camera_transform = Transform::default();
+                camera_transform.set_xyz(x, y, entities::CAM_Z_POS);
+                transforms.insert(hero, hero_transform).unwrap();
+                transforms.insert(camera, camera_transform).unwrap();
+                GameState::Overworld((x, y))
+            }
+            state => state,
+        };
+    }
+}
+
+/// Save the game to `SAVE_PATH`.
+pub fn save_game(world: &World) -> Result<(), SaveError> {
+    let save = SaveData::capture(world)?;
+    let path = Path::new(SAVE_PATH);
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&save)?)?;
+    Ok(())
+}
+
+/// Load the saved game, upgrading it if it is an older version.
+pub fn load_game() -> Result<SaveData, SaveError> {
+    let path = saved_game_path().unwrap_or(SAVE_PATH);
+    parse_save(&fs::read_to_string(path)?)
+}
+
+/// Read a save of any supported version.
+fn parse_save(text: &str) -> Result<SaveData, SaveError> {
+    // JSON saves are objects, version 1 saves are RON structs.
+    let save = if text.trim_start().starts_with('{') {
+        serde_json::from_str::<Value>(text)?
+    } else {
+        migrations::read_v1(text)?
+    };
+    let save = migrations::migrate(save)?;
+    Ok(serde_json::from_value(save)?)
+}
+
+/// The file the game is continued from: `SAVE_PATH`, or `LEGACY_SAVE_PATH` if the game
+/// hasn't been saved since version 1. None if there is no saved game.
+pub fn saved_game_path() -> Option<&'static str> {
+    [SAVE_PATH, LEGACY_SAVE_PATH]
+        .iter()
+        .cloned()
+        .find(|path| Path::new(path).is_file())
+}
+
+/// Is there a saved game to continue.
+pub fn save_exists() -> bool {
+    saved_game_path().is_some()
+}
+
+fn position(transform: &Transform) -> (f32, f32) {
+    let translation = transform.translation();
+    (translation.x, translation.y)
+}
@@ -0,0 +1,568 @@
+use super::GameState;
+use crate::{
+    assets,
+    battle::{Battle, BattleAction, BattleEvent, BattleOutcome, Side},
+    components::{LayerId, Mob, NpcBattle},
+    creatures::SpeciesDatabase,
+    entities,
+    game_data::{RustymonGameData, RustymonTrans},
+    items::{Inventory, ItemDatabase, Pocket, UseContext},
+    resources::{ActiveLayers, Flags, Party, Storage},
+    rng::Rng,
+    transition::{ScreenTransition, TransitionEffect},
+};
+use amethyst::{
+    core::{timing::Time, transform::Transform},
+    ecs::{Entity, World},
+    input,
+    renderer::SpriteRender,
+    winit::{Event, VirtualKeyCode},
+    State, StateData, StateEvent, Trans,
+};
+use log::{error, info};
+use std::{collections::VecDeque, f32::consts::PI};
+
+/// Where the battle scene is drawn, far away from the overworld and instances.
+const BATTLE_ORIGIN: (f32, f32) = (20000.0, 20000.0);
+/// Offset of the creatures' sprites from the battle origin, the wild one is mirrored.
+const CREATURE_OFFSET: (f32, f32) = (80.0, 40.0);
+/// How long each battle event is shown before the next one.
+const EVENT_SECONDS: f32 = 0.6;
+/// How long the capture item shakes for each successful capture check.
+const SHAKE_SECONDS: f32 = 1.0;
+/// Maximum rotation of the wild creature's sprite while shaking, in radians.
+const SHAKE_ANGLE: f32 = 0.3;
+/// Scale of the wild creature's sprite while it is inside a capture item.
+const CAPTURED_SCALE: f32 = 0.5;
+
+/// Keys choosing a move, a creature of the party or an item depending on the menu.
+const CHOICE_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+/// What the number keys choose.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Menu {
+    Moves,
+    Party,
+    Items,
+    /// The creature of the party to use an item on, by id.
+    ItemTarget(String),
+}
+
+/// State active during a fight against a mob's creature, or the creature of an NPC.
+/// Moves are chosen with the number keys, S opens the party to switch creatures,
+/// I opens the items and R runs away. NPCs' creatures can't be captured nor ran away from.
+pub struct BattleState {
+    /// The mob, or the `NpcBattle`, owning the opponent creature.
+    mob: Entity,
+    camera: Entity,
+    battle: Option<Battle>,
+    menu: Menu,
+    /// Events waiting to be shown, the first one is being shown.
+    pending: VecDeque<BattleEvent>,
+    /// Time the first pending event has been shown for.
+    event_elapsed: f32,
+    /// Sprite of the player's creature, replaced when switching.
+    player_sprite: Option<Entity>,
+    /// Sprite of the wild creature, animated when capturing.
+    wild_sprite: Option<Entity>,
+    /// Is the opponent the creature of an NPC.
+    npc_battle: bool,
+    /// The game state set once the battle is over, after fading out.
+    resume: GameState,
+    sprites: Vec<Entity>,
+}
+
+impl BattleState {
+    pub fn new(mob: Entity, camera: Entity, resume: GameState) -> Self {
+        BattleState {
+            mob,
+            camera,
+            battle: None,
+            menu: Menu::Moves,
+            pending: VecDeque::new(),
+            event_elapsed: 0.0,
+            player_sprite: None,
+            wild_sprite: None,
+            npc_battle: false,
+            resume,
+            sprites: Vec::new(),
+        }
+    }
+
+    /// Print the creatures' health and the available choices.
+    fn print_status(&self, world: &World) {
+        let database = world.read_resource::<SpeciesDatabase>();
+        let battle = self.battle.as_ref().unwrap();
+        for &side in &[Side::Wild, Side::Player] {
+            let creature = battle.active(side);
+            info!(
+                "{} Lv.{}: {}/{} HP",
+                creature.name(&database),
+                creature.level,
+                creature.hp,
+                creature.stats(&database).hp
+            );
+        }
+
+        if battle.switch_required() {
+            print_party(battle, &database);
+            return;
+        }
+        match &self.menu {
+            Menu::Moves => {
+                for (index, learned) in battle.active(Side::Player).moves.iter().enumerate() {
+                    info!(
+                        "  [{}] {} ({} PP)",
+                        index + 1,
+                        database.moves[&learned.move_id].name,
+                        learned.pp
+                    );
+                }
+                info!("  [S] Switch");
+                info!("  [I] Items");
+                info!("  [R] Run");
+            }
+            Menu::Party => {
+                print_party(battle, &database);
+                info!("  [S] Back");
+            }
+            Menu::Items => {
+                let items = world.read_resource::<ItemDatabase>();
+                for (index, (item, count)) in usable_items(world).iter().enumerate() {
+                    info!("  [{}] {} (x{})", index + 1, items.items[item].name, count);
+                }
+                info!("  [I] Back");
+            }
+            Menu::ItemTarget(item) => {
+                info!(
+                    "Use {} on:",
+                    world.read_resource::<ItemDatabase>().items[item].name
+                );
+                print_party(battle, &database);
+                info!("  [I] Back");
+            }
+        }
+    }
+
+    /// Convert a key press into an action, or open and close the menus.
+    fn read_action(&mut self, event: &Event, world: &World) -> Option<BattleAction> {
+        let switch_required = self.battle.as_ref().unwrap().switch_required();
+        let choice = CHOICE_KEYS
+            .iter()
+            .position(|&key| input::is_key_down(event, key));
+
+        if let Some(index) = choice {
+            return match &self.menu {
+                _ if switch_required => Some(BattleAction::Switch(index)),
+                Menu::Moves => Some(BattleAction::Move(index)),
+                Menu::Party => Some(BattleAction::Switch(index)),
+                Menu::Items => {
+                    let (item, _) = usable_items(world).into_iter().nth(index)?;
+                    let captures = world.read_resource::<ItemDatabase>().items[&item]
+                        .capture_bonus()
+                        .is_some();
+                    if captures && self.npc_battle {
+                        info!("You can't capture someone else's creature!");
+                        None
+                    } else if captures {
+                        Some(BattleAction::UseItem { item, target: 0 })
+                    } else {
+                        self.menu = Menu::ItemTarget(item);
+                        self.print_status(world);
+                        None
+                    }
+                }
+                Menu::ItemTarget(item) => Some(BattleAction::UseItem {
+                    item: item.clone(),
+                    target: index,
+                }),
+            };
+        }
+        if switch_required {
+            return None;
+        }
+
+        let menu = if input::is_key_down(event, VirtualKeyCode::S) {
+            Menu::Party
+        } else if input::is_key_down(event, VirtualKeyCode::I) {
+            Menu::Items
+        } else if input::is_key_down(event, VirtualKeyCode::R) && self.menu == Menu::Moves {
+            if self.npc_battle {
+                info!("There's no running from this fight!");
+                return None;
+            }
+            return Some(BattleAction::Flee);
+        } else {
+            return None;
+        };
+        self.menu = match (&self.menu, menu) {
+            (Menu::ItemTarget(_), Menu::Items) => Menu::Items,
+            (current, menu) if *current == menu => Menu::Moves,
+            (_, menu) => menu,
+        };
+        self.print_status(world);
+        None
+    }
+
+    /// Queue events to be shown one after the other.
+    fn queue_events(&mut self, events: Vec<BattleEvent>, world: &mut World) {
+        let idle = self.pending.is_empty();
+        self.pending.extend(events);
+        if idle {
+            self.event_elapsed = 0.0;
+            self.show_event(world);
+        }
+    }
+
+    /// Show the pending events, animating the capture item's shakes.
+    /// Returns true while events are still being shown.
+    fn update_events(&mut self, world: &mut World) -> bool {
+        let duration = match self.pending.front() {
+            Some(BattleEvent::Shook) => SHAKE_SECONDS,
+            Some(_) => EVENT_SECONDS,
+            None => return false,
+        };
+        self.event_elapsed += world.read_resource::<Time>().delta_seconds();
+
+        if let Some(BattleEvent::Shook) = self.pending.front() {
+            let progress = (self.event_elapsed / duration).min(1.0);
+            let angle = (progress * 2.0 * PI).sin() * SHAKE_ANGLE;
+            self.set_wild_pose(CAPTURED_SCALE, angle, world);
+        }
+
+        if self.event_elapsed >= duration {
+            self.pending.pop_front();
+            self.event_elapsed = 0.0;
+            if !self.pending.is_empty() {
+                self.show_event(world);
+            } else if self.battle.as_ref().unwrap().outcome().is_none() {
+                self.print_status(world);
+            }
+        }
+        true
+    }
+
+    /// Print the first pending event and update the sprites it affects.
+    fn show_event(&mut self, world: &mut World) {
+        let event = match self.pending.front() {
+            Some(event) => event.clone(),
+            None => return,
+        };
+        let line = {
+            let database = world.read_resource::<SpeciesDatabase>();
+            let items = world.read_resource::<ItemDatabase>();
+            event.describe(self.battle.as_ref().unwrap(), &database, &items)
+        };
+        info!("{}", line);
+
+        match event {
+            BattleEvent::SentOut { side, creature } => {
+                let species = self
+                    .battle
+                    .as_ref()
+                    .unwrap()
+                    .creature(side, creature)
+                    .species
+                    .clone();
+                let (previous, offset) = match side {
+                    Side::Player => (
+                        self.player_sprite.take(),
+                        (-CREATURE_OFFSET.0, -CREATURE_OFFSET.1),
+                    ),
+                    Side::Wild => (self.wild_sprite.take(), CREATURE_OFFSET),
+                };
+                if let Some(sprite) = previous {
+                    world.delete_entity(sprite).unwrap();
+                }
+                let sprite = Some(self.build_sprite(&species, offset, world));
+                match side {
+                    Side::Player => self.player_sprite = sprite,
+                    Side::Wild => self.wild_sprite = sprite,
+                }
+            }
+            BattleEvent::CaptureItemThrown { .. } => self.set_wild_pose(CAPTURED_SCALE, 0.0, world),
+            BattleEvent::BrokeFree => self.set_wild_pose(1.0, 0.0, world),
+            _ => (),
+        }
+    }
+
+    /// Change the scale and rotation of the wild creature's sprite.
+    fn set_wild_pose(&self, scale: f32, angle: f32, world: &mut World) {
+        let sprite = match self.wild_sprite {
+            Some(sprite) => sprite,
+            None => return,
+        };
+        if let Some(transform) = world.write_storage::<Transform>().get_mut(sprite) {
+            transform.set_scale(scale, scale, 1.0);
+            transform.set_rotation_euler(0.0, 0.0, angle);
+        }
+    }
+
+    /// Build the sprite of a creature at an offset from the battle origin.
+    fn build_sprite(&mut self, species: &str, offset: (f32, f32), world: &mut World) -> Entity {
+        let sprite = world.read_resource::<SpeciesDatabase>().species[species]
+            .sprite
+            .clone();
+        let sprite_sheet = assets::load_sprite_sheet_definition(&sprite.sheet, world);
+        let entity = entities::build_battle_sprite(
+            BATTLE_ORIGIN.0 + offset.0,
+            BATTLE_ORIGIN.1 + offset.1,
+            SpriteRender {
+                sprite_sheet,
+                sprite_number: sprite.index,
+            },
+            world,
+        );
+        self.sprites.push(entity);
+        entity
+    }
+
+    /// Write the battle's results back to the party and the opponent.
+    fn finish(&mut self, outcome: BattleOutcome, world: &mut World) {
+        let battle = self.battle.take().unwrap();
+        let captured = battle.captured().cloned();
+
+        // Keep the damage taken by the party, heal it if it was defeated.
+        let mut creatures = battle.into_party();
+        if outcome == BattleOutcome::Lost {
+            let database = world.read_resource::<SpeciesDatabase>();
+            for creature in &mut creatures {
+                creature.heal(&database);
+            }
+        }
+        // The battle was started with the creatures of the party, in the same order.
+        let mut party = world.write_resource::<Party>();
+        for (creature, fought) in party.creatures_mut().iter_mut().zip(creatures) {
+            *creature = fought;
+        }
+
+        if let Some(creature) = captured {
+            let name = creature
+                .name(&world.read_resource::<SpeciesDatabase>())
+                .to_owned();
+            match party.add(creature) {
+                Ok(()) => info!("{} joined the party.", name),
+                Err(creature) => {
+                    let mut storage = world.write_resource::<Storage>();
+                    match storage.add(creature) {
+                        Ok(location) => info!(
+                            "{} was sent to {}.",
+                            name,
+                            storage.boxes()[location.storage_box].name
+                        ),
+                        Err(_) => info!("There is no room left, {} was released.", name),
+                    }
+                }
+            }
+        }
+        drop(party);
+
+        // The creature of an NPC only exists for the battle.
+        if self.npc_battle {
+            let won_flag = world
+                .read_storage::<NpcBattle>()
+                .get(self.mob)
+                .and_then(|battle| battle.won_flag.clone());
+            if let (BattleOutcome::Won, Some(flag)) = (outcome, won_flag) {
+                world.write_resource::<Flags>().set(flag);
+            }
+            world.delete_entity(self.mob).unwrap();
+            return;
+        }
+
+        // A defeated or captured mob disappears, otherwise it goes back to its spawn.
+        if outcome == BattleOutcome::Won || outcome == BattleOutcome::Captured {
+            world.delete_entity(self.mob).unwrap();
+        } else {
+            let database = world.read_resource::<SpeciesDatabase>();
+            let mut mobs = world.write_storage::<Mob>();
+            let mob = mobs.get_mut(self.mob).unwrap();
+            mob.target = None;
+            mob.resetting = false;
+            mob.creature.heal(&database);
+
+            let mut transform = Transform::default();
+            transform.set_xyz(mob.spawn.0, mob.spawn.1, 0.0);
+            world
+                .write_storage::<Transform>()
+                .insert(self.mob, transform)
+                .unwrap();
+        }
+    }
+}
+
+/// The items of the inventory which can be used in battle, sorted by pocket.
+fn usable_items(world: &World) -> Vec<(String, u32)> {
+    let inventory = world.read_resource::<Inventory>();
+    let items = world.read_resource::<ItemDatabase>();
+    Pocket::ALL
+        .iter()
+        .flat_map(|&pocket| inventory.pocket(pocket, &items))
+        .filter(|(item, _)| items.items[*item].usable_in(UseContext::Battle))
+        .map(|(item, count)| (item.to_owned(), count))
+        .collect()
+}
+
+fn print_party(battle: &Battle, database: &SpeciesDatabase) {
+    for (index, creature) in battle.creatures(Side::Player).iter().enumerate() {
+        info!(
+            "  [{}] {} Lv.{} ({} HP)",
+            index + 1,
+            creature.name(database),
+            creature.level,
+            creature.hp
+        );
+    }
+}
+
+impl<'a, 'b> State<RustymonGameData<'a, 'b>, StateEvent> for BattleState {
+    fn on_start(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
+        let world = data.world;
+
+        // The battle has its own scene, hide the one it started from.
+        world
+            .write_resource::<ActiveLayers>()
+            .set(&[LayerId::BATTLE]);
+
+        let party = world.read_resource::<Party>().creatures().to_vec();
+        let npc_battle = world
+            .read_storage::<NpcBattle>()
+            .get(self.mob)
+            .map(|battle| battle.creature.clone());
+        self.npc_battle = npc_battle.is_some();
+        let wild = npc_battle.unwrap_or_else(|| {
+            world
+                .read_storage::<Mob>()
+                .get(self.mob)
+                .unwrap()
+                .creature
+                .clone()
+        });
+        let seed = world.write_resource::<Rng>().next_u64();
+        match Battle::new(party, vec![wild], seed) {
+            Ok(battle) => self.battle = Some(battle),
+            Err(error) => {
+                error!("{}", error);
+                // Nothing is fought: the NPC's creature is dropped and the mob walks back to
+                // its spawn instead of starting the battle again.
+                if self.npc_battle {
+                    world.delete_entity(self.mob).unwrap();
+                } else if let Some(mob) = world.write_storage::<Mob>().get_mut(self.mob) {
+                    mob.target = None;
+                    mob.resetting = true;
+                }
+                world
+                    .write_resource::<ScreenTransition>()
+                    .start(TransitionEffect::Fade, self.resume.clone());
+                return;
+            }
+        }
+
+        // Point the camera on the battle scene
+        {
+            let mut transforms = world.write_storage::<Transform>();
+            let mut transform = Transform::default();
+            transform.set_xyz(BATTLE_ORIGIN.0, BATTLE_ORIGIN.1, entities::CAM_Z_POS);
+            transforms.insert(self.camera, transform).unwrap();
+        }
+
+        let events = self.battle.as_ref().unwrap().log().to_vec();
+        self.queue_events(events, world);
+    }
+
+    fn on_stop(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
+        for entity in self.sprites.drain(..) {
+            data.world.delete_entity(entity).unwrap();
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<RustymonGameData<'a, 'b>>,
+        event: StateEvent,
+    ) -> RustymonTrans<'a, 'b> {
+        let event = match &event {
+            StateEvent::Window(event) => event,
+            _ => return Trans::None,
+        };
+        if input::is_close_requested(event) {
+            return Trans::Quit;
+        }
+
+        // Wait for the screen to be uncovered and for the previous turn to be shown
+        // before choosing the next action.
+        let over = self
+            .battle
+            .as_ref()
+            .map_or(true, |battle| battle.outcome().is_some());
+        if data.world.read_resource::<ScreenTransition>().is_running()
+            || !self.pending.is_empty()
+            || over
+        {
+            return Trans::None;
+        }
+
+        if let Some(action) = self.read_action(event, data.world) {
+            let used_item = match &action {
+                BattleAction::UseItem { item, .. } => Some(item.clone()),
+                _ => None,
+            };
+            let result = {
+                let database = data.world.read_resource::<SpeciesDatabase>();
+                let items = data.world.read_resource::<ItemDatabase>();
+                self.battle
+                    .as_mut()
+                    .unwrap()
+                    .play_turn(action, &database, &items)
+            };
+            match result {
+                Ok(events) => {
+                    if let Some(item) = used_item {
+                        let consumed =
+                            data.world.read_resource::<ItemDatabase>().items[&item].is_consumed();
+                        if consumed {
+                            data.world.write_resource::<Inventory>().take(&item);
+                        }
+                    }
+                    self.menu = Menu::Moves;
+                    self.queue_events(events, data.world);
+                }
+                Err(error) => {
+                    info!("{}", error);
+                    self.print_status(data.world);
+                }
+            }
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, data: StateData<RustymonGameData<'a, 'b>>) -> RustymonTrans<'a, 'b> {
+        data.data.update(&data.world);
+        if self.update_events(data.world) {
+            return Trans::None;
+        }
+
+        if let Some(outcome) = self.battle.as_ref().and_then(Battle::outcome) {
+            self.finish(outcome, data.world);
+            data.world
+                .write_resource::<ScreenTransition>()
+                .start(TransitionEffect::Fade, self.resume.clone());
+        }
+
+        // The battle is left once the screen is covered.
+        match *data.world.read_resource::<GameState>() {
+            GameState::Battle(_) => Trans::None,
+            _ => Trans::Pop,
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use super::{BattleState, GameState};
+use crate::{
+    components::{Interactable, Interaction, NpcBattle},
+    creatures::{Creature, SpeciesDatabase},
+    dialogue::{Dialogue, DialogueAction, DialogueDatabase, DialogueNext},
+    entities,
+    game_data::{RustymonGameData, RustymonTrans},
+    items::{Inventory, ItemDatabase},
+    resources::{Flags, Party},
+    rng::Rng,
+    transition::{ScreenTransition, TransitionEffect},
+};
+use amethyst::{
+    ecs::{Entity, World},
+    input,
+    winit::VirtualKeyCode,
+    State, StateData, StateEvent, Trans,
+};
+use log::{error, info};
+
+/// Keys choosing an answer.
+const CHOICE_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+/// Nodes gone through at most before waiting for the player. Loops of nodes without text
+/// are rejected when validating the dialogues, this stops the ones depending on flags.
+const MAX_STEPS: usize = 100;
+
+/// What the conversation waits for.
+enum Prompt {
+    /// The player reading the text, then go to the node or end the conversation.
+    Continue(Option<String>),
+    /// The player picking an answer, each leading to a node or ending the conversation.
+    Choose(Vec<Option<String>>),
+    Done,
+}
+
+/// State active while talking with an NPC, reading a sign or opening a chest, pushed on top
+/// of the overworld or an instance. The simulation is paused during the conversation.
+/// Space goes on and the number keys pick an answer. A battle started by the dialogue is
+/// fought once the conversation ends.
+pub struct DialogueState {
+    /// The entity interacted with.
+    npc: Entity,
+    camera: Entity,
+    /// The game state set once the conversation, or the battle following it, is over.
+    resume: GameState,
+    /// Who is talking, signs and chests have no name.
+    name: Option<String>,
+    dialogue: Option<Dialogue>,
+    prompt: Prompt,
+    battle: Option<NpcBattle>,
+}
+
+impl DialogueState {
+    pub fn new(npc: Entity, camera: Entity, resume: GameState) -> Self {
+        DialogueState {
+            npc,
+            camera,
+            resume,
+            name: None,
+            dialogue: None,
+            prompt: Prompt::Done,
+            battle: None,
+        }
+    }
+
+    /// Go through nodes until the player has to do something or the conversation ends.
+    fn enter(&mut self, node: Option<String>, world: &mut World) {
+        let mut next = node;
+        let mut steps = 0;
+        while let Some(id) = next {
+            steps += 1;
+            if steps > MAX_STEPS {
+                error!(
+                    "dialogue stopped after {} nodes without an answer",
+                    MAX_STEPS
+                );
+                break;
+            }
+            let node = self.dialogue.as_ref().unwrap().nodes[&id].clone();
+            self.run_actions(&node.actions, world);
+            match (&self.name, &node.text) {
+                (Some(name), Some(text)) => info!("{}: {}", name, text),
+                (None, Some(text)) => info!("{}", text),
+                _ => (),
+            }
+
+            next = match node.next {
+                DialogueNext::End if node.text.is_none() => None,
+                DialogueNext::End => {
+                    self.prompt = Prompt::Continue(None);
+                    return;
+                }
+                DialogueNext::Goto(next) if node.text.is_none() => Some(next),
+                DialogueNext::Goto(next) => {
+                    self.prompt = Prompt::Continue(Some(next));
+                    return;
+                }
+                DialogueNext::Choices(choices) => {
+                    let flags = world.read_resource::<Flags>();
+                    let inventory = world.read_resource::<Inventory>();
+                    let choices = choices
+                        .into_iter()
+                        .filter(|choice| {
+                            choice
+                                .condition
+                                .as_ref()
+                                .map_or(true, |c| c.is_met(&flags, &inventory))
+                        })
+                        .take(CHOICE_KEYS.len())
+                        .collect::<Vec<_>>();
+                    if choices.is_empty() {
+                        None
+                    } else {
+                        for (index, choice) in choices.iter().enumerate() {
+                            info!("  [{}] {}", index + 1, choice.text);
+                        }
+                        self.prompt = Prompt::Choose(choices.into_iter().map(|c| c.node).collect());
+                        return;
+                    }
+                }
+                DialogueNext::Branch {
+                    branches,
+                    otherwise,
+                } => {
+                    let flags = world.read_resource::<Flags>();
+                    let inventory = world.read_resource::<Inventory>();
+                    branches
+                        .into_iter()
+                        .find(|(condition, _)| condition.is_met(&flags, &inventory))
+                        .map(|(_, node)| node)
+                        .or(otherwise)
+                }
+            };
+        }
+        self.prompt = Prompt::Done;
+    }
+
+    fn run_actions(&mut self, actions: &[DialogueAction], world: &mut World) {
+        for action in actions {
+            match action {
+                DialogueAction::SetFlag(flag) => world.write_resource::<Flags>().set(flag.as_str()),
+                DialogueAction::UnsetFlag(flag) => world.write_resource::<Flags>().unset(flag),
+                DialogueAction::GiveItem { item, count } => {
+                    world
+                        .write_resource::<Inventory>()
+                        .add(item.as_str(), *count);
+                    let items = world.read_resource::<ItemDatabase>();
+                    info!("Received {} x{}.", items.items[item].name, count);
+                }
+                DialogueAction::TakeItem { item, count } => {
+                    let mut inventory = world.write_resource::<Inventory>();
+                    for _ in 0..*count {
+                        inventory.take(item);
+                    }
+                }
+                DialogueAction::HealParty => {
+                    let database = world.read_resource::<SpeciesDatabase>();
+                    for creature in world.write_resource::<Party>().creatures_mut() {
+                        creature.heal(&database);
+                    }
+                    info!("Your party is fully healed.");
+                }
+                DialogueAction::StartBattle {
+                    species,
+                    level,
+                    won_flag,
+                } => {
+                    let creature = Creature::new(
+                        species,
+                        *level,
+                        &world.read_resource::<SpeciesDatabase>(),
+                        &mut world.write_resource::<Rng>(),
+                    );
+                    self.battle = Some(NpcBattle {
+                        creature,
+                        won_flag: won_flag.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl<'a, 'b> State<RustymonGameData<'a, 'b>, StateEvent> for DialogueState {
+    fn on_start(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
+        let world = data.world;
+        let dialogue = {
+            let interactables = world.read_storage::<Interactable>();
+            match &interactables.get(self.npc).unwrap().interaction {
+                Interaction::Talk { name, dialogue, .. } => {
+                    self.name = Some(name.clone());
+                    // Dialogues have been validated by the loading state.
+                    world
+                        .read_resource::<DialogueDatabase>()
+                        .dialogue(dialogue)
+                        .cloned()
+                        .unwrap()
+                }
+                Interaction::PickUp { flag, .. } if world.read_resource::<Flags>().is_set(flag) => {
+                    Dialogue::single(Some("There is nothing left.".to_owned()), Vec::new())
+                }
+                Interaction::PickUp { items, flag } => {
+                    let mut actions = items
+                        .iter()
+                        .map(|(item, count)| DialogueAction::GiveItem {
+                            item: item.clone(),
+                            count: *count,
+                        })
+                        .collect::<Vec<_>>();
+                    actions.push(DialogueAction::SetFlag(flag.clone()));
+                    Dialogue::single(None, actions)
+                }
+                Interaction::ReadSign(text) => Dialogue::single(Some(text.clone()), Vec::new()),
+                _ => unreachable!("dialogues are only started by talking, picking up or reading"),
+            }
+        };
+        let start = dialogue.start.clone();
+        self.dialogue = Some(dialogue);
+        self.enter(Some(start), world);
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<RustymonGameData<'a, 'b>>,
+        event: StateEvent,
+    ) -> RustymonTrans<'a, 'b> {
+        let event = match &event {
+            StateEvent::Window(event) => event,
+            _ => return Trans::None,
+        };
+        if input::is_close_requested(event) {
+            return Trans::Quit;
+        }
+
+        let next = match &self.prompt {
+            Prompt::Continue(next)
+                if input::is_key_down(event, VirtualKeyCode::Space)
+                    || input::is_key_down(event, VirtualKeyCode::Return) =>
+            {
+                next.clone()
+            }
+            Prompt::Choose(nodes) => {
+                match CHOICE_KEYS
+                    .iter()
+                    .position(|key| input::is_key_down(event, *key))
+                    .and_then(|index| nodes.get(index))
+                {
+                    Some(node) => node.clone(),
+                    None => return Trans::None,
+                }
+            }
+            _ => return Trans::None,
+        };
+        self.enter(next, data.world);
+        Trans::None
+    }
+
+    fn update(&mut self, data: StateData<RustymonGameData<'a, 'b>>) -> RustymonTrans<'a, 'b> {
+        data.data.update_frame(&data.world);
+        match self.prompt {
+            Prompt::Done => (),
+            _ => return Trans::None,
+        }
+
+        if let Some(battle) = self.battle.take() {
+            let opponent = entities::build_npc_battle(battle, data.world);
+            data.world
+                .write_resource::<ScreenTransition>()
+                .start(TransitionEffect::BattleSwirl, GameState::Battle(opponent));
+        }
+
+        // A battle replaces the dialogue once the screen is covered.
+        let state = data.world.read_resource::<GameState>().clone();
+        match state {
+            GameState::Battle(opponent) => Trans::Switch(Box::new(BattleState::new(
+                opponent,
+                self.camera,
+                self.resume.clone(),
+            ))),
+            _ if data.world.read_resource::<ScreenTransition>().is_running() => Trans::None,
+            _ => {
+                *data.world.write_resource::<GameState>() = self.resume.clone();
+                Trans::Pop
+            }
+        }
+    }
+}
@@ -1,89 +1,224 @@
-use super::GameState;
+use super::{
+    overworld, BattleState, DialogueState, GameState, InventoryState, LayerTransition, PartyState,
+};
 use amethyst::winit::VirtualKeyCode;
 use amethyst::{
-    core::transform::Transform, ecs::Entity, input, GameData, SimpleState, SimpleTrans, StateData,
-    StateEvent, Trans,
+    core::transform::Transform, ecs::prelude::*, input, State, StateData, StateEvent, Trans,
 };
 
-use crate::{entities, resources::WorldBounds};
+use crate::{
+    components::{Layer, LayerId},
+    entities,
+    game_data::{RustymonGameData, RustymonTrans},
+    maps::{self, InstanceMaps, MapDefinition},
+    resources::ActiveLayers,
+    transition::{ScreenTransition, TransitionEffect},
+};
+use serde_derive::*;
 
 /// Instance data.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Instance {
-    /// The position the hero will spawn when entering.
+    /// Name of the instance, shown when entering it.
+    pub name: String,
+    /// Path of the instance's map, relative to the asset directory.
+    pub map: String,
+    /// The position the hero will spawn when entering, in the instance's map.
     pub spawn: (f32, f32),
-    /// The boundaries of the instance.
-    pub bounds: WorldBounds,
-    /// The position in the overworld the hero will end up when exiting the instance.
+    /// The position in the previous layer (the overworld or the instance the hero came from)
+    /// the hero will end up when exiting the instance.
     pub exit: (f32, f32),
+    /// The effect played when entering and leaving the instance, set by the portal used.
+    #[serde(default)]
+    pub transition: TransitionEffect,
 }
 
 /// State active when inside an instance (building/dungeon).
+/// The entities of the instance's map are spawned when entering and deleted when leaving.
+/// Entering another instance from this one pushes a new instance state on top of it.
 pub struct InstanceState {
-    instance: Instance,
+    /// The instances leading to this one, this one being the last.
+    instances: Vec<Instance>,
     hero: Entity,
     camera: Entity,
+    /// Entities spawned from the instance's map.
+    entities: Vec<Entity>,
 }
 
 impl InstanceState {
-    /// Build a new instance state from an `Instance`.
-    pub fn new(instance: Instance, hero: Entity, camera: Entity) -> Self {
+    /// Build a new instance state for the last of nested instances.
+    pub fn new(instances: Vec<Instance>, hero: Entity, camera: Entity) -> Self {
         InstanceState {
-            instance,
+            instances,
             hero,
             camera,
+            entities: Vec::new(),
         }
     }
 
-    fn build_transform_from_spawn(&self) -> Transform {
-        let mut t = Transform::default();
-        t.set_xyz(self.instance.spawn.0, self.instance.spawn.1, 0.0);
-        t
+    fn instance(&self) -> &Instance {
+        self.instances.last().unwrap()
+    }
+
+    /// The map of the instance, loaded and validated by the loading state.
+    fn map(&self, world: &World) -> MapDefinition {
+        world
+            .read_resource::<InstanceMaps>()
+            .get(&self.instance().map)
+            .cloned()
+            .unwrap()
+    }
+
+    /// The state to come back to after a battle, a dialogue or a menu.
+    fn resume_state(&self, world: &World) -> GameState {
+        let transforms = world.read_storage::<Transform>();
+        let translation = transforms.get(self.hero).unwrap().translation();
+        GameState::inside(&self.instances, (translation.x, translation.y))
+    }
+
+    /// Move the hero to a position, along with the camera.
+    fn place_hero(&self, (x, y): (f32, f32), world: &World) {
+        let mut storage = world.write_storage::<Transform>();
+        let hero_transform = {
+            let mut t = Transform::default();
+            t.set_xyz(x, y, 0.0);
+            t
+        };
+        let cam_trans = {
+            let mut t = hero_transform.clone();
+            t.set_z(entities::CAM_Z_POS);
+            t
+        };
+
+        storage.insert(self.hero, hero_transform).unwrap();
+        storage.insert(self.camera, cam_trans).unwrap();
+    }
+
+    /// The layer of the instance's entities, each nested instance has its own.
+    fn layer(&self) -> LayerId {
+        LayerId::instance(self.instances.len())
+    }
+
+    /// Show the hero and the instance, nothing else.
+    fn show(&self, world: &World) {
+        world
+            .write_resource::<ActiveLayers>()
+            .set(&[LayerId::HERO, self.layer()]);
     }
 }
 
-impl SimpleState for InstanceState {
+impl<'a, 'b> State<RustymonGameData<'a, 'b>, StateEvent> for InstanceState {
     /// Create and add entities to the world
-    fn on_start(&mut self, data: StateData<GameData>) {
-        println!("Starting InstanceState");
+    fn on_start(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
+        let world = data.world;
+
+        let map = self.map(world);
+        self.entities = maps::spawn_map(&map, world);
 
-        // set up hero and camera position
+        // Map entities are built for the overworld, they only belong to this instance.
         {
-            let mut storage = data.world.write_storage::<Transform>();
-            let hero_transform = self.build_transform_from_spawn();
-            let cam_trans = {
-                let mut t = hero_transform.clone();
-                t.set_z(entities::CAM_Z_POS);
-                t
-            };
-
-            storage.insert(self.hero, hero_transform.clone()).unwrap();
-            storage.insert(self.camera, cam_trans).unwrap();
+            let mut layers = world.write_storage::<Layer>();
+            for &entity in &self.entities {
+                layers.insert(entity, Layer::new(self.layer())).unwrap();
+            }
         }
+        self.show(world);
+
+        self.place_hero(self.instance().spawn, world);
 
         // set up world boundaries
-        *data.world.write_resource::<WorldBounds>() = self.instance.bounds;
+        world.add_resource(map.bounds());
     }
 
     /// Remove entities specific to the instance.
-    fn on_stop(&mut self, _data: StateData<GameData>) {
-        println!("Stoping InstanceState");
+    fn on_stop(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
+        for entity in self.entities.drain(..) {
+            if data.world.is_alive(entity) {
+                data.world.delete_entity(entity).unwrap();
+            }
+        }
+    }
+
+    fn update(&mut self, data: StateData<RustymonGameData<'a, 'b>>) -> RustymonTrans<'a, 'b> {
+        data.data.update(&data.world);
+
+        let state = data.world.read_resource::<GameState>().clone();
+        match state {
+            GameState::Battle(mob) => Trans::Push(Box::new(BattleState::new(
+                mob,
+                self.camera,
+                self.resume_state(data.world),
+            ))),
+            GameState::Dialogue(npc) => Trans::Push(Box::new(DialogueState::new(
+                npc,
+                self.camera,
+                self.resume_state(data.world),
+            ))),
+            _ => match state.layer_transition(self.instances.len()) {
+                // The nested instance replaces this one until the hero leaves it.
+                LayerTransition::Enter(instances) => Trans::Push(Box::new(InstanceState::new(
+                    instances,
+                    self.hero,
+                    self.camera,
+                ))),
+                LayerTransition::Leave => Trans::Pop,
+                LayerTransition::Stay => Trans::None,
+            },
+        }
     }
 
-    fn update(&mut self, data: &mut StateData<GameData>) -> SimpleTrans {
-        data.data.update(&mut data.world);
+    /// Show the instance again after a battle or after leaving a nested instance.
+    fn on_resume(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
+        let world = data.world;
+        self.show(world);
 
-        match *data.world.read_resource::<GameState>() {
-            GameState::Overworld(_) => Trans::Pop,
-            _ => Trans::None,
+        // Restore the instance's boundaries and movement mode, a nested instance replaces them.
+        let map = self.map(world);
+        world.add_resource(map.bounds());
+        world.add_resource(map.movement);
+
+        // Put the hero back where the game state says, unless this instance is being left too.
+        let position = match &*world.read_resource::<GameState>() {
+            GameState::Instance(instances) if instances.len() == self.instances.len() => {
+                instances.last().map(|instance| instance.spawn)
+            }
+            _ => None,
+        };
+        if let Some(position) = position {
+            self.place_hero(position, world);
         }
     }
 
-    fn handle_event(&mut self, data: StateData<GameData>, event: StateEvent) -> SimpleTrans {
+    fn handle_event(
+        &mut self,
+        data: StateData<RustymonGameData<'a, 'b>>,
+        event: StateEvent,
+    ) -> RustymonTrans<'a, 'b> {
+        if let StateEvent::Window(event) = &event {
+            if input::is_close_requested(&event) {
+                return Trans::Quit;
+            }
+        }
+        if data.world.read_resource::<ScreenTransition>().is_running() {
+            return Trans::None;
+        }
+
+        overworld::handle_save_request(&event, data.world);
+        if overworld::is_inventory_request(&event) {
+            // Resuming puts the hero back at the position held by the game state.
+            *data.world.write_resource::<GameState>() = self.resume_state(data.world);
+            return Trans::Push(Box::new(InventoryState::default()));
+        }
+        if overworld::is_party_request(&event) {
+            *data.world.write_resource::<GameState>() = self.resume_state(data.world);
+            return Trans::Push(Box::new(PartyState::default()));
+        }
         if let StateEvent::Window(event) = &event {
             if input::is_key_down(&event, VirtualKeyCode::Escape) {
-                *data.world.write_resource::<GameState>() =
-                    GameState::Overworld(self.instance.exit);
+                data.world.write_resource::<ScreenTransition>().start(
+                    self.instance().transition,
+                    GameState::leaving(&self.instances),
+                );
             }
         }
         Trans::None
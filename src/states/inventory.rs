@@ -0,0 +1,187 @@
+use crate::{
+    creatures::SpeciesDatabase,
+    game_data::{RustymonGameData, RustymonTrans},
+    items::{self, Inventory, ItemDatabase, Pocket, UseContext},
+    resources::Party,
+};
+use amethyst::{ecs::World, input, winit::VirtualKeyCode, State, StateData, StateEvent, Trans};
+use log::info;
+
+/// Keys choosing a pocket, an item or a creature depending on the menu.
+const CHOICE_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+/// What the number keys choose.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Menu {
+    Pockets,
+    Items(Pocket),
+    /// The creature of the party to use an item on, by id.
+    Target(String),
+}
+
+impl Default for Menu {
+    fn default() -> Self {
+        Menu::Pockets
+    }
+}
+
+/// Inventory menu, pushed on top of the overworld or an instance.
+/// The simulation is paused while it is open. Number keys pick a pocket, an item
+/// then the creature to use it on, Escape goes back and I closes the menu.
+#[derive(Default)]
+pub struct InventoryState {
+    menu: Menu,
+}
+
+impl InventoryState {
+    fn print_menu(&self, world: &World) {
+        let inventory = world.read_resource::<Inventory>();
+        let items = world.read_resource::<ItemDatabase>();
+        match &self.menu {
+            Menu::Pockets => {
+                info!("Inventory:");
+                for (index, pocket) in Pocket::ALL.iter().enumerate() {
+                    let count = inventory.pocket(*pocket, &items).len();
+                    info!("  [{}] {} ({})", index + 1, pocket.name(), count);
+                }
+                info!("  [I] Close");
+            }
+            Menu::Items(pocket) => {
+                info!("{}:", pocket.name());
+                for (index, (item, count)) in inventory.pocket(*pocket, &items).iter().enumerate() {
+                    let definition = &items.items[*item];
+                    info!(
+                        "  [{}] {} (x{}): {}",
+                        index + 1,
+                        definition.name,
+                        count,
+                        definition.description
+                    );
+                }
+                info!("  [Esc] Back");
+            }
+            Menu::Target(item) => {
+                let database = world.read_resource::<SpeciesDatabase>();
+                info!("Use {} on:", items.items[item].name);
+                let party = world.read_resource::<Party>();
+                for (index, creature) in party.creatures().iter().enumerate() {
+                    info!(
+                        "  [{}] {} Lv.{} ({} HP)",
+                        index + 1,
+                        creature.name(&database),
+                        creature.level,
+                        creature.hp
+                    );
+                }
+                info!("  [Esc] Back");
+            }
+        }
+    }
+
+    /// Handle a number key, return the next menu.
+    fn choose(&self, index: usize, world: &World) -> Option<Menu> {
+        match &self.menu {
+            Menu::Pockets => Pocket::ALL.get(index).map(|pocket| Menu::Items(*pocket)),
+            Menu::Items(pocket) => {
+                let inventory = world.read_resource::<Inventory>();
+                let items = world.read_resource::<ItemDatabase>();
+                let (item, _) = *inventory.pocket(*pocket, &items).get(index)?;
+                if items.items[item].usable_in(UseContext::Overworld) {
+                    Some(Menu::Target(item.to_owned()))
+                } else {
+                    info!("This can't be used now.");
+                    None
+                }
+            }
+            Menu::Target(item) => {
+                let database = world.read_resource::<SpeciesDatabase>();
+                let mut party = world.write_resource::<Party>();
+                let creature = party.creatures_mut().get_mut(index)?;
+                let result = items::use_on_creature(
+                    item,
+                    creature,
+                    &mut world.write_resource::<Inventory>(),
+                    &world.read_resource::<ItemDatabase>(),
+                    &database,
+                );
+                match result {
+                    Ok(outcomes) => {
+                        for outcome in &outcomes {
+                            info!(
+                                "{}",
+                                items::describe_outcome(
+                                    outcome,
+                                    creature.name(&database),
+                                    &database
+                                )
+                            );
+                        }
+                    }
+                    Err(error) => info!("{}", error),
+                }
+                let pocket = world.read_resource::<ItemDatabase>().items[item].pocket;
+                Some(Menu::Items(pocket))
+            }
+        }
+    }
+}
+
+impl<'a, 'b> State<RustymonGameData<'a, 'b>, StateEvent> for InventoryState {
+    fn on_start(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
+        self.print_menu(data.world);
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<RustymonGameData<'a, 'b>>,
+        event: StateEvent,
+    ) -> RustymonTrans<'a, 'b> {
+        let event = match &event {
+            StateEvent::Window(event) => event,
+            _ => return Trans::None,
+        };
+        if input::is_close_requested(event) {
+            return Trans::Quit;
+        }
+        if input::is_key_down(event, VirtualKeyCode::I) {
+            return Trans::Pop;
+        }
+
+        let next = if input::is_key_down(event, VirtualKeyCode::Escape) {
+            match &self.menu {
+                Menu::Pockets => return Trans::Pop,
+                Menu::Items(_) => Some(Menu::Pockets),
+                Menu::Target(item) => {
+                    let pocket = data.world.read_resource::<ItemDatabase>().items[item].pocket;
+                    Some(Menu::Items(pocket))
+                }
+            }
+        } else {
+            CHOICE_KEYS
+                .iter()
+                .position(|key| input::is_key_down(event, *key))
+                .and_then(|index| self.choose(index, data.world))
+        };
+
+        if let Some(menu) = next {
+            self.menu = menu;
+            self.print_menu(data.world);
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, data: StateData<RustymonGameData<'a, 'b>>) -> RustymonTrans<'a, 'b> {
+        data.data.update_frame(&data.world);
+        Trans::None
+    }
+}
@@ -1,21 +1,93 @@
+mod battle;
+mod dialogue;
 mod instance;
+mod inventory;
 mod overworld;
+mod party;
+mod title;
+
+use amethyst::ecs::Entity;
 
 pub use self::{
+    battle::BattleState,
+    dialogue::DialogueState,
     instance::{Instance, InstanceState},
+    inventory::InventoryState,
     overworld::LoadingState as OverworldState,
+    party::PartyState,
+    title::TitleState,
 };
 
 /// Represent an order to transition into another states.
 /// Each state will test the current value of this ressource.
 /// If it doesn't match the one they are attached to then it has to
 /// transition to the requested one.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum GameState {
     /// Should transition to the overworld state and set the players at the given position.
     Overworld((f32, f32)),
-    /// Should transition to the instance state. Inside the provided instance.
-    Instance(Instance),
+    /// Should transition to the instance state, inside the last of the provided instances.
+    /// Each instance has been entered from the previous one, the first one from the overworld.
+    /// The hero is placed at the spawn of the last instance.
+    Instance(Vec<Instance>),
+    /// Should transition to the battle state, against the given mob.
+    Battle(Entity),
+    /// Should transition to the dialogue state, with the given NPC, sign or chest.
+    Dialogue(Entity),
+}
+
+/// What the state of a layer (the overworld or an instance) has to do to reach the `GameState`.
+#[derive(Clone, Debug)]
+pub enum LayerTransition {
+    /// The layer is the requested one, or the requested state isn't a layer.
+    Stay,
+    /// Push the state of the last of these instances, entered from the current layer.
+    Enter(Vec<Instance>),
+    /// Pop the layer, the requested one is below it.
+    Leave,
+}
+
+impl GameState {
+    /// Be at a position inside the last of nested instances, or in the overworld if there are none.
+    pub fn inside(instances: &[Instance], position: (f32, f32)) -> Self {
+        let mut instances = instances.to_vec();
+        match instances.last_mut() {
+            Some(instance) => {
+                instance.spawn = position;
+                GameState::Instance(instances)
+            }
+            None => GameState::Overworld(position),
+        }
+    }
+
+    /// Leave the last of nested instances, coming out at its exit in the previous layer.
+    pub fn leaving(instances: &[Instance]) -> Self {
+        let (current, previous) = instances.split_last().expect("not inside an instance");
+        GameState::inside(previous, current.exit)
+    }
+
+    /// Enter an instance from the current layer, the overworld unless inside an instance.
+    pub fn entering(&self, instance: Instance) -> Self {
+        let mut instances = match self {
+            GameState::Instance(instances) => instances.clone(),
+            _ => Vec::new(),
+        };
+        instances.push(instance);
+        GameState::Instance(instances)
+    }
+
+    /// What the layer `depth` instances deep, 0 being the overworld, has to do.
+    /// Layers are pushed and popped one at a time until the requested one is on top.
+    pub fn layer_transition(&self, depth: usize) -> LayerTransition {
+        match self {
+            GameState::Overworld(_) if depth > 0 => LayerTransition::Leave,
+            GameState::Instance(instances) if instances.len() < depth => LayerTransition::Leave,
+            GameState::Instance(instances) if instances.len() > depth => {
+                LayerTransition::Enter(instances[..=depth].to_vec())
+            }
+            _ => LayerTransition::Stay,
+        }
+    }
 }
 
 impl Default for GameState {
@@ -23,3 +95,140 @@ impl Default for GameState {
         GameState::Overworld((0.0, 0.0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::ecs::World;
+
+    fn instance(name: &str, exit: (f32, f32)) -> Instance {
+        Instance {
+            name: name.to_owned(),
+            map: format!("maps/{}.ron", name),
+            spawn: (0.0, 0.0),
+            exit,
+            transition: Default::default(),
+        }
+    }
+
+    /// Push and pop layers until the top one stays, as the states do.
+    /// Layers are represented by the instances leading to them, the overworld by none.
+    fn settle(layers: &mut Vec<Vec<Instance>>, state: &GameState) {
+        loop {
+            let depth = layers.last().unwrap().len();
+            match state.layer_transition(depth) {
+                LayerTransition::Enter(instances) => {
+                    assert_eq!(instances.len(), depth + 1);
+                    layers.push(instances);
+                }
+                LayerTransition::Leave => {
+                    layers.pop();
+                }
+                LayerTransition::Stay => return,
+            }
+        }
+    }
+
+    fn names(layers: &[Vec<Instance>]) -> Vec<Vec<&str>> {
+        layers
+            .iter()
+            .map(|layer| layer.iter().map(|i| i.name.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn enter_and_leave_nested_instances() {
+        let mut layers = vec![Vec::new()];
+        let state = GameState::Overworld((5.0, 5.0)).entering(instance("house", (1.0, 2.0)));
+        settle(&mut layers, &state);
+        assert_eq!(names(&layers), vec![vec![], vec!["house"]]);
+
+        let state = state.entering(instance("cellar", (3.0, 4.0)));
+        settle(&mut layers, &state);
+        assert_eq!(
+            names(&layers),
+            vec![vec![], vec!["house"], vec!["house", "cellar"]]
+        );
+
+        // Leaving the cellar comes back into the house, at the cellar's exit.
+        let state = GameState::leaving(layers.last().unwrap());
+        settle(&mut layers, &state);
+        assert_eq!(names(&layers), vec![vec![], vec!["house"]]);
+        match &state {
+            GameState::Instance(instances) => assert_eq!(instances[0].spawn, (3.0, 4.0)),
+            other => panic!("unexpected state: {:?}", other),
+        }
+
+        let state = GameState::leaving(layers.last().unwrap());
+        settle(&mut layers, &state);
+        assert_eq!(names(&layers), vec![Vec::<&str>::new()]);
+        match state {
+            GameState::Overworld(position) => assert_eq!(position, (1.0, 2.0)),
+            other => panic!("unexpected state: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deep_dungeon_floors() {
+        let mut layers = vec![Vec::new()];
+        let mut state = GameState::default();
+        for floor in 0..5 {
+            state = state.entering(instance(&format!("floor{}", floor), (floor as f32, 0.0)));
+            settle(&mut layers, &state);
+            assert_eq!(layers.len(), floor + 2);
+        }
+
+        for floor in (0..5).rev() {
+            state = GameState::leaving(layers.last().unwrap());
+            settle(&mut layers, &state);
+            assert_eq!(layers.len(), floor + 1);
+        }
+        match state {
+            GameState::Overworld(position) => assert_eq!(position, (0.0, 0.0)),
+            other => panic!("unexpected state: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn restoring_nested_instances_pushes_each_layer() {
+        let mut layers = vec![Vec::new()];
+        let state = GameState::inside(
+            &[
+                instance("house", (1.0, 2.0)),
+                instance("cellar", (3.0, 4.0)),
+            ],
+            (7.0, 8.0),
+        );
+        settle(&mut layers, &state);
+        assert_eq!(
+            names(&layers),
+            vec![vec![], vec!["house"], vec!["house", "cellar"]]
+        );
+        assert_eq!(layers[2][1].spawn, (7.0, 8.0));
+    }
+
+    #[test]
+    fn going_back_to_the_overworld_pops_every_instance() {
+        let mut layers = vec![Vec::new()];
+        let state = GameState::default()
+            .entering(instance("house", (1.0, 2.0)))
+            .entering(instance("cellar", (3.0, 4.0)));
+        settle(&mut layers, &state);
+        assert_eq!(layers.len(), 3);
+
+        settle(&mut layers, &GameState::Overworld((9.0, 9.0)));
+        assert_eq!(layers.len(), 1);
+    }
+
+    #[test]
+    fn battles_and_dialogues_keep_the_layers() {
+        let state = GameState::default().entering(instance("house", (1.0, 2.0)));
+        let mut layers = vec![Vec::new()];
+        settle(&mut layers, &state);
+
+        let npc = World::new().entities().create();
+        settle(&mut layers, &GameState::Dialogue(npc));
+        settle(&mut layers, &GameState::Battle(npc));
+        assert_eq!(layers.len(), 2);
+    }
+}
@@ -1,28 +1,55 @@
 use crate::{
     animations::{HeroAnimationId, SpriteAnimation},
     assets,
-    components::{Active, InstanceCompat, OverworldCompat},
+    components::LayerId,
+    creatures::SpeciesDatabase,
+    dialogue::DialogueDatabase,
     entities,
-    resources::WorldBounds,
+    game_data::{RustymonGameData, RustymonTrans},
+    items::ItemDatabase,
+    maps::{self, InstanceMaps, MapDefinition, OverworldChunks},
+    resources::ActiveLayers,
+    save::{self, SaveData},
+    transition::ScreenTransition,
 };
 
-use super::{GameState, Instance, InstanceState};
+use super::{
+    BattleState, DialogueState, GameState, InstanceState, InventoryState, LayerTransition,
+    PartyState, TitleState,
+};
 
 use amethyst::{
     animation::AnimationSet,
-    assets::{Handle, ProgressCounter},
+    assets::{AssetStorage, Handle, ProgressCounter},
     core::transform::Transform,
     ecs::prelude::*,
+    input::{is_close_requested, is_key_down},
     renderer::{DisplayConfig, SpriteRender},
-    GameData, SimpleState, SimpleTrans, StateData, Trans,
+    winit::VirtualKeyCode,
+    State, StateData, StateEvent, Trans,
 };
+use log::{error, info};
+use std::{collections::HashSet, mem};
+
+/// Path of the overworld map, relative to the asset directory.
+const OVERWORLD_MAP_PATH: &str = "maps/overworld.ron";
+/// Path of the species database, relative to the asset directory.
+const SPECIES_DATABASE_PATH: &str = "creatures/species.ron";
+/// Path of the item database, relative to the asset directory.
+const ITEM_DATABASE_PATH: &str = "items/items.ron";
+/// Path of the dialogue database, relative to the asset directory.
+const DIALOGUE_DATABASE_PATH: &str = "dialogues/dialogues.ron";
 
-use ncollide2d::{math::Vector, shape::Cuboid};
+/// Layers shown while walking around the overworld.
+const OVERWORLD_LAYERS: [LayerId; 2] = [LayerId::HERO, LayerId::OVERWORLD];
 
 pub struct OverworldState {
     display_config: DisplayConfig,
     /// Option so I can transfer ownership of the animation set ... Is there a better option ?
     hero_animations: Option<AnimationSet<HeroAnimationId, SpriteRender>>,
+    map: Handle<MapDefinition>,
+    /// The save to restore once the map is spawned, None for a new game.
+    save: Option<SaveData>,
     hero: Option<Entity>,
     camera: Option<Entity>,
 }
@@ -31,10 +58,14 @@ impl OverworldState {
     pub fn new(
         display_config: DisplayConfig,
         hero_animations: AnimationSet<HeroAnimationId, SpriteRender>,
+        map: Handle<MapDefinition>,
+        save: Option<SaveData>,
     ) -> Self {
         OverworldState {
             display_config,
             hero_animations: Some(hero_animations),
+            map,
+            save,
             hero: None,
             camera: None,
         }
@@ -42,84 +73,123 @@ impl OverworldState {
 }
 
 impl OverworldState {
-    fn build_overworld_bounds() -> WorldBounds {
-        WorldBounds::new_around_origin(10000.0, 10000.0)
+    /// Use the bounds and the movement mode of the overworld map, instances replace them.
+    fn restore_map_settings(&self, world: &mut World) {
+        let (bounds, movement) = {
+            let maps = world.read_resource::<AssetStorage<MapDefinition>>();
+            let map = maps.get(&self.map).unwrap();
+            (map.bounds(), map.movement)
+        };
+        world.add_resource(bounds);
+        world.add_resource(movement);
+    }
+
+    /// Load the chunks of the overworld seen by the camera and unload the others.
+    fn stream_chunks(&self, world: &mut World) {
+        let camera = {
+            let transforms = world.read_storage::<Transform>();
+            let translation = transforms.get(self.camera.unwrap()).unwrap().translation();
+            (translation.x, translation.y)
+        };
+        let (width, height) = self.display_config.dimensions.unwrap();
+        maps::stream_chunks(camera, (width as f32 * 0.5, height as f32 * 0.5), world);
+    }
+
+    fn hero_position(&self, world: &World) -> (f32, f32) {
+        let transforms = world.read_storage::<Transform>();
+        let translation = transforms.get(self.hero.unwrap()).unwrap().translation();
+        (translation.x, translation.y)
     }
 }
 
-impl SimpleState for OverworldState {
-    fn on_start(&mut self, data: StateData<GameData>) {
+impl<'a, 'b> State<RustymonGameData<'a, 'b>, StateEvent> for OverworldState {
+    fn on_start(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
         let world = data.world;
-        world.add_resource(Self::build_overworld_bounds());
 
-        // TODO: Move asset loading code into loading state...
-        let building_texture = assets::load_texture("sprite_sheets/buildings.png", world);
-        let building_sprite_sheet =
-            assets::load_sprite_sheet("sprite_sheets/buildings.ron", building_texture, world);
-
-        let ferris_texture = assets::load_texture("sprite_sheets/ferris.png", world);
-        let ferris_sprite_sheet =
-            assets::load_sprite_sheet("sprite_sheets/ferris.ron", ferris_texture, world);
+        // The map has been validated by the loading state.
+        let map = world
+            .read_resource::<AssetStorage<MapDefinition>>()
+            .get(&self.map)
+            .cloned()
+            .unwrap();
+        world.add_resource(map.bounds());
+        // Entities are spawned by chunks around the camera, once the hero is in place.
+        let chunks = OverworldChunks::new(map, world);
+        world.add_resource(chunks);
 
         let hero = entities::build_hero(self.hero_animations.take().unwrap(), world);
-        entities::build_ferris(50.0, 0.0, 300.0, 100.0, ferris_sprite_sheet, world);
         let camera = entities::build_camera(&self.display_config, world, hero);
-        entities::build_building(100.0, 100.0, building_sprite_sheet.clone(), world);
-        entities::build_portal(
-            100.0,
-            76.0,
-            Instance {
-                spawn: (1000.0, 1000.0),
-                bounds: WorldBounds::new(975.0, 1025.0, 975.0, 1025.0),
-                exit: (100.0, 76.0),
-            },
-            Cuboid::new(Vector::new(16.0, 16.0)),
-            building_sprite_sheet,
-            world,
-        );
+        entities::build_transition_overlay(&self.display_config, world);
+
+        if let Some(save) = self.save.take() {
+            save.restore(world, hero, camera);
+        }
 
         self.hero = Some(hero);
         self.camera = Some(camera);
+        world
+            .write_resource::<ActiveLayers>()
+            .set(&OVERWORLD_LAYERS);
     }
 
-    fn update(&mut self, data: &mut StateData<GameData>) -> SimpleTrans {
-        data.data.update(data.world);
-        let state = *data.world.read_resource::<GameState>();
+    fn update(&mut self, data: StateData<RustymonGameData<'a, 'b>>) -> RustymonTrans<'a, 'b> {
+        data.data.update(&data.world);
+        self.stream_chunks(data.world);
+        let state = data.world.read_resource::<GameState>().clone();
+        if let LayerTransition::Enter(instances) = state.layer_transition(0) {
+            // Transition to instance state, which shows its own layer instead of the overworld.
+            return Trans::Push(Box::new(InstanceState::new(
+                instances,
+                self.hero.unwrap(),
+                self.camera.unwrap(),
+            )));
+        }
+
         match state {
-            GameState::Instance(instance) => {
-                // Deactivate entities which are not 'instance compatible'
-                data.world.exec(
-                    |(entities, mut actives, instance_compats): (
-                        Entities,
-                        WriteStorage<Active>,
-                        ReadStorage<InstanceCompat>,
-                    )| {
-                        for (entity, _) in (&entities, !&instance_compats).join() {
-                            actives.remove(entity);
-                        }
-                    },
-                );
-
-                // Transition to instance state
-                Trans::Push(Box::new(InstanceState::new(
-                    instance.clone(),
-                    self.hero.unwrap(),
+            GameState::Battle(mob) => {
+                // Transition to battle state
+                Trans::Push(Box::new(BattleState::new(
+                    mob,
                     self.camera.unwrap(),
+                    GameState::Overworld(self.hero_position(data.world)),
                 )))
             }
+            GameState::Dialogue(npc) => Trans::Push(Box::new(DialogueState::new(
+                npc,
+                self.camera.unwrap(),
+                GameState::Overworld(self.hero_position(data.world)),
+            ))),
             _ => Trans::None,
         }
     }
 
-    fn on_pause(&mut self, _data: StateData<GameData>) {
-        println!("Pausing OverworldState");
+    fn handle_event(
+        &mut self,
+        data: StateData<RustymonGameData<'a, 'b>>,
+        event: StateEvent,
+    ) -> RustymonTrans<'a, 'b> {
+        // Menus and saves wait for the end of the transition.
+        if data.world.read_resource::<ScreenTransition>().is_running() {
+            return handle_close_request(&event);
+        }
+        handle_save_request(&event, data.world);
+        if is_inventory_request(&event) {
+            // Resuming puts the hero back at the position held by the game state.
+            let position = self.hero_position(data.world);
+            *data.world.write_resource::<GameState>() = GameState::Overworld(position);
+            return Trans::Push(Box::new(InventoryState::default()));
+        }
+        if is_party_request(&event) {
+            let position = self.hero_position(data.world);
+            *data.world.write_resource::<GameState>() = GameState::Overworld(position);
+            return Trans::Push(Box::new(PartyState::default()));
+        }
+        handle_close_request(&event)
     }
 
-    fn on_resume(&mut self, data: StateData<GameData>) {
-        println!("Resuming OverworldState");
-
-        // Restore overworld's boundaries
-        *data.world.write_resource::<WorldBounds>() = Self::build_overworld_bounds();
+    fn on_resume(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
+        // Restore overworld's boundaries and movement mode
+        self.restore_map_settings(data.world);
 
         // Put hero back to the position it was before state switch.
         // Point camera on hero.
@@ -146,18 +216,10 @@ impl SimpleState for OverworldState {
             }
         }
 
-        // Activate entities which are 'overworld compatible' and currently inactive
-        data.world.exec(
-            |(entities, mut actives, overworld_compats): (
-                Entities,
-                WriteStorage<Active>,
-                ReadStorage<OverworldCompat>,
-            )| {
-                for (entity, _) in (&entities, &overworld_compats).join() {
-                    actives.insert(entity, Active).unwrap();
-                }
-            },
-        );
+        // Show the overworld again, instead of the battle or the instance.
+        data.world
+            .write_resource::<ActiveLayers>()
+            .set(&OVERWORLD_LAYERS);
     }
 }
 
@@ -173,6 +235,15 @@ pub struct LoadingState {
     go_right_backward_animation_handle: Option<Handle<SpriteAnimation>>,
     go_left_backward_animation_handle: Option<Handle<SpriteAnimation>>,
     go_left_forward_animation_handle: Option<Handle<SpriteAnimation>>,
+    map_handle: Option<Handle<MapDefinition>>,
+    species_handle: Option<Handle<SpeciesDatabase>>,
+    items_handle: Option<Handle<ItemDatabase>>,
+    dialogues_handle: Option<Handle<DialogueDatabase>>,
+    /// Paths of the maps already loaded or being loaded.
+    requested_maps: HashSet<String>,
+    /// Instance maps being loaded, along with their path.
+    pending_maps: Vec<(String, Handle<MapDefinition>)>,
+    instance_maps: InstanceMaps,
 }
 
 impl LoadingState {
@@ -189,9 +260,72 @@ impl LoadingState {
             go_right_backward_animation_handle: None,
             go_left_backward_animation_handle: None,
             go_left_forward_animation_handle: None,
+            map_handle: None,
+            species_handle: None,
+            items_handle: None,
+            dialogues_handle: None,
+            requested_maps: HashSet::new(),
+            pending_maps: Vec::new(),
+            instance_maps: InstanceMaps::default(),
+        }
+    }
+
+    /// Load the maps of the instances of a map which are not loaded yet.
+    fn load_instance_maps(&mut self, map: &MapDefinition, world: &World) {
+        for instance in map.instances.values() {
+            if self.requested_maps.insert(instance.map.clone()) {
+                let handle = assets::load_map(&instance.map, &mut self.progress, world);
+                self.pending_maps.push((instance.map.clone(), handle));
+            }
         }
     }
 
+    /// Validate the instance maps loaded since the last call, then load the maps of their
+    /// own instances. Once every map is loaded, go to the title menu.
+    fn update_instance_maps<'a, 'b>(&mut self, world: &mut World) -> RustymonTrans<'a, 'b> {
+        for (path, handle) in mem::replace(&mut self.pending_maps, Vec::new()) {
+            let map = world
+                .read_resource::<AssetStorage<MapDefinition>>()
+                .get(&handle)
+                .cloned();
+            let map = match map {
+                Some(map) => map,
+                None => {
+                    error!("{}: failed to load map", path);
+                    return Trans::Quit;
+                }
+            };
+            let validation = map.validate(
+                &path,
+                &world.read_resource::<SpeciesDatabase>(),
+                &world.read_resource::<ItemDatabase>(),
+                &world.read_resource::<DialogueDatabase>(),
+            );
+            if let Err(errors) = validation {
+                for error in errors {
+                    error!("{}", error);
+                }
+                return Trans::Quit;
+            }
+
+            self.load_instance_maps(&map, world);
+            self.instance_maps.insert(path, map);
+        }
+        if !self.pending_maps.is_empty() {
+            return Trans::None;
+        }
+
+        world.add_resource(mem::replace(
+            &mut self.instance_maps,
+            InstanceMaps::default(),
+        ));
+        Trans::Switch(Box::new(TitleState::new(
+            self.display_config.clone(),
+            self.build_hero_animations(world),
+            self.map_handle.take().unwrap(),
+        )))
+    }
+
     fn build_hero_animations(
         &mut self,
         world: &World,
@@ -237,8 +371,8 @@ impl LoadingState {
     }
 }
 
-impl SimpleState for LoadingState {
-    fn on_start(&mut self, data: StateData<GameData>) {
+impl<'a, 'b> State<RustymonGameData<'a, 'b>, StateEvent> for LoadingState {
+    fn on_start(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
         self.idle_animation_handle =
             self.load_sprite_animation("animations/hero/idle.ron", data.world);
         self.go_right_animation_handle =
@@ -257,17 +391,160 @@ impl SimpleState for LoadingState {
             self.load_sprite_animation("animations/hero/go_left_backward.ron", data.world);
         self.go_left_forward_animation_handle =
             self.load_sprite_animation("animations/hero/go_left_forward.ron", data.world);
+        self.map_handle = Some(assets::load_map(
+            OVERWORLD_MAP_PATH,
+            &mut self.progress,
+            data.world,
+        ));
+        self.species_handle = Some(assets::load_species_database(
+            SPECIES_DATABASE_PATH,
+            &mut self.progress,
+            data.world,
+        ));
+        self.items_handle = Some(assets::load_item_database(
+            ITEM_DATABASE_PATH,
+            &mut self.progress,
+            data.world,
+        ));
+        self.dialogues_handle = Some(assets::load_dialogue_database(
+            DIALOGUE_DATABASE_PATH,
+            &mut self.progress,
+            data.world,
+        ));
     }
 
-    fn update(&mut self, data: &mut StateData<GameData>) -> SimpleTrans {
-        data.data.update(&mut data.world);
-        let world = &data.world;
+    fn handle_event(
+        &mut self,
+        _data: StateData<RustymonGameData<'a, 'b>>,
+        event: StateEvent,
+    ) -> RustymonTrans<'a, 'b> {
+        handle_close_request(&event)
+    }
+
+    fn update(&mut self, data: StateData<RustymonGameData<'a, 'b>>) -> RustymonTrans<'a, 'b> {
+        data.data.update(&data.world);
+        let world = data.world;
         if self.progress.is_complete() {
-            return Trans::Switch(Box::new(OverworldState::new(
-                self.display_config.clone(),
-                self.build_hero_animations(world),
-            )));
+            // The databases and the overworld map are loaded first, then the instance maps.
+            if self.species_handle.is_none() {
+                return self.update_instance_maps(world);
+            }
+
+            let species = world
+                .read_resource::<AssetStorage<SpeciesDatabase>>()
+                .get(&self.species_handle.take().unwrap())
+                .cloned();
+            let species = match species {
+                Some(species) => species,
+                None => {
+                    error!("{}: failed to load species", SPECIES_DATABASE_PATH);
+                    return Trans::Quit;
+                }
+            };
+            if let Err(errors) = species.validate() {
+                for error in errors {
+                    error!("{}: {}", SPECIES_DATABASE_PATH, error);
+                }
+                return Trans::Quit;
+            }
+
+            let items = world
+                .read_resource::<AssetStorage<ItemDatabase>>()
+                .get(&self.items_handle.take().unwrap())
+                .cloned();
+            let items = match items {
+                Some(items) => items,
+                None => {
+                    error!("{}: failed to load items", ITEM_DATABASE_PATH);
+                    return Trans::Quit;
+                }
+            };
+            if let Err(errors) = items.validate(&species) {
+                for error in errors {
+                    error!("{}: {}", ITEM_DATABASE_PATH, error);
+                }
+                return Trans::Quit;
+            }
+
+            let dialogues = world
+                .read_resource::<AssetStorage<DialogueDatabase>>()
+                .get(&self.dialogues_handle.take().unwrap())
+                .cloned();
+            let dialogues = match dialogues {
+                Some(dialogues) => dialogues,
+                None => {
+                    error!("{}: failed to load dialogues", DIALOGUE_DATABASE_PATH);
+                    return Trans::Quit;
+                }
+            };
+            if let Err(errors) = dialogues.validate(&species, &items) {
+                for error in errors {
+                    error!("{}: {}", DIALOGUE_DATABASE_PATH, error);
+                }
+                return Trans::Quit;
+            }
+
+            let map = world
+                .read_resource::<AssetStorage<MapDefinition>>()
+                .get(self.map_handle.as_ref().unwrap())
+                .cloned();
+            let map = match map {
+                Some(map) => map,
+                None => {
+                    error!("{}: failed to load map", OVERWORLD_MAP_PATH);
+                    return Trans::Quit;
+                }
+            };
+            if let Err(errors) = map.validate(OVERWORLD_MAP_PATH, &species, &items, &dialogues) {
+                for error in errors {
+                    error!("{}", error);
+                }
+                return Trans::Quit;
+            }
+
+            world.add_resource(species);
+            world.add_resource(items);
+            world.add_resource(dialogues);
+            self.requested_maps.insert(OVERWORLD_MAP_PATH.to_owned());
+            self.load_instance_maps(&map, world);
+            return self.update_instance_maps(world);
         }
         Trans::None
     }
 }
+
+/// Quit the game when the window is closed.
+fn handle_close_request<'a, 'b>(event: &StateEvent) -> RustymonTrans<'a, 'b> {
+    match event {
+        StateEvent::Window(event) if is_close_requested(event) => Trans::Quit,
+        _ => Trans::None,
+    }
+}
+
+/// Is the inventory menu being opened.
+pub(super) fn is_inventory_request(event: &StateEvent) -> bool {
+    match event {
+        StateEvent::Window(event) => is_key_down(event, VirtualKeyCode::I),
+        _ => false,
+    }
+}
+
+/// Is the party menu being opened.
+pub(super) fn is_party_request(event: &StateEvent) -> bool {
+    match event {
+        StateEvent::Window(event) => is_key_down(event, VirtualKeyCode::P),
+        _ => false,
+    }
+}
+
+/// Save the game when F5 is pressed.
+pub(super) fn handle_save_request(event: &StateEvent, world: &World) {
+    if let StateEvent::Window(event) = event {
+        if is_key_down(event, VirtualKeyCode::F5) {
+            match save::save_game(world) {
+                Ok(()) => info!("Game saved."),
+                Err(error) => error!("{}: {}", save::SAVE_PATH, error),
+            }
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use crate::{
+    creatures::{Creature, SpeciesDatabase},
+    game_data::{RustymonGameData, RustymonTrans},
+    resources::{BoxSlot, Party, PartyError, Storage},
+};
+use amethyst::{ecs::World, input, winit::VirtualKeyCode, State, StateData, StateEvent, Trans};
+use log::info;
+
+/// Keys choosing a creature, an action or a box depending on the menu.
+const CHOICE_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+/// What the number keys choose. Creatures of the party are given by index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Menu {
+    Party,
+    /// The actions on a creature of the party.
+    Creature(usize),
+    /// The position a creature of the party moves to.
+    Reorder(usize),
+    /// The creature of the party exchanged with another one.
+    Swap(usize),
+    /// The box a creature of the party is deposited in.
+    Deposit(usize),
+    Boxes,
+    /// The slots of a box, shown by pages of nine.
+    Box {
+        storage_box: usize,
+        page: usize,
+    },
+    /// The actions on a creature of the storage.
+    Stored(BoxSlot),
+    /// The creature of the party exchanged with a creature of the storage.
+    Exchange(BoxSlot),
+    /// The box a creature of the storage moves to.
+    Move(BoxSlot),
+}
+
+impl Default for Menu {
+    fn default() -> Self {
+        Menu::Party
+    }
+}
+
+/// Party and storage menu, pushed on top of the overworld or an instance.
+/// The simulation is paused while it is open. Number keys pick a creature then what to do
+/// with it, B opens the boxes, the arrow keys turn the pages of a box, Escape goes back
+/// and P closes the menu.
+#[derive(Default)]
+pub struct PartyState {
+    menu: Menu,
+}
+
+fn describe(creature: &Creature, database: &SpeciesDatabase) -> String {
+    format!(
+        "{} Lv.{} ({} HP)",
+        creature.name(database),
+        creature.level,
+        creature.hp
+    )
+}
+
+fn print_party(title: &str, party: &Party, database: &SpeciesDatabase) {
+    info!("{}", title);
+    for (index, creature) in party.creatures().iter().enumerate() {
+        info!("  [{}] {}", index + 1, describe(creature, database));
+    }
+}
+
+fn print_boxes(title: &str, storage: &Storage) {
+    info!("{}", title);
+    for (index, storage_box) in storage.boxes().iter().enumerate() {
+        let count = storage_box
+            .slots()
+            .iter()
+            .filter(|slot| slot.is_some())
+            .count();
+        info!(
+            "  [{}] {} ({}/{})",
+            index + 1,
+            storage_box.name,
+            count,
+            storage_box.slots().len()
+        );
+    }
+}
+
+/// Print the outcome of an operation which can fail.
+fn report<T>(result: Result<T, PartyError>, message: impl FnOnce(T) -> String) {
+    match result {
+        Ok(value) => info!("{}", message(value)),
+        Err(error) => info!("{}", error),
+    }
+}
+
+impl PartyState {
+    fn print_menu(&self, world: &World) {
+        let party = world.read_resource::<Party>();
+        let storage = world.read_resource::<Storage>();
+        let database = world.read_resource::<SpeciesDatabase>();
+        match self.menu {
+            Menu::Party => {
+                print_party("Party:", &party, &database);
+                info!("  [B] Boxes");
+                info!("  [P] Close");
+                return;
+            }
+            Menu::Creature(index) => {
+                info!("{}:", describe(&party.creatures()[index], &database));
+                info!("  [1] Move");
+                info!("  [2] Swap");
+                info!("  [3] Deposit");
+                info!("  [4] Release");
+            }
+            Menu::Reorder(_) => print_party("Move to:", &party, &database),
+            Menu::Swap(_) => print_party("Swap with:", &party, &database),
+            Menu::Deposit(_) => print_boxes("Deposit in:", &storage),
+            Menu::Boxes => print_boxes("Boxes:", &storage),
+            Menu::Box { storage_box, page } => {
+                let storage_box = &storage.boxes()[storage_box];
+                info!("{}:", storage_box.name);
+                let first = page * CHOICE_KEYS.len();
+                for (index, slot) in storage_box
+                    .slots()
+                    .iter()
+                    .skip(first)
+                    .take(CHOICE_KEYS.len())
+                    .enumerate()
+                {
+                    match slot {
+                        Some(creature) => {
+                            info!("  [{}] {}", index + 1, describe(creature, &database))
+                        }
+                        None => info!("  [{}] -", index + 1),
+                    }
+                }
+                info!("  [Left/Right] Previous/next page");
+            }
+            Menu::Stored(location) => {
+                let creature = storage.get(location).unwrap();
+                info!("{}:", describe(creature, &database));
+                info!("  [1] Withdraw");
+                info!("  [2] Swap with the party");
+                info!("  [3] Move");
+                info!("  [4] Release");
+            }
+            Menu::Exchange(_) => print_party("Swap with:", &party, &database),
+            Menu::Move(_) => print_boxes("Move to:", &storage),
+        }
+        info!("  [Esc] Back");
+    }
+
+    /// Handle a number key, return the next menu.
+    fn choose(&self, index: usize, world: &World) -> Option<Menu> {
+        let mut party = world.write_resource::<Party>();
+        let mut storage = world.write_resource::<Storage>();
+        let database = world.read_resource::<SpeciesDatabase>();
+        let party_size = party.creatures().len();
+        let box_count = storage.boxes().len();
+        match self.menu {
+            Menu::Party if index < party_size => Some(Menu::Creature(index)),
+            Menu::Creature(creature) => match index {
+                0 => Some(Menu::Reorder(creature)),
+                1 => Some(Menu::Swap(creature)),
+                2 => Some(Menu::Deposit(creature)),
+                3 => {
+                    report(party.release(creature), |released| {
+                        format!("{} was released.", released.name(&database))
+                    });
+                    Some(Menu::Party)
+                }
+                _ => None,
+            },
+            Menu::Reorder(creature) if index < party_size => {
+                report(party.reorder(creature, index), |()| "Moved.".to_owned());
+                Some(Menu::Party)
+            }
+            Menu::Swap(creature) if index < party_size => {
+                report(party.swap(creature, index), |()| "Swapped.".to_owned());
+                Some(Menu::Party)
+            }
+            Menu::Deposit(creature) if index < box_count => {
+                let name = party.creatures()[creature].name(&database).to_owned();
+                report(storage.deposit(&mut party, creature, index), |location| {
+                    let storage_box = &storage.boxes()[location.storage_box];
+                    format!("{} was sent to {}.", name, storage_box.name)
+                });
+                Some(Menu::Party)
+            }
+            Menu::Boxes if index < box_count => Some(Menu::Box {
+                storage_box: index,
+                page: 0,
+            }),
+            Menu::Box { storage_box, page } => {
+                let location = BoxSlot {
+                    storage_box,
+                    slot: page * CHOICE_KEYS.len() + index,
+                };
+                storage.get(location).map(|_| Menu::Stored(location))
+            }
+            Menu::Stored(location) => match index {
+                0 => {
+                    let name = storage.get(location).unwrap().name(&database).to_owned();
+                    report(storage.withdraw(&mut party, location), |()| {
+                        format!("{} joined the party.", name)
+                    });
+                    Some(Menu::Party)
+                }
+                1 => Some(Menu::Exchange(location)),
+                2 => Some(Menu::Move(location)),
+                3 => {
+                    report(storage.release(location), |released| {
+                        format!("{} was released.", released.name(&database))
+                    });
+                    Some(Menu::Boxes)
+                }
+                _ => None,
+            },
+            Menu::Exchange(location) if index < party_size => {
+                report(storage.swap(&mut party, index, location), |()| {
+                    "Swapped.".to_owned()
+                });
+                Some(Menu::Party)
+            }
+            Menu::Move(location) if index < box_count => {
+                let free = storage.boxes()[index]
+                    .slots()
+                    .iter()
+                    .position(Option::is_none);
+                let result = free.ok_or(PartyError::BoxFull).and_then(|slot| {
+                    let target = BoxSlot {
+                        storage_box: index,
+                        slot,
+                    };
+                    storage.move_creature(location, target)
+                });
+                report(result, |()| {
+                    format!("Moved to {}.", storage.boxes()[index].name)
+                });
+                Some(Menu::Boxes)
+            }
+            _ => None,
+        }
+    }
+
+    /// The menu Escape goes back to, None to close the menu.
+    fn back(&self) -> Option<Menu> {
+        match self.menu {
+            Menu::Party => None,
+            Menu::Creature(_) | Menu::Boxes => Some(Menu::Party),
+            Menu::Reorder(creature) | Menu::Swap(creature) | Menu::Deposit(creature) => {
+                Some(Menu::Creature(creature))
+            }
+            Menu::Box { .. } => Some(Menu::Boxes),
+            Menu::Stored(location) => Some(Menu::Box {
+                storage_box: location.storage_box,
+                page: location.slot / CHOICE_KEYS.len(),
+            }),
+            Menu::Exchange(location) | Menu::Move(location) => Some(Menu::Stored(location)),
+        }
+    }
+
+    /// Turn the pages of a box, return the next menu.
+    fn turn_page(&self, forward: bool, world: &World) -> Option<Menu> {
+        match self.menu {
+            Menu::Box { storage_box, page } => {
+                let slots = world.read_resource::<Storage>().boxes()[storage_box]
+                    .slots()
+                    .len();
+                let pages = (slots + CHOICE_KEYS.len() - 1) / CHOICE_KEYS.len();
+                let page = if forward {
+                    (page + 1) % pages
+                } else {
+                    (page + pages - 1) % pages
+                };
+                Some(Menu::Box { storage_box, page })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a, 'b> State<RustymonGameData<'a, 'b>, StateEvent> for PartyState {
+    fn on_start(&mut self, data: StateData<RustymonGameData<'a, 'b>>) {
+        self.print_menu(data.world);
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<RustymonGameData<'a, 'b>>,
+        event: StateEvent,
+    ) -> RustymonTrans<'a, 'b> {
+        let event = match &event {
+            StateEvent::Window(event) => event,
+            _ => return Trans::None,
+        };
+        if input::is_close_requested(event) {
+            return Trans::Quit;
+        }
+        if input::is_key_down(event, VirtualKeyCode::P) {
+            return Trans::Pop;
+        }
+
+        let next = if input::is_key_down(event, VirtualKeyCode::Escape) {
+            match self.back() {
+                Some(menu) => Some(menu),
+                None => return Trans::Pop,
+            }
+        } else if input::is_key_down(event, VirtualKeyCode::B) && self.menu == Menu::Party {
+            Some(Menu::Boxes)
+        } else if input::is_key_down(event, VirtualKeyCode::Right) {
+            self.turn_page(true, data.world)
+        } else if input::is_key_down(event, VirtualKeyCode::Left) {
+            self.turn_page(false, data.world)
+        } else {
+            CHOICE_KEYS
+                .iter()
+                .position(|key| input::is_key_down(event, *key))
+                .and_then(|index| self.choose(index, data.world))
+        };
+
+        if let Some(menu) = next {
+            self.menu = menu;
+            self.print_menu(data.world);
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, data: StateData<RustymonGameData<'a, 'b>>) -> RustymonTrans<'a, 'b> {
+        data.data.update_frame(&data.world);
+        Trans::None
+    }
+}
@@ -0,0 +1,121 @@
+use super::overworld::OverworldState;
+use crate::{
+    animations::HeroAnimationId,
+    creatures::{Creature, SpeciesDatabase},
+    game_data::{RustymonGameData, RustymonTrans},
+    items::Inventory,
+    maps::MapDefinition,
+    resources::{Flags, Party, Storage},
+    rng::Rng,
+    save,
+};
+use amethyst::{
+    animation::AnimationSet,
+    assets::Handle,
+    ecs::World,
+    input,
+    renderer::{DisplayConfig, SpriteRender},
+    winit::VirtualKeyCode,
+    State, StateData, StateEvent, Trans,
+};
+use log::{error, info};
+
+/// Items the player starts with, by id, along with their count.
+const STARTER_ITEMS: [(&str, u32); 2] = [("rustball", 5), ("potion", 2)];
+/// The creature the player starts with.
+const STARTER_SPECIES: &str = "ferris";
+const STARTER_LEVEL: u32 = 5;
+
+/// Title menu shown once the assets are loaded.
+/// N starts a new game and C continues the saved one.
+pub struct TitleState {
+    display_config: DisplayConfig,
+    hero_animations: Option<AnimationSet<HeroAnimationId, SpriteRender>>,
+    map: Handle<MapDefinition>,
+}
+
+impl TitleState {
+    pub fn new(
+        display_config: DisplayConfig,
+        hero_animations: AnimationSet<HeroAnimationId, SpriteRender>,
+        map: Handle<MapDefinition>,
+    ) -> Self {
+        TitleState {
+            display_config,
+            hero_animations: Some(hero_animations),
+            map,
+        }
+    }
+
+    /// Give the player what they start a new game with.
+    fn add_new_game_resources(world: &mut World) {
+        let starter = Creature::new(
+            STARTER_SPECIES,
+            STARTER_LEVEL,
+            &world.read_resource::<SpeciesDatabase>(),
+            &mut world.write_resource::<Rng>(),
+        );
+        world.add_resource(Party::new(vec![starter]).expect("the starter fits in the party"));
+        world.add_resource(Storage::default());
+        let mut inventory = Inventory::default();
+        for &(item, count) in STARTER_ITEMS.iter() {
+            inventory.add(item, count);
+        }
+        world.add_resource(inventory);
+        world.add_resource(Flags::default());
+    }
+
+    fn start<'a, 'b>(&mut self, save: Option<save::SaveData>) -> RustymonTrans<'a, 'b> {
+        Trans::Switch(Box::new(OverworldState::new(
+            self.display_config.clone(),
+            self.hero_animations.take().unwrap(),
+            self.map.clone(),
+            save,
+        )))
+    }
+}
+
+impl<'a, 'b> State<RustymonGameData<'a, 'b>, StateEvent> for TitleState {
+    fn on_start(&mut self, _data: StateData<RustymonGameData<'a, 'b>>) {
+        info!("Rustymon");
+        info!("  [N] New game");
+        if save::save_exists() {
+            info!("  [C] Continue");
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<RustymonGameData<'a, 'b>>,
+        event: StateEvent,
+    ) -> RustymonTrans<'a, 'b> {
+        let event = match &event {
+            StateEvent::Window(event) => event,
+            _ => return Trans::None,
+        };
+        if input::is_close_requested(event) {
+            return Trans::Quit;
+        }
+
+        if input::is_key_down(event, VirtualKeyCode::N) {
+            Self::add_new_game_resources(data.world);
+            self.start(None)
+        } else if input::is_key_down(event, VirtualKeyCode::C) && save::save_exists() {
+            match save::load_game() {
+                Ok(save) => self.start(Some(save)),
+                Err(error) => {
+                    let path = save::saved_game_path().unwrap_or(save::SAVE_PATH);
+                    error!("{}: {}", path, error);
+                    Trans::None
+                }
+            }
+        } else {
+            Trans::None
+        }
+    }
+
+    fn update(&mut self, data: StateData<RustymonGameData<'a, 'b>>) -> RustymonTrans<'a, 'b> {
+        data.data.update(&data.world);
+        Trans::None
+    }
+}
@@ -0,0 +1,133 @@
+use ncollide2d::bounding_volume::{BoundingVolume, AABB};
+use std::collections::HashMap;
+
+/// Default size of a grid cell, should be around the size of the common bodies.
+pub const DEFAULT_CELL_SIZE: f32 = 64.0;
+
+/// Select the pairs of bodies which might be colliding,
+/// so that precise contacts are only computed between nearby bodies.
+pub enum BroadPhase {
+    /// Test every pair of bodies. Only useful as a reference.
+    BruteForce,
+    /// Bucket bodies into a uniform grid and only test bodies sharing a cell.
+    Grid { cell_size: f32 },
+}
+
+impl Default for BroadPhase {
+    fn default() -> Self {
+        BroadPhase::Grid {
+            cell_size: DEFAULT_CELL_SIZE,
+        }
+    }
+}
+
+impl BroadPhase {
+    /// Compute the pairs of overlapping bounding boxes with at least one dynamic body.
+    /// Each pair (a, b) holds indices into `aabbs`, with a < b, and appears only once.
+    pub fn pairs(&self, aabbs: &[AABB<f32>], dynamics: &[bool]) -> Vec<(usize, usize)> {
+        let mut pairs = match self {
+            BroadPhase::BruteForce => brute_force_pairs(aabbs, dynamics),
+            BroadPhase::Grid { cell_size } => grid_pairs(*cell_size, aabbs, dynamics),
+        };
+
+        pairs.sort();
+        pairs.dedup();
+        pairs
+    }
+}
+
+fn brute_force_pairs(aabbs: &[AABB<f32>], dynamics: &[bool]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for a in 0..aabbs.len() {
+        for b in (a + 1)..aabbs.len() {
+            if (dynamics[a] || dynamics[b]) && aabbs[a].intersects(&aabbs[b]) {
+                pairs.push((a, b));
+            }
+        }
+    }
+    pairs
+}
+
+fn grid_pairs(cell_size: f32, aabbs: &[AABB<f32>], dynamics: &[bool]) -> Vec<(usize, usize)> {
+    let cells_of = |aabb: &AABB<f32>| {
+        let min_x = (aabb.mins().x / cell_size).floor() as i32;
+        let min_y = (aabb.mins().y / cell_size).floor() as i32;
+        let max_x = (aabb.maxs().x / cell_size).floor() as i32;
+        let max_y = (aabb.maxs().y / cell_size).floor() as i32;
+        (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+    };
+
+    let mut grid = HashMap::<(i32, i32), Vec<usize>>::new();
+    for (index, aabb) in aabbs.iter().enumerate() {
+        for cell in cells_of(aabb) {
+            grid.entry(cell).or_insert_with(Vec::new).push(index);
+        }
+    }
+
+    // Only dynamic bodies look for neighbours, static bodies never collide with each other.
+    let mut pairs = Vec::new();
+    for (a, aabb) in aabbs.iter().enumerate().filter(|(a, _)| dynamics[*a]) {
+        for cell in cells_of(aabb) {
+            for &b in &grid[&cell] {
+                if a != b && aabb.intersects(&aabbs[b]) {
+                    pairs.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use ncollide2d::math::Point;
+
+    fn aabb((x, y): (f32, f32), (half_width, half_height): (f32, f32)) -> AABB<f32> {
+        AABB::new(
+            Point::new(x - half_width, y - half_height),
+            Point::new(x + half_width, y + half_height),
+        )
+    }
+
+    #[test]
+    fn the_grid_finds_the_same_pairs_as_brute_force() {
+        let mut rng = Rng::new(7);
+        let mut coordinate = |min: f32, max: f32| min + rng.next_f32() * (max - min);
+        let mut aabbs = Vec::new();
+        let mut dynamics = Vec::new();
+        for index in 0..400 {
+            let center = (coordinate(-600.0, 600.0), coordinate(-600.0, 600.0));
+            // Some bodies span many cells.
+            let half_extents = if index % 10 == 0 {
+                (coordinate(64.0, 200.0), coordinate(64.0, 200.0))
+            } else {
+                (coordinate(2.0, 40.0), coordinate(2.0, 40.0))
+            };
+            aabbs.push(aabb(center, half_extents));
+            dynamics.push(coordinate(0.0, 1.0) < 0.5);
+        }
+
+        let expected = BroadPhase::BruteForce.pairs(&aabbs, &dynamics);
+        let pairs = BroadPhase::default().pairs(&aabbs, &dynamics);
+        assert_eq!(pairs, expected);
+        assert!(pairs.len() > 100);
+        assert!(pairs
+            .iter()
+            .all(|&(a, b)| a < b && (dynamics[a] || dynamics[b])));
+    }
+
+    #[test]
+    fn static_bodies_are_never_paired_together() {
+        let aabbs = [
+            aabb((0.0, 0.0), (100.0, 100.0)),
+            aabb((10.0, 10.0), (10.0, 10.0)),
+            aabb((-10.0, 0.0), (10.0, 10.0)),
+        ];
+        let dynamics = [false, false, true];
+        for broad_phase in &[BroadPhase::BruteForce, BroadPhase::default()] {
+            assert_eq!(broad_phase.pairs(&aabbs, &dynamics), [(0, 2), (1, 2)]);
+        }
+    }
+}
@@ -1,13 +1,15 @@
 use crate::components::{Active, CameraTarget};
 use amethyst::{
-    core::transform::Transform,
-    ecs::prelude::{Entities, Join, ReadStorage, System, WriteStorage},
+    core::{timing::Time, transform::Transform},
+    ecs::prelude::{Entities, Join, Read, ReadStorage, System, WriteStorage},
     renderer::Camera,
 };
 use nalgebra::base::Vector3;
 
 const MAX_TARGET_DISTANCE: f32 = 2500.0;
+/// Share of the distance to the target covered during 1/60th of a second.
 const MAX_CAMERA_LERP_FACTOR: f32 = 0.01;
+const LERP_REFERENCE_FPS: f32 = 60.0;
 
 /// Make cameras follow their target.
 pub struct CameraTargetingSystem;
@@ -19,16 +21,27 @@ impl<'a> System<'a> for CameraTargetingSystem {
         ReadStorage<'a, CameraTarget>,
         WriteStorage<'a, Transform>,
         ReadStorage<'a, Active>,
+        Read<'a, Time>,
     );
 
-    fn run(&mut self, (entities, cameras, targets, mut transforms, actives): Self::SystemData) {
+    fn run(
+        &mut self,
+        (entities, cameras, targets, mut transforms, actives, time): Self::SystemData,
+    ) {
+        // Compound the per reference frame factor so the camera speed doesn't depend on the frame rate.
+        let lerp_factor = 1.0
+            - (1.0 - MAX_CAMERA_LERP_FACTOR).powf(time.delta_seconds() * LERP_REFERENCE_FPS);
+
         for (entity, _, target, _) in (&entities, &cameras, &targets, &actives).join() {
             if actives.get(target.entity).is_none() {
                 continue;
             }
             
-            let new_cam_position =
-                compute_new_camera_position(transforms.get(entity), transforms.get(target.entity));
+            let new_cam_position = compute_new_camera_position(
+                transforms.get(entity),
+                transforms.get(target.entity),
+                lerp_factor,
+            );
             if let Some(new_cam_position) = new_cam_position {
                 if let Some(cam_trans) = transforms.get_mut(entity) {
                     cam_trans.set_x(new_cam_position.x);
@@ -42,6 +55,7 @@ impl<'a> System<'a> for CameraTargetingSystem {
 fn compute_new_camera_position(
     cam_trans: Option<&Transform>,
     target_trans: Option<&Transform>,
+    lerp_factor: f32,
 ) -> Option<Vector3<f32>> {
     if let (Some(cam_trans), Some(target_trans)) = (cam_trans, target_trans) {
         let direction = target_trans.translation() - cam_trans.translation();
@@ -50,7 +64,7 @@ fn compute_new_camera_position(
             Some(
                 cam_trans
                     .translation()
-                    .lerp(target_trans.translation(), MAX_CAMERA_LERP_FACTOR),
+                    .lerp(target_trans.translation(), lerp_factor),
             )
         } else {
             None
@@ -0,0 +1,75 @@
+use crate::{
+    components::{Active, Hero, Mob},
+    events::{CollisionEvent, CollisionEventKind},
+    states::GameState,
+    transition::{ScreenTransition, TransitionEffect},
+};
+use amethyst::{
+    ecs::{Entities, Read, ReadStorage, Resources, System, SystemData, Write},
+    shrev::{EventChannel, ReaderId},
+};
+
+/// Start a battle when the hero touches a mob chasing him.
+#[derive(Default)]
+pub struct EncounterSystem {
+    reader: Option<ReaderId<CollisionEvent>>,
+}
+
+impl<'a> System<'a> for EncounterSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventChannel<CollisionEvent>>,
+        Read<'a, GameState>,
+        Write<'a, ScreenTransition>,
+        ReadStorage<'a, Hero>,
+        ReadStorage<'a, Mob>,
+        ReadStorage<'a, Active>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, events, state, mut transition, heros, mobs, actives): Self::SystemData,
+    ) {
+        // Always read the events so they do not pile up during battles and dialogues.
+        let events = events
+            .read(self.reader.as_mut().unwrap())
+            .collect::<Vec<_>>();
+        match *state {
+            GameState::Overworld(_) | GameState::Instance(_) if !transition.is_running() => (),
+            _ => return,
+        }
+
+        for event in events {
+            if event.kind == CollisionEventKind::Stopped {
+                continue;
+            }
+
+            let (a, b) = event.entities;
+            let (hero, mob) = if heros.get(a).is_some() {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            let is_aggroed = mobs
+                .get(mob)
+                .map_or(false, |mob| mob.target == Some(hero) && !mob.resetting);
+
+            if heros.get(hero).is_some()
+                && is_aggroed
+                && actives.get(mob).is_some()
+                && entities.is_alive(mob)
+            {
+                transition.start(TransitionEffect::BattleSwirl, GameState::Battle(mob));
+                break;
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.reader = Some(
+            res.fetch_mut::<EventChannel<CollisionEvent>>()
+                .register_reader(),
+        );
+    }
+}
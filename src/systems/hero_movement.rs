@@ -1,16 +1,35 @@
+use super::physics::compute_isometry;
 use crate::{
     animations::{create_singleton_looping_set, HeroAnimationId},
-    components::{Active, Hero, Velocity},
+    components::{Active, Body, GridStep, Hero, Tilemap, Velocity},
+    resources::{MovementMode, SimulationTime},
+    transition::ScreenTransition,
 };
 use amethyst::{
     animation::{AnimationControlSet, AnimationSet},
-    ecs::prelude::{Entities, Join, Read, ReadStorage, System, WriteStorage},
+    core::transform::Transform,
+    ecs::prelude::{Entities, Entity, Join, Read, ReadStorage, System, WriteStorage},
     input::InputHandler,
     renderer::SpriteRender,
 };
-use ncollide2d::math::Vector;
+use ncollide2d::{
+    math::{Isometry, Vector},
+    query::contact,
+    shape::{Cuboid, Shape},
+};
+
+/// In world units per second.
+const HERO_SPEED: f32 = 60.0;
+
+/// In grid movement, for how long a direction must be held before the hero walks
+/// instead of only turning in place.
+const TURN_SECONDS: f32 = 0.1;
+
+/// Overlap ignored when looking for obstacles, so that a body filling its tile
+/// is not blocked by the neighbouring tiles it touches.
+const OVERLAP_TOLERANCE: f32 = 0.01;
 
-/// Move the hero according to the input.
+/// Move the hero according to the input and the movement mode of the map.
 /// If the hero has animations, also animates him according to its direction.
 pub struct HeroMovementSystem;
 
@@ -18,8 +37,14 @@ impl<'a> System<'a> for HeroMovementSystem {
     type SystemData = (
         Entities<'a>,
         Read<'a, InputHandler<String, String>>,
+        Read<'a, MovementMode>,
+        Read<'a, SimulationTime>,
+        Read<'a, ScreenTransition>,
         WriteStorage<'a, Velocity>,
         WriteStorage<'a, Hero>,
+        WriteStorage<'a, Transform>,
+        ReadStorage<'a, Body>,
+        ReadStorage<'a, Tilemap>,
         ReadStorage<'a, AnimationSet<HeroAnimationId, SpriteRender>>,
         WriteStorage<'a, AnimationControlSet<HeroAnimationId, SpriteRender>>,
         ReadStorage<'a, Active>,
@@ -27,22 +52,113 @@ impl<'a> System<'a> for HeroMovementSystem {
 
     fn run(
         &mut self,
-        (entities, input, mut velocities, mut heros, animations, mut animation_controls, actives): Self::SystemData,
+        (
+            entities,
+            input,
+            mode,
+            time,
+            transition,
+            mut velocities,
+            mut heros,
+            mut transforms,
+            bodies,
+            tilemaps,
+            animations,
+            mut animation_controls,
+            actives,
+        ): Self::SystemData,
     ) {
+        // The hero doesn't take orders while the screen is covered.
+        let (left_right_amount, up_down_amount) = if transition.is_running() {
+            (0.0, 0.0)
+        } else {
+            (
+                input.axis_value("right_left").unwrap() as f32,
+                input.axis_value("up_down").unwrap() as f32,
+            )
+        };
+
         for (entity, velocity, hero, _) in (&entities, &mut velocities, &mut heros, &actives).join()
         {
             velocity.reset();
 
-            let left_right_amount = input.axis_value("right_left").unwrap() as f32;
-            let up_down_amount = input.axis_value("up_down").unwrap() as f32;
+            // The direction the hero is moving toward, used to pick the animation.
+            let direction = match *mode {
+                MovementMode::Free => {
+                    if left_right_amount != 0.0 || up_down_amount != 0.0 {
+                        velocity.direction =
+                            Vector::new(left_right_amount, up_down_amount).normalize();
+                        velocity.speed = HERO_SPEED;
+                        hero.facing = velocity.direction;
+                    }
+                    hero.step = None;
+                    Vector::new(left_right_amount, up_down_amount)
+                }
+                MovementMode::Grid {
+                    tile_size,
+                    step_seconds,
+                } => {
+                    let position = match transforms.get(entity) {
+                        Some(t) => Vector::new(t.translation().x, t.translation().y),
+                        None => continue,
+                    };
+                    let input_direction = to_axis_direction(left_right_amount, up_down_amount);
+
+                    // Start a new step if none is in progress. A step is dropped when finished,
+                    // or when the hero was moved by something else (collision, teleport...).
+                    let step_in_progress = hero.step.map_or(false, |step| {
+                        step.position == position && step.elapsed_seconds < step_seconds
+                    });
+                    if !step_in_progress {
+                        hero.step = None;
+                        if let Some(input_direction) = input_direction {
+                            let from = snap_to_grid(position, tile_size);
+                            let to = from + input_direction * tile_size;
+                            hero.step = try_start_step(
+                                hero,
+                                input_direction,
+                                from,
+                                to,
+                                time.tick_seconds(),
+                                |to| {
+                                    is_blocked(
+                                        entity,
+                                        to,
+                                        &entities,
+                                        &transforms,
+                                        &bodies,
+                                        &tilemaps,
+                                        &actives,
+                                    )
+                                },
+                            );
+                        } else {
+                            // The next press toward the facing direction walks right away
+                            hero.turn_held_seconds = TURN_SECONDS;
+                        }
+                    }
 
-            if left_right_amount != 0.0 || up_down_amount != 0.0 {
-                velocity.direction = Vector::new(left_right_amount, up_down_amount).normalize();
-                velocity.speed = 1.0;
-            }
+                    // Progress the step in progress
+                    match hero.step.as_mut() {
+                        Some(step) => {
+                            step.elapsed_seconds += time.tick_seconds();
+                            let progress = (step.elapsed_seconds / step_seconds).min(1.0);
+                            step.position = step.from + (step.to - step.from) * progress;
+
+                            let transform = transforms.get_mut(entity).unwrap();
+                            transform.set_x(step.position.x);
+                            transform.set_y(step.position.y);
+
+                            // Kept once finished so that chained steps do not restart the animation
+                            step.to - step.from
+                        }
+                        None => Vector::new(0.0, 0.0),
+                    }
+                }
+            };
 
             if let Some(animations) = animations.get(entity) {
-                let id = compute_animation_id(left_right_amount, up_down_amount);
+                let id = compute_animation_id(direction.x, direction.y);
 
                 if hero.current_animation_id.is_none() || hero.current_animation_id.unwrap() != id {
                     let handle = animations.get(&id).unwrap();
@@ -55,6 +171,120 @@ impl<'a> System<'a> for HeroMovementSystem {
     }
 }
 
+/// Handle a direction input in grid movement.
+/// A hero not facing the input direction first turns in place, and only walks
+/// if the direction is held long enough. Return the step to start, if any.
+fn try_start_step<F>(
+    hero: &mut Hero,
+    direction: Vector<f32>,
+    from: Vector<f32>,
+    to: Vector<f32>,
+    tick_seconds: f32,
+    is_blocked: F,
+) -> Option<GridStep>
+where
+    F: Fn(Vector<f32>) -> bool,
+{
+    if hero.facing != direction {
+        hero.facing = direction;
+        hero.turn_held_seconds = 0.0;
+        return None;
+    }
+
+    hero.turn_held_seconds += tick_seconds;
+    if hero.turn_held_seconds < TURN_SECONDS {
+        return None;
+    }
+
+    if is_blocked(to) {
+        return None;
+    }
+
+    Some(GridStep {
+        from,
+        to,
+        elapsed_seconds: 0.0,
+        position: from,
+    })
+}
+
+/// Would the body of `entity`, moved to `to`, overlap a solid tile or a non sensor body.
+/// An entity without a body never collides, so it is never blocked.
+fn is_blocked(
+    entity: Entity,
+    to: Vector<f32>,
+    entities: &Entities,
+    transforms: &WriteStorage<Transform>,
+    bodies: &ReadStorage<Body>,
+    tilemaps: &ReadStorage<Tilemap>,
+    actives: &ReadStorage<Active>,
+) -> bool {
+    let (body, transform) = match (bodies.get(entity), transforms.get(entity)) {
+        (Some(body), Some(transform)) => (body, transform),
+        _ => return false,
+    };
+    let mut isometry = compute_isometry(transform);
+    isometry.translation.vector = to;
+    let shape = body.shape.as_ncollide();
+    let overlaps = |position: &Isometry<f32>, other: &dyn Shape<f32>| {
+        contact(&isometry, shape, position, other, 0.0)
+            .map_or(false, |contact| contact.depth > OVERLAP_TOLERANCE)
+    };
+
+    let aabb = shape.aabb(&isometry);
+    let tile_blocked = (tilemaps, transforms, actives)
+        .join()
+        .any(|(tilemap, transform, _)| {
+            let origin = transform.translation();
+            let range = tilemap.tiles_in(
+                (aabb.mins().x - origin.x, aabb.mins().y - origin.y),
+                (aabb.maxs().x - origin.x, aabb.maxs().y - origin.y),
+            );
+            let ((min_column, max_column), (min_row, max_row)) = match range {
+                Some(range) => range,
+                None => return false,
+            };
+            let tile_shape = Cuboid::new(Vector::new(
+                tilemap.tile_width * 0.5,
+                tilemap.tile_height * 0.5,
+            ));
+            (min_row..=max_row).any(|row| {
+                (min_column..=max_column).any(|column| {
+                    let (x, y) = tilemap.tile_center(column, row);
+                    let position =
+                        Isometry::new(Vector::new(origin.x + x, origin.y + y), nalgebra::zero());
+                    tilemap.is_solid(column, row) && overlaps(&position, &tile_shape)
+                })
+            })
+        });
+
+    tile_blocked
+        || (entities, bodies, transforms, actives)
+            .join()
+            .any(|(other, body, transform, _)| {
+                other != entity
+                    && !body.sensor
+                    && overlaps(&compute_isometry(transform), body.shape.as_ncollide())
+            })
+}
+
+/// Keep only the dominant axis of the input, as a unit vector.
+fn to_axis_direction(left_right_amount: f32, up_down_amount: f32) -> Option<Vector<f32>> {
+    if left_right_amount == 0.0 && up_down_amount == 0.0 {
+        None
+    } else if left_right_amount.abs() >= up_down_amount.abs() {
+        Some(Vector::new(left_right_amount.signum(), 0.0))
+    } else {
+        Some(Vector::new(0.0, up_down_amount.signum()))
+    }
+}
+
+/// Get the center of the tile containing a position.
+fn snap_to_grid(position: Vector<f32>, tile_size: f32) -> Vector<f32> {
+    let snap = |v: f32| ((v / tile_size).floor() + 0.5) * tile_size;
+    Vector::new(snap(position.x), snap(position.y))
+}
+
 /// Compute the current animation if from the direction of the hero.
 fn compute_animation_id(left_right_amount: f32, up_down_amount: f32) -> HeroAnimationId {
     if left_right_amount > 0.0 {
@@ -81,3 +311,154 @@ fn compute_animation_id(left_right_amount: f32, up_down_amount: f32) -> HeroAnim
         HeroAnimationId::Idle
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Dynamic, Shape};
+    use amethyst::ecs::prelude::{Builder, World};
+    use ncollide2d::shape::Ball;
+
+    const TICK: f32 = 1.0 / 60.0;
+    const TILE: f32 = 32.0;
+
+    fn step(hero: &mut Hero, direction: Vector<f32>) -> Option<GridStep> {
+        let from = Vector::new(16.0, 16.0);
+        try_start_step(hero, direction, from, from + direction * TILE, TICK, |_| {
+            false
+        })
+    }
+
+    #[test]
+    fn tapping_a_new_direction_turns_and_holding_it_walks() {
+        let right = Vector::new(1.0, 0.0);
+        let mut hero = Hero::new();
+        assert!(step(&mut hero, right).is_none());
+        assert_eq!(hero.facing, right);
+
+        let mut ticks = 1;
+        while step(&mut hero, right).is_none() {
+            ticks += 1;
+            assert!(ticks as f32 * TICK < TURN_SECONDS + TICK, "{} ticks", ticks);
+        }
+        assert!(ticks as f32 * TICK >= TURN_SECONDS);
+
+        // Once released, the facing direction walks right away.
+        hero.turn_held_seconds = TURN_SECONDS;
+        let started = step(&mut hero, right).unwrap();
+        assert_eq!(started.to, Vector::new(48.0, 16.0));
+        assert_eq!(started.position, started.from);
+    }
+
+    #[test]
+    fn blocked_steps_do_not_start() {
+        let right = Vector::new(1.0, 0.0);
+        let mut hero = Hero::new();
+        hero.facing = right;
+        hero.turn_held_seconds = TURN_SECONDS;
+        let from = Vector::new(16.0, 16.0);
+        let step = try_start_step(&mut hero, right, from, from + right * TILE, TICK, |to| {
+            to == Vector::new(48.0, 16.0)
+        });
+        assert!(step.is_none());
+        assert_eq!(hero.facing, right);
+    }
+
+    fn add_body(world: &mut World, (x, y): (f32, f32), shape: Shape, sensor: bool) -> Entity {
+        let mut transform = Transform::default();
+        transform.set_xyz(x, y, 0.0);
+        world
+            .create_entity()
+            .with(transform)
+            .with(Body {
+                shape,
+                dynamic: Dynamic::Static,
+                sensor,
+            })
+            .with(Active)
+            .build()
+    }
+
+    fn circle(radius: f32) -> Shape {
+        Shape::Circle {
+            shape: Ball::new(radius),
+        }
+    }
+
+    /// Is the hero, standing in the bottom left tile, blocked when stepping to a tile.
+    fn blocked(world: &mut World, hero: Entity, (x, y): (f32, f32)) -> bool {
+        world.exec(
+            |(entities, transforms, bodies, tilemaps, actives): (
+                Entities,
+                WriteStorage<Transform>,
+                ReadStorage<Body>,
+                ReadStorage<Tilemap>,
+                ReadStorage<Active>,
+            )| {
+                is_blocked(
+                    hero,
+                    Vector::new(x, y),
+                    &entities,
+                    &transforms,
+                    &bodies,
+                    &tilemaps,
+                    &actives,
+                )
+            },
+        )
+    }
+
+    #[test]
+    fn any_overlap_with_the_destination_blocks_the_step() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Body>();
+        world.register::<Tilemap>();
+        world.register::<Active>();
+
+        // A 3x2 grid whose top right tile is solid.
+        let mut solid = vec![false; 6];
+        solid[2] = true;
+        world
+            .create_entity()
+            .with(Tilemap {
+                width: 3,
+                height: 2,
+                tile_width: TILE,
+                tile_height: TILE,
+                layers: Vec::new(),
+                solid,
+            })
+            .with(Transform::default())
+            .with(Active)
+            .build();
+        // The hero fills its tile.
+        let hero = add_body(&mut world, (16.0, 16.0), circle(16.0), false);
+        // A small mob on the edge of the tile above the hero, away from its center.
+        add_body(&mut world, (28.0, 60.0), circle(4.0), false);
+        // A sensor in the middle tile.
+        add_body(&mut world, (48.0, 16.0), circle(8.0), true);
+
+        assert!(blocked(&mut world, hero, (16.0, 48.0)));
+        assert!(blocked(&mut world, hero, (80.0, 48.0)));
+        // Sensors and solid tiles only touching the destination don't block.
+        assert!(!blocked(&mut world, hero, (48.0, 16.0)));
+        assert!(!blocked(&mut world, hero, (80.0, 16.0)));
+    }
+
+    #[test]
+    fn positions_snap_to_the_center_of_their_tile() {
+        assert_eq!(
+            snap_to_grid(Vector::new(5.0, 40.0), TILE),
+            Vector::new(16.0, 48.0)
+        );
+        assert_eq!(
+            snap_to_grid(Vector::new(32.0, 31.9), TILE),
+            Vector::new(48.0, 16.0)
+        );
+        assert_eq!(
+            snap_to_grid(Vector::new(-1.0, -33.0), TILE),
+            Vector::new(-16.0, -48.0)
+        );
+    }
+}
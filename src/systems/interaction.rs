@@ -0,0 +1,143 @@
+use crate::{
+    components::{Active, Hero, Interactable, Interaction, NpcBattle},
+    creatures::{Creature, SpeciesDatabase},
+    resources::Flags,
+    rng::Rng,
+    states::GameState,
+    transition::{ScreenTransition, TransitionEffect},
+};
+use amethyst::{
+    core::transform::Transform,
+    ecs::{Entities, Join, Read, ReadStorage, System, Write, WriteStorage},
+    input::InputHandler,
+    renderer::SpriteRender,
+};
+use ncollide2d::{
+    math::{Isometry, Point, Vector},
+    query::PointQuery,
+};
+
+/// Resolve the interaction of the entity the hero uses.
+/// When several trigger zones contain the hero, the nearest entity in front of him is used.
+#[derive(Default)]
+pub struct InteractionSystem {
+    /// Was "use" down during the previous tick, interactions only happen when it is pressed.
+    use_was_down: bool,
+}
+
+impl<'a> System<'a> for InteractionSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, GameState>,
+        Write<'a, ScreenTransition>,
+        Read<'a, Flags>,
+        Write<'a, Rng>,
+        Read<'a, SpeciesDatabase>,
+        Read<'a, InputHandler<String, String>>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Interactable>,
+        WriteStorage<'a, SpriteRender>,
+        WriteStorage<'a, NpcBattle>,
+        ReadStorage<'a, Hero>,
+        ReadStorage<'a, Active>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut state,
+            mut transition,
+            flags,
+            mut rng,
+            species,
+            input,
+            transforms,
+            interactables,
+            mut sprites,
+            mut npc_battles,
+            heros,
+            actives,
+        ): Self::SystemData,
+    ) {
+        let use_down = input.action_is_down("use").unwrap_or(false);
+        let pressed = use_down && !self.use_was_down;
+        self.use_was_down = use_down;
+        match *state {
+            GameState::Overworld(_) | GameState::Instance(_)
+                if pressed && !transition.is_running() => {}
+            _ => return,
+        }
+
+        let (hero_position, facing) = match (&transforms, &heros, &actives).join().next() {
+            Some((transform, hero, _)) => {
+                let t = transform.translation();
+                (Point::new(t.x, t.y), hero.facing)
+            }
+            None => return,
+        };
+
+        let target = (&entities, &transforms, &interactables, &actives)
+            .join()
+            .filter_map(|(entity, transform, interactable, _)| {
+                let t = transform.translation();
+                let position = Isometry::new(Vector::new(t.x, t.y), nalgebra::zero());
+                if interactable
+                    .trigger_zone
+                    .contains_point(&position, &hero_position)
+                {
+                    Some((
+                        entity,
+                        interactable,
+                        Vector::new(t.x, t.y) - hero_position.coords,
+                    ))
+                } else {
+                    None
+                }
+            })
+            .filter(|(_, _, offset)| offset.dot(&facing) >= 0.0)
+            .min_by(|(_, _, a), (_, _, b)| {
+                a.norm_squared().partial_cmp(&b.norm_squared()).unwrap()
+            });
+        let (entity, interactable, offset) = match target {
+            Some(target) => target,
+            None => return,
+        };
+
+        match &interactable.interaction {
+            Interaction::EnterInstance(instance) => {
+                transition.start(instance.transition, state.entering(instance.clone()))
+            }
+            Interaction::Talk { facing_sprites, .. } => {
+                if let (Some(facing), Some(sprite)) = (facing_sprites, sprites.get_mut(entity)) {
+                    sprite.sprite_number = facing.facing(-offset.x, -offset.y);
+                }
+                *state = GameState::Dialogue(entity);
+            }
+            // Chests and signs speak through the dialogue state too.
+            Interaction::PickUp { .. } | Interaction::ReadSign(_) => {
+                *state = GameState::Dialogue(entity);
+            }
+            Interaction::StartBattle {
+                species: species_id,
+                level,
+                won_flag,
+            } => {
+                if flags.is_set(won_flag) {
+                    return;
+                }
+                let opponent = entities.create();
+                npc_battles
+                    .insert(
+                        opponent,
+                        NpcBattle {
+                            creature: Creature::new(species_id, *level, &species, &mut rng),
+                            won_flag: Some(won_flag.clone()),
+                        },
+                    )
+                    .unwrap();
+                transition.start(TransitionEffect::BattleSwirl, GameState::Battle(opponent));
+            }
+        }
+    }
+}
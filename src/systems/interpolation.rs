@@ -0,0 +1,68 @@
+use crate::{components::Interpolation, resources::SimulationTime};
+use amethyst::{
+    core::transform::Transform,
+    ecs::prelude::{Join, Read, ReadStorage, System, WriteStorage},
+};
+use ncollide2d::math::Vector;
+
+/// First system of a simulation tick.
+/// Restore the simulated position of interpolated entities.
+pub struct SimulationBeginSystem;
+
+impl<'a> System<'a> for SimulationBeginSystem {
+    type SystemData = (WriteStorage<'a, Transform>, WriteStorage<'a, Interpolation>);
+
+    fn run(&mut self, (mut transforms, mut interpolations): Self::SystemData) {
+        for (transform, interpolation) in (&mut transforms, &mut interpolations).join() {
+            interpolation.sync(get_position(transform));
+            interpolation.previous = interpolation.current;
+            interpolation.rendered = Some(interpolation.current);
+            transform.set_x(interpolation.current.x);
+            transform.set_y(interpolation.current.y);
+        }
+    }
+}
+
+/// Last system of a simulation tick.
+/// Record the simulated position of interpolated entities.
+pub struct SimulationEndSystem;
+
+impl<'a> System<'a> for SimulationEndSystem {
+    type SystemData = (ReadStorage<'a, Transform>, WriteStorage<'a, Interpolation>);
+
+    fn run(&mut self, (transforms, mut interpolations): Self::SystemData) {
+        for (transform, interpolation) in (&transforms, &mut interpolations).join() {
+            interpolation.current = get_position(transform);
+            interpolation.rendered = Some(interpolation.current);
+        }
+    }
+}
+
+/// Place interpolated entities between their last two simulated positions before rendering.
+pub struct InterpolationSystem;
+
+impl<'a> System<'a> for InterpolationSystem {
+    type SystemData = (
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Interpolation>,
+        Read<'a, SimulationTime>,
+    );
+
+    fn run(&mut self, (mut transforms, mut interpolations, time): Self::SystemData) {
+        let alpha = time.alpha();
+        for (transform, interpolation) in (&mut transforms, &mut interpolations).join() {
+            interpolation.sync(get_position(transform));
+
+            let position = interpolation.previous.lerp(&interpolation.current, alpha);
+            transform.set_x(position.x);
+            transform.set_y(position.y);
+            interpolation.rendered = Some(position);
+        }
+    }
+}
+
+/// Get the position as a Vector from a `Tranform`.
+fn get_position(t: &Transform) -> Vector<f32> {
+    let translation = t.translation();
+    Vector::new(translation.x, translation.y)
+}
@@ -0,0 +1,127 @@
+use crate::{
+    components::{Active, Layer},
+    resources::ActiveLayers,
+};
+use amethyst::{
+    ecs::prelude::{Entities, Join, Read, ReadStorage, System, WriteStorage},
+    renderer::Hidden,
+};
+
+/// Activate and show the entities of the active layers, deactivate and hide the others.
+/// Both components are checked on every run, so entities spawned active or hidden
+/// into a layer match it.
+pub struct LayerActivationSystem;
+
+impl<'a> System<'a> for LayerActivationSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, ActiveLayers>,
+        ReadStorage<'a, Layer>,
+        WriteStorage<'a, Active>,
+        WriteStorage<'a, Hidden>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, active_layers, layers, mut actives, mut hiddens): Self::SystemData,
+    ) {
+        for (entity, layer) in (&entities, &layers).join() {
+            let active = active_layers.contains(layer.id);
+            if active != actives.contains(entity) {
+                if active {
+                    actives.insert(entity, Active).unwrap();
+                } else {
+                    actives.remove(entity);
+                }
+            }
+            if active == hiddens.contains(entity) {
+                if active {
+                    hiddens.remove(entity);
+                } else {
+                    hiddens.insert(entity, Hidden).unwrap();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::LayerId;
+    use amethyst::ecs::prelude::{Builder, RunNow, World};
+
+    #[test]
+    fn only_the_entities_of_active_layers_are_active_and_shown() {
+        let mut world = World::new();
+        world.register::<Layer>();
+        world.register::<Active>();
+        world.register::<Hidden>();
+        let mut active_layers = ActiveLayers::default();
+        active_layers.set(&[LayerId::HERO, LayerId::instance(1)]);
+        world.add_resource(active_layers);
+
+        let hero = world
+            .create_entity()
+            .with(Layer::new(LayerId::HERO))
+            .build();
+        let overworld = world
+            .create_entity()
+            .with(Layer::new(LayerId::OVERWORLD))
+            .with(Active)
+            .build();
+        let instance = world
+            .create_entity()
+            .with(Layer::new(LayerId::instance(1)))
+            .with(Hidden)
+            .build();
+        let no_layer = world.create_entity().with(Active).build();
+
+        let is_shown = |world: &World, entity| {
+            world.read_storage::<Active>().contains(entity)
+                && !world.read_storage::<Hidden>().contains(entity)
+        };
+        LayerActivationSystem.run_now(&world.res);
+        assert!(is_shown(&world, hero));
+        assert!(!is_shown(&world, overworld));
+        assert!(world.read_storage::<Hidden>().contains(overworld));
+        assert!(is_shown(&world, instance));
+        assert!(is_shown(&world, no_layer));
+
+        world
+            .write_resource::<ActiveLayers>()
+            .set(&[LayerId::BATTLE]);
+        LayerActivationSystem.run_now(&world.res);
+        assert!(!is_shown(&world, hero));
+        assert!(!is_shown(&world, instance));
+        assert!(is_shown(&world, no_layer));
+    }
+
+    #[test]
+    fn entities_spawned_hidden_into_an_active_layer_are_shown() {
+        let mut world = World::new();
+        world.register::<Layer>();
+        world.register::<Active>();
+        world.register::<Hidden>();
+        let mut active_layers = ActiveLayers::default();
+        active_layers.set(&[LayerId::OVERWORLD]);
+        world.add_resource(active_layers);
+
+        let hidden = world
+            .create_entity()
+            .with(Layer::new(LayerId::OVERWORLD))
+            .with(Active)
+            .with(Hidden)
+            .build();
+        let shown = world
+            .create_entity()
+            .with(Layer::new(LayerId::BATTLE))
+            .build();
+
+        LayerActivationSystem.run_now(&world.res);
+        assert!(world.read_storage::<Active>().contains(hidden));
+        assert!(!world.read_storage::<Hidden>().contains(hidden));
+        assert!(!world.read_storage::<Active>().contains(shown));
+        assert!(world.read_storage::<Hidden>().contains(shown));
+    }
+}
@@ -1,11 +1,15 @@
-use crate::components::{Active, Hero, Mob, Velocity};
+use crate::{
+    components::{Active, Hero, Mob, Velocity},
+    resources::SimulationTime,
+};
 use amethyst::{
     core::transform::Transform,
-    ecs::{Entities, Join, ReadStorage, System, WriteStorage},
+    ecs::{Entities, Join, Read, ReadStorage, System, WriteStorage},
 };
 use ncollide2d::math::Vector;
 
-const MOB_SPEED: f32 = 0.8;
+/// In world units per second.
+const MOB_SPEED: f32 = 48.0;
 
 /// Acquire close target.
 pub struct MobTargetSystem;
@@ -49,9 +53,11 @@ impl<'a> System<'a> for MobMovementSystem {
         ReadStorage<'a, Transform>,
         WriteStorage<'a, Velocity>,
         ReadStorage<'a, Active>,
+        Read<'a, SimulationTime>,
     );
 
-    fn run(&mut self, (mut mobs, transforms, mut velocities, actives): Self::SystemData) {
+    fn run(&mut self, (mut mobs, transforms, mut velocities, actives, time): Self::SystemData) {
+        let delta = time.tick_seconds();
         for (mob, transform, velocity, _) in
             (&mut mobs, &transforms, &mut velocities, &actives).join()
         {
@@ -79,10 +85,11 @@ impl<'a> System<'a> for MobMovementSystem {
                 mob.resetting = true;
             }
 
-            // If resetting, go toward spawn and stop when close
+            // If resetting, go toward spawn and stop when close.
+            // Slow down on the last step so the spawn is not overshot.
             if mob.resetting && squared_distance > 1.0 {
                 velocity.direction = direction.normalize();
-                velocity.speed = MOB_SPEED;
+                velocity.speed = MOB_SPEED.min(squared_distance.sqrt() / delta);
             } else if mob.resetting {
                 mob.resetting = false;
             }
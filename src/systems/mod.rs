@@ -1,13 +1,25 @@
+mod broad_phase;
 mod camera_targeting;
+mod encounter;
 mod hero_movement;
+mod interaction;
+mod interpolation;
+mod layer;
 mod mob;
 mod physics;
-mod portal;
+mod tilemap;
+mod transition;
 
 pub use self::{
+    broad_phase::BroadPhase,
     camera_targeting::CameraTargetingSystem,
+    encounter::EncounterSystem,
     hero_movement::HeroMovementSystem,
+    interaction::InteractionSystem,
+    interpolation::{InterpolationSystem, SimulationBeginSystem, SimulationEndSystem},
+    layer::LayerActivationSystem,
     mob::{MobMovementSystem, MobTargetSystem},
     physics::{PhysicsSystem, MovementSystem},
-    portal::PortalTriggerSystem,
+    tilemap::TilemapRenderSystem,
+    transition::TransitionSystem,
 };
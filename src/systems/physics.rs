@@ -1,19 +1,33 @@
 use amethyst::{
     core::transform::Transform,
-    ecs::prelude::{Entities, Join, Read, ReadStorage, System, WriteStorage},
+    ecs::{
+        prelude::{Entities, Entity, Join, Read, ReadStorage, System, Write, WriteStorage},
+        world::EntitiesRes,
+    },
+    shrev::EventChannel,
 };
 
 use ncollide2d::{
+    bounding_volume::AABB,
     math::{Isometry, Vector},
     query::{contact, Contact},
+    shape::Cuboid,
 };
 
 use crate::{
-    components::{Body, CollisionMarker, Dynamic, Shape, Velocity, Active},
-    resources::WorldBounds,
+    components::{Body, CollisionMarker, Dynamic, Tilemap, Velocity, Active},
+    events::{CollisionEvent, CollisionEventKind},
+    resources::{SimulationTime, WorldBounds},
 };
 
-/// Move entities according to their velocity.
+use std::collections::HashSet;
+
+use super::broad_phase::BroadPhase;
+
+/// Number of detection and resolution passes per physics step.
+const SOLVER_ITERATIONS: usize = 4;
+
+/// Move entities according to their velocity during a simulation tick.
 /// Also make sure that entity is kept in world boundaries if any.
 pub struct MovementSystem;
 
@@ -23,13 +37,15 @@ impl<'a> System<'a> for MovementSystem {
         ReadStorage<'a, Velocity>,
         ReadStorage<'a, Active>,
         Option<Read<'a, WorldBounds>>,
+        Read<'a, SimulationTime>,
     );
 
-    fn run(&mut self, (mut transforms, velocities, actives, bounds): Self::SystemData) {
+    fn run(&mut self, (mut transforms, velocities, actives, bounds, time): Self::SystemData) {
+        let delta = time.tick_seconds();
         for (transform, _, velocity) in (&mut transforms, &actives, &velocities).join() {
             let translation = transform.translation();
-            let mut x = translation.x + velocity.direction.x * velocity.speed;
-            let mut y = translation.y + velocity.direction.y * velocity.speed;
+            let mut x = translation.x + velocity.direction.x * velocity.speed * delta;
+            let mut y = translation.y + velocity.direction.y * velocity.speed * delta;
 
             if let Some(bounds) = &bounds {
                 x = x.min(bounds.right).max(bounds.left);
@@ -43,8 +59,23 @@ impl<'a> System<'a> for MovementSystem {
 }
 
 /// Simple physics computation system. Handle collision detection and resolution.
+/// Publish a `CollisionEvent` for each pair of touching bodies.
 /// This is a first draft which might be removed in favor of ncollide simulation.
-pub struct PhysicsSystem;
+#[derive(Default)]
+pub struct PhysicsSystem {
+    broad_phase: BroadPhase,
+    /// Pairs of entities touching during the previous frame, the lowest id first.
+    contacts: HashSet<(Entity, Entity)>,
+}
+
+impl PhysicsSystem {
+    pub fn new(broad_phase: BroadPhase) -> Self {
+        PhysicsSystem {
+            broad_phase,
+            contacts: HashSet::new(),
+        }
+    }
+}
 
 impl<'a> System<'a> for PhysicsSystem {
     type SystemData = (
@@ -52,40 +83,193 @@ impl<'a> System<'a> for PhysicsSystem {
         WriteStorage<'a, Transform>,
         ReadStorage<'a, Body>,
         WriteStorage<'a, CollisionMarker>,
+        ReadStorage<'a, Tilemap>,
         ReadStorage<'a, Active>,
+        Write<'a, EventChannel<CollisionEvent>>,
     );
 
-    fn run(&mut self, (entities, mut transforms, bodies, mut collisions, actives): Self::SystemData) {
-        // Detect collisions and mark dynamics colliding entities
-        for (entity_a, transform_a, body_a, _) in (&entities, &transforms, &bodies, &actives).join() {
-            for (entity_b, transform_b, body_b, _) in (&entities, &transforms, &bodies, &actives).join() {
-                if entity_a == entity_b {
+    fn run(
+        &mut self,
+        (entities, mut transforms, bodies, mut collisions, tilemaps, actives, mut events): Self::SystemData,
+    ) {
+        let mut contacts = HashSet::new();
+
+        // Resolving a contact might create a new one, so detect and resolve several times.
+        for _ in 0..SOLVER_ITERATIONS {
+            self.detect_body_collisions(
+                &entities,
+                &transforms,
+                &bodies,
+                &actives,
+                &mut collisions,
+                &mut contacts,
+            );
+            detect_tile_collisions(&entities, &transforms, &bodies, &tilemaps, &actives, &mut collisions);
+
+            if (&collisions).join().next().is_none() {
+                break;
+            }
+
+            // Resolve all collisions
+            for (transform, collision, _) in (&mut transforms, &collisions, &actives).join() {
+                let penetration = collision.penetration();
+                transform.translate_x(penetration.x);
+                transform.translate_y(penetration.y);
+            }
+
+            // Remove all collision markers
+            collisions.clear();
+        }
+
+        self.publish_events(contacts, &mut events);
+    }
+}
+
+impl PhysicsSystem {
+    /// Detect collisions between nearby bodies and mark dynamics colliding entities.
+    /// Two colliding dynamic bodies are pushed away from each other by half the penetration each.
+    /// Sensors are never pushed nor push other bodies.
+    /// Every touching pair is added to `contacts`.
+    fn detect_body_collisions<'a>(
+        &self,
+        entities: &EntitiesRes,
+        transforms: &WriteStorage<'a, Transform>,
+        bodies: &ReadStorage<'a, Body>,
+        actives: &ReadStorage<'a, Active>,
+        collisions: &mut WriteStorage<'a, CollisionMarker>,
+        contacts: &mut HashSet<(Entity, Entity)>,
+    ) {
+        let candidates = (entities, transforms, bodies, actives)
+            .join()
+            .map(|(entity, transform, body, _)| (entity, transform, body))
+            .collect::<Vec<_>>();
+        let aabbs = candidates
+            .iter()
+            .map(|(_, transform, body)| compute_aabb(transform, body))
+            .collect::<Vec<_>>();
+        let dynamics = candidates
+            .iter()
+            .map(|(_, _, body)| Dynamic::Dynamic == body.dynamic)
+            .collect::<Vec<_>>();
+
+        for (a, b) in self.broad_phase.pairs(&aabbs, &dynamics) {
+            let (entity_a, transform_a, body_a) = candidates[a];
+            let (entity_b, transform_b, body_b) = candidates[b];
+
+            let contact = compute_contact(transform_a, body_a, transform_b, body_b);
+
+            if let Some(contact) = contact {
+                contacts.insert((entity_a.min(entity_b), entity_a.max(entity_b)));
+                if body_a.sensor || body_b.sensor {
                     continue;
                 }
 
-                let contact = compute_contact(transform_a, body_a, transform_b, body_b);
+                let penetration = contact.normal.unwrap() * contact.depth;
+                let penetration = if dynamics[a] && dynamics[b] {
+                    penetration * 0.5
+                } else {
+                    penetration
+                };
 
-                if let Some(contact) = contact {
-                    if Dynamic::Dynamic == body_a.dynamic {
-                        collisions
-                            .insert(
-                                entity_a,
-                                CollisionMarker::new(contact.normal.unwrap() * -contact.depth),
-                            )
-                            .unwrap();
-                    }
+                if dynamics[a] {
+                    mark_collision(collisions, entity_a, -penetration);
+                }
+                if dynamics[b] {
+                    mark_collision(collisions, entity_b, penetration);
                 }
             }
         }
+    }
+
+    /// Publish the events of this frame's contacts and remember them for the next frame.
+    fn publish_events(
+        &mut self,
+        contacts: HashSet<(Entity, Entity)>,
+        events: &mut EventChannel<CollisionEvent>,
+    ) {
+        let stopped = self
+            .contacts
+            .difference(&contacts)
+            .map(|&entities| CollisionEvent {
+                kind: CollisionEventKind::Stopped,
+                entities,
+            });
+        let current = contacts.iter().map(|&entities| CollisionEvent {
+            kind: if self.contacts.contains(&entities) {
+                CollisionEventKind::Ongoing
+            } else {
+                CollisionEventKind::Started
+            },
+            entities,
+        });
+        events.iter_write(stopped.chain(current).collect::<Vec<_>>());
+
+        self.contacts = contacts;
+    }
+}
+
+/// Detect collisions of dynamic bodies, sensors excepted, against solid tiles.
+fn detect_tile_collisions<'a>(
+    entities: &EntitiesRes,
+    transforms: &WriteStorage<'a, Transform>,
+    bodies: &ReadStorage<'a, Body>,
+    tilemaps: &ReadStorage<'a, Tilemap>,
+    actives: &ReadStorage<'a, Active>,
+    collisions: &mut WriteStorage<'a, CollisionMarker>,
+) {
+    for (entity, transform, body, _) in (entities, transforms, bodies, actives).join() {
+        if Dynamic::Dynamic != body.dynamic || body.sensor {
+            continue;
+        }
+
+        let aabb = compute_aabb(transform, body);
+        for (tilemap, tilemap_transform, _) in (tilemaps, transforms, actives).join() {
+            let origin = tilemap_transform.translation();
+            let range = tilemap.tiles_in(
+                (aabb.mins().x - origin.x, aabb.mins().y - origin.y),
+                (aabb.maxs().x - origin.x, aabb.maxs().y - origin.y),
+            );
+            let ((min_column, max_column), (min_row, max_row)) = match range {
+                Some(range) => range,
+                None => continue,
+            };
+
+            let tile_shape = Cuboid::new(Vector::new(
+                tilemap.tile_width * 0.5,
+                tilemap.tile_height * 0.5,
+            ));
+            for row in min_row..=max_row {
+                for column in min_column..=max_column {
+                    if !tilemap.is_solid(column, row) {
+                        continue;
+                    }
+
+                    let (x, y) = tilemap.tile_center(column, row);
+                    let tile_position =
+                        Isometry::new(Vector::new(origin.x + x, origin.y + y), nalgebra::zero());
+                    let contact = compute_tile_contact(transform, body, &tile_position, &tile_shape);
 
-        // Resolve all collisions
-        for (transform, collision, _) in (&mut transforms, &collisions, &actives).join() {
-            transform.translate_x(collision.penetration.x);
-            transform.translate_y(collision.penetration.y);
+                    if let Some(contact) = contact {
+                        mark_collision(collisions, entity, contact.normal.unwrap() * -contact.depth);
+                    }
+                }
+            }
         }
+    }
+}
 
-        // Remove all collision markers
-        collisions.clear();
+/// Add a penetration to the collision marker of an entity, creating the marker if needed.
+fn mark_collision(
+    collisions: &mut WriteStorage<CollisionMarker>,
+    entity: Entity,
+    penetration: Vector<f32>,
+) {
+    if let Some(collision) = collisions.get_mut(entity) {
+        collision.add(penetration);
+    } else {
+        collisions
+            .insert(entity, CollisionMarker::new(penetration))
+            .unwrap();
     }
 }
 
@@ -97,27 +281,214 @@ fn compute_contact(
     transform_b: &Transform,
     body_b: &Body,
 ) -> Option<Contact<f32>> {
-    let pos_a = Isometry::new(
-        Vector::new(transform_a.translation().x, transform_a.translation().y),
-        nalgebra::zero(),
-    );
-    let pos_b = Isometry::new(
-        Vector::new(transform_b.translation().x, transform_b.translation().y),
-        nalgebra::zero(),
-    );
+    contact(
+        &compute_isometry(transform_a),
+        body_a.shape.as_ncollide(),
+        &compute_isometry(transform_b),
+        body_b.shape.as_ncollide(),
+        0.0,
+    )
+}
+
+/// Compute the distance to separate an entity from a tile.
+/// Return None if not colliding.
+fn compute_tile_contact(
+    transform: &Transform,
+    body: &Body,
+    tile_position: &Isometry<f32>,
+    tile_shape: &Cuboid<f32>,
+) -> Option<Contact<f32>> {
+    contact(
+        &compute_isometry(transform),
+        body.shape.as_ncollide(),
+        tile_position,
+        tile_shape,
+        0.0,
+    )
+}
+
+/// Compute the axis aligned bounding box of a body.
+fn compute_aabb(transform: &Transform, body: &Body) -> AABB<f32> {
+    body.shape.as_ncollide().aabb(&compute_isometry(transform))
+}
+
+/// Compute the 2D position and orientation of an entity.
+/// Only the rotation around the z axis is kept.
+pub fn compute_isometry(transform: &Transform) -> Isometry<f32> {
+    let translation = transform.translation();
+    let (_, _, angle) = transform.rotation().euler_angles();
+    Isometry::new(Vector::new(translation.x, translation.y), angle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Shape;
+    use amethyst::{
+        ecs::prelude::{Builder, RunNow, World},
+        shrev::ReaderId,
+    };
+    use ncollide2d::shape::Ball;
+
+    fn physics_world() -> World {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Body>();
+        world.register::<CollisionMarker>();
+        world.register::<Tilemap>();
+        world.register::<Active>();
+        world.add_resource(EventChannel::<CollisionEvent>::new());
+        world
+    }
+
+    fn add_body(world: &mut World, (x, y): (f32, f32), shape: Shape, dynamic: Dynamic) -> Entity {
+        let mut transform = Transform::default();
+        transform.set_xyz(x, y, 0.0);
+        world
+            .create_entity()
+            .with(transform)
+            .with(Body {
+                shape,
+                dynamic,
+                sensor: false,
+            })
+            .with(Active)
+            .build()
+    }
 
-    match (&body_a.shape, &body_b.shape) {
-        (Shape::Circle { shape: shape_a }, Shape::Circle { shape: shape_b }) => {
-            contact(&pos_a, shape_a, &pos_b, shape_b, 0.0)
+    fn circle(radius: f32) -> Shape {
+        Shape::Circle {
+            shape: Ball::new(radius),
         }
-        (Shape::Box { shape: shape_a }, Shape::Box { shape: shape_b }) => {
-            contact(&pos_a, shape_a, &pos_b, shape_b, 0.0)
+    }
+
+    fn wall(half_width: f32, half_height: f32) -> Shape {
+        Shape::Box {
+            shape: Cuboid::new(Vector::new(half_width, half_height)),
         }
-        (Shape::Circle { shape: shape_a }, Shape::Box { shape: shape_b }) => {
-            contact(&pos_a, shape_a, &pos_b, shape_b, 0.0)
+    }
+
+    fn position(world: &World, entity: Entity) -> (f32, f32) {
+        let transforms = world.read_storage::<Transform>();
+        let translation = transforms.get(entity).unwrap().translation();
+        (translation.x, translation.y)
+    }
+
+    #[test]
+    fn a_hero_wedged_into_a_corner_is_pushed_out_of_both_walls() {
+        let mut world = physics_world();
+        // Walls along the x = 0 and y = 0 lines, overlapping at the corner.
+        add_body(&mut world, (-50.0, 0.0), wall(50.0, 100.0), Dynamic::Static);
+        add_body(&mut world, (0.0, -50.0), wall(100.0, 50.0), Dynamic::Static);
+        let hero = add_body(&mut world, (5.0, 6.0), circle(8.0), Dynamic::Dynamic);
+
+        PhysicsSystem::default().run_now(&world.res);
+
+        // Touching both walls, within the precision of the contacts.
+        let (x, y) = position(&world, hero);
+        assert!((x - 8.0).abs() < 0.01, "x = {}", x);
+        assert!((y - 8.0).abs() < 0.01, "y = {}", y);
+    }
+
+    #[test]
+    fn a_sandwiched_hero_neither_tunnels_nor_jitters() {
+        let mut world = physics_world();
+        let left = add_body(&mut world, (-14.0, 0.0), circle(8.0), Dynamic::Dynamic);
+        let hero = add_body(&mut world, (0.0, 0.0), circle(8.0), Dynamic::Dynamic);
+        let right = add_body(&mut world, (13.0, 0.0), circle(8.0), Dynamic::Dynamic);
+        let mut physics = PhysicsSystem::default();
+
+        // The mobs keep walking into the hero from both sides.
+        let mut previous = 0.0;
+        for tick in 0..20 {
+            {
+                let mut transforms = world.write_storage::<Transform>();
+                transforms.get_mut(left).unwrap().translate_x(2.0);
+                transforms.get_mut(right).unwrap().translate_x(-2.0);
+            }
+            physics.run_now(&world.res);
+
+            let (left_x, _) = position(&world, left);
+            let (hero_x, hero_y) = position(&world, hero);
+            let (right_x, _) = position(&world, right);
+            assert!(left_x < hero_x && hero_x < right_x, "tick {}", tick);
+            assert!(hero_x.abs() < 1.0 && hero_y.abs() < 1e-3, "tick {}", tick);
+            if tick > 10 {
+                assert!((hero_x - previous).abs() < 0.05, "tick {}", tick);
+            }
+            previous = hero_x;
         }
-        (Shape::Box { shape: shape_a }, Shape::Circle { shape: shape_b }) => {
-            contact(&pos_a, shape_a, &pos_b, shape_b, 0.0)
+    }
+
+    fn read_events(world: &World, reader: &mut ReaderId<CollisionEvent>) -> Vec<CollisionEvent> {
+        world
+            .read_resource::<EventChannel<CollisionEvent>>()
+            .read(reader)
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn contacts_are_published_when_they_start_go_on_and_stop() {
+        let mut world = physics_world();
+        let wall = add_body(&mut world, (20.0, 0.0), wall(8.0, 8.0), Dynamic::Static);
+        let hero = add_body(&mut world, (0.0, 0.0), circle(8.0), Dynamic::Dynamic);
+        let mut reader = world
+            .write_resource::<EventChannel<CollisionEvent>>()
+            .register_reader();
+        let mut physics = PhysicsSystem::default();
+
+        // The hero walks into the wall twice, then walks away.
+        let mut kinds = Vec::new();
+        for &x in &[6.0, 6.0, -20.0] {
+            world
+                .write_storage::<Transform>()
+                .get_mut(hero)
+                .unwrap()
+                .set_x(x);
+            physics.run_now(&world.res);
+            let events = read_events(&world, &mut reader);
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].entities, (wall.min(hero), wall.max(hero)));
+            kinds.push(events[0].kind);
         }
+        assert_eq!(
+            kinds,
+            [
+                CollisionEventKind::Started,
+                CollisionEventKind::Ongoing,
+                CollisionEventKind::Stopped,
+            ]
+        );
+
+        physics.run_now(&world.res);
+        assert!(read_events(&world, &mut reader).is_empty());
+    }
+
+    #[test]
+    fn sensors_are_reported_but_never_push() {
+        let mut world = physics_world();
+        let sensor = add_body(&mut world, (0.0, 0.0), wall(16.0, 16.0), Dynamic::Dynamic);
+        world
+            .write_storage::<Body>()
+            .get_mut(sensor)
+            .unwrap()
+            .sensor = true;
+        let hero = add_body(&mut world, (4.0, 2.0), circle(8.0), Dynamic::Dynamic);
+        let mut reader = world
+            .write_resource::<EventChannel<CollisionEvent>>()
+            .register_reader();
+
+        PhysicsSystem::default().run_now(&world.res);
+
+        assert_eq!(position(&world, sensor), (0.0, 0.0));
+        assert_eq!(position(&world, hero), (4.0, 2.0));
+        assert_eq!(
+            read_events(&world, &mut reader),
+            [CollisionEvent {
+                kind: CollisionEventKind::Started,
+                entities: (sensor.min(hero), sensor.max(hero)),
+            }]
+        );
     }
 }
@@ -0,0 +1,127 @@
+use crate::components::{Active, Tilemap};
+use amethyst::{
+    core::transform::Transform,
+    ecs::prelude::{Entities, Entity, Join, ReadStorage, System, WriteStorage},
+    renderer::{Camera, SpriteRender},
+};
+use std::collections::{HashMap, HashSet};
+
+/// Depth of the first tile layer. Following layers are drawn slightly above.
+const TILE_Z_POS: f32 = -0.5;
+const TILE_LAYER_Z_STEP: f32 = 0.01;
+
+/// (tilemap, layer, column, row)
+type TileKey = (Entity, usize, usize, usize);
+
+/// Render tilemaps.
+/// Only the tiles seen by an active camera get a sprite entity,
+/// so the number of rendered entities does not depend on the size of the maps.
+#[derive(Default)]
+pub struct TilemapRenderSystem {
+    tiles: HashMap<TileKey, Entity>,
+}
+
+impl<'a> System<'a> for TilemapRenderSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Tilemap>,
+        ReadStorage<'a, Camera>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, SpriteRender>,
+        ReadStorage<'a, Active>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, tilemaps, cameras, mut transforms, mut sprites, actives): Self::SystemData,
+    ) {
+        let views = (&cameras, &transforms, &actives)
+            .join()
+            .map(|(camera, transform, _)| {
+                // Orthographic projections scale x and y by 1 / half extent.
+                let half_width = 1.0 / camera.proj[(0, 0)];
+                let half_height = 1.0 / camera.proj[(1, 1)];
+                let t = transform.translation();
+                (
+                    (t.x - half_width, t.y - half_height),
+                    (t.x + half_width, t.y + half_height),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut visible = HashSet::new();
+        for (entity, tilemap, transform, _) in (&entities, &tilemaps, &transforms, &actives).join()
+        {
+            let origin = transform.translation();
+            for (min, max) in &views {
+                let range = tilemap.tiles_in(
+                    (min.0 - origin.x, min.1 - origin.y),
+                    (max.0 - origin.x, max.1 - origin.y),
+                );
+                let ((min_column, max_column), (min_row, max_row)) = match range {
+                    Some(range) => range,
+                    None => continue,
+                };
+
+                for (layer_index, layer) in tilemap.layers.iter().enumerate() {
+                    for row in min_row..=max_row {
+                        for column in min_column..=max_column {
+                            if layer.tiles[row * tilemap.width + column].is_some() {
+                                visible.insert((entity, layer_index, column, row));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Remove tiles which are not visible anymore
+        let hidden = self
+            .tiles
+            .keys()
+            .filter(|key| !visible.contains(key))
+            .cloned()
+            .collect::<Vec<TileKey>>();
+        for key in hidden {
+            if let Some(tile) = self.tiles.remove(&key) {
+                entities.delete(tile).unwrap();
+            }
+        }
+
+        // Add tiles which became visible
+        for key in visible {
+            if self.tiles.contains_key(&key) {
+                continue;
+            }
+
+            let (tilemap_entity, layer_index, column, row) = key;
+            let tilemap = tilemaps.get(tilemap_entity).unwrap();
+            let layer = &tilemap.layers[layer_index];
+            let sprite_number = layer.tiles[row * tilemap.width + column].unwrap();
+
+            let mut transform = Transform::default();
+            {
+                let origin = transforms.get(tilemap_entity).unwrap().translation();
+                let (x, y) = tilemap.tile_center(column, row);
+                transform.set_xyz(
+                    origin.x + x,
+                    origin.y + y,
+                    TILE_Z_POS + layer_index as f32 * TILE_LAYER_Z_STEP,
+                );
+            }
+
+            let tile = entities.create();
+            transforms.insert(tile, transform).unwrap();
+            sprites
+                .insert(
+                    tile,
+                    SpriteRender {
+                        sprite_sheet: layer.sprite_sheet.clone(),
+                        sprite_number,
+                    },
+                )
+                .unwrap();
+            self.tiles.insert(key, tile);
+        }
+    }
+}
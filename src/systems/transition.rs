@@ -0,0 +1,69 @@
+use crate::{components::TransitionOverlay, states::GameState, transition::ScreenTransition};
+use amethyst::{
+    core::{timing::Time, transform::Transform},
+    ecs::prelude::{Entities, Join, Read, ReadStorage, System, Write, WriteStorage},
+    renderer::{Camera, Hidden, TextureHandle},
+};
+
+/// Just in front of the camera, above every sprite.
+const OVERLAY_Z_POS: f32 = 0.5;
+
+/// Play the screen transition, switch the game state once the screen is covered
+/// and draw the transition overlay over the camera's view.
+pub struct TransitionSystem;
+
+impl<'a> System<'a> for TransitionSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Write<'a, ScreenTransition>,
+        Write<'a, GameState>,
+        ReadStorage<'a, Camera>,
+        ReadStorage<'a, TransitionOverlay>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, TextureHandle>,
+        WriteStorage<'a, Hidden>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            time,
+            mut transition,
+            mut state,
+            cameras,
+            overlays,
+            mut transforms,
+            mut textures,
+            mut hiddens,
+        ): Self::SystemData,
+    ) {
+        if let Some(target) = transition.advance(time.delta_seconds()) {
+            *state = target;
+        }
+
+        let camera_position = (&cameras, &transforms)
+            .join()
+            .next()
+            .map(|(_, transform)| (transform.translation().x, transform.translation().y));
+
+        for (entity, overlay) in (&entities, &overlays).join() {
+            let frame = transition
+                .coverage()
+                .and_then(|(effect, coverage)| overlay.frames.frame(effect, coverage));
+            match (frame, camera_position) {
+                (Some(frame), Some((x, y))) => {
+                    textures.insert(entity, frame.clone()).unwrap();
+                    hiddens.remove(entity);
+                    if let Some(transform) = transforms.get_mut(entity) {
+                        transform.set_xyz(x, y, OVERLAY_Z_POS);
+                    }
+                }
+                _ => {
+                    hiddens.insert(entity, Hidden).unwrap();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Screen transitions played when switching between states.
+//!
+//! A transition first covers the screen, then sets the `GameState` it was started with, so the
+//! states switch while the screen is covered, and finally uncovers the screen of the new state.
+//! The hero's input is ignored while a transition runs.
+//!
+//! Effects are drawn by an overlay entity following the camera. Its frames are generated
+//! when the game starts, one texture per step of each effect.
+
+use crate::states::GameState;
+use amethyst::{
+    assets::{AssetStorage, Loader},
+    ecs::prelude::World,
+    renderer::{Texture, TextureData, TextureHandle, TextureMetadata},
+};
+use serde_derive::*;
+use std::{collections::HashMap, f32::consts::PI};
+
+/// Size in pixels of the overlay textures, they are stretched over the screen.
+pub const OVERLAY_SIZE: u16 = 128;
+/// Number of frames drawn while covering or uncovering the screen.
+const FRAME_COUNT: usize = 16;
+/// Number of arms of the battle swirl.
+const SWIRL_ARMS: f32 = 4.0;
+
+/// How the screen is covered and uncovered.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TransitionEffect {
+    /// Fade to black.
+    Fade,
+    /// A black ring closing on the center of the screen.
+    Iris,
+    /// Black arms spinning around the center of the screen.
+    BattleSwirl,
+}
+
+impl Default for TransitionEffect {
+    fn default() -> Self {
+        TransitionEffect::Fade
+    }
+}
+
+impl TransitionEffect {
+    pub const ALL: [TransitionEffect; 3] = [
+        TransitionEffect::Fade,
+        TransitionEffect::Iris,
+        TransitionEffect::BattleSwirl,
+    ];
+
+    /// Duration of each half of the transition, in seconds.
+    pub fn half_duration(self) -> f32 {
+        match self {
+            TransitionEffect::Fade => 0.3,
+            TransitionEffect::Iris => 0.4,
+            TransitionEffect::BattleSwirl => 0.6,
+        }
+    }
+
+    /// Opacity of the overlay at a point of the screen, from 0 to 1.
+    /// `x` and `y` go from -1 to 1, `coverage` from 0 (nothing covered) to 1 (black screen).
+    pub fn opacity(self, coverage: f32, x: f32, y: f32) -> f32 {
+        match self {
+            TransitionEffect::Fade => coverage,
+            TransitionEffect::Iris => {
+                // The corners are the farthest points from the center.
+                let radius = (1.0 - coverage) * 2.0_f32.sqrt();
+                if coverage >= 1.0 || (x * x + y * y).sqrt() > radius {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            TransitionEffect::BattleSwirl => {
+                let turn = (y.atan2(x) + PI) / (2.0 * PI);
+                let distance = (x * x + y * y).sqrt();
+                // Bend the arms so they spiral around the center.
+                let arm = (turn + distance * 0.25) * SWIRL_ARMS;
+                if coverage >= 1.0 || arm - arm.floor() < coverage {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Phase {
+    Idle,
+    /// Covering the screen, the state is set once it is covered.
+    Covering {
+        elapsed: f32,
+        target: GameState,
+    },
+    Uncovering {
+        elapsed: f32,
+    },
+}
+
+/// The transition being played, if any.
+#[derive(Clone, Debug)]
+pub struct ScreenTransition {
+    effect: TransitionEffect,
+    phase: Phase,
+}
+
+impl Default for ScreenTransition {
+    fn default() -> Self {
+        ScreenTransition {
+            effect: TransitionEffect::default(),
+            phase: Phase::Idle,
+        }
+    }
+}
+
+impl ScreenTransition {
+    /// Cover the screen with an effect, then switch to the target state.
+    /// Does nothing if a transition is already running.
+    pub fn start(&mut self, effect: TransitionEffect, target: GameState) {
+        if self.is_running() {
+            return;
+        }
+        self.effect = effect;
+        self.phase = Phase::Covering {
+            elapsed: 0.0,
+            target,
+        };
+    }
+
+    pub fn is_running(&self) -> bool {
+        match self.phase {
+            Phase::Idle => false,
+            _ => true,
+        }
+    }
+
+    /// The effect being played and how much of the screen it covers, from 0 to 1.
+    pub fn coverage(&self) -> Option<(TransitionEffect, f32)> {
+        let duration = self.effect.half_duration();
+        match self.phase {
+            Phase::Idle => None,
+            Phase::Covering { elapsed, .. } => Some((self.effect, (elapsed / duration).min(1.0))),
+            Phase::Uncovering { elapsed } => {
+                Some((self.effect, (1.0 - elapsed / duration).max(0.0)))
+            }
+        }
+    }
+
+    /// Play the transition for some time.
+    /// Returns the state to switch to when the screen has just been covered.
+    pub fn advance(&mut self, delta_seconds: f32) -> Option<GameState> {
+        let duration = self.effect.half_duration();
+        match &mut self.phase {
+            Phase::Idle => None,
+            Phase::Covering { elapsed, .. } if *elapsed < duration => {
+                *elapsed += delta_seconds;
+                None
+            }
+            Phase::Covering { target, .. } => {
+                let target = target.clone();
+                self.phase = Phase::Uncovering { elapsed: 0.0 };
+                Some(target)
+            }
+            Phase::Uncovering { elapsed } => {
+                *elapsed += delta_seconds;
+                if *elapsed >= duration {
+                    self.phase = Phase::Idle;
+                }
+                None
+            }
+        }
+    }
+}
+
+/// The textures drawn by the overlay, for each effect.
+/// The first frame covers a bit of the screen, the last one covers it entirely.
+#[derive(Clone, Debug)]
+pub struct OverlayFrames {
+    frames: HashMap<TransitionEffect, Vec<TextureHandle>>,
+}
+
+impl OverlayFrames {
+    /// Generate the frames of every effect.
+    pub fn build(world: &World) -> Self {
+        let loader = world.read_resource::<Loader>();
+        let storage = world.read_resource::<AssetStorage<Texture>>();
+        let frames = TransitionEffect::ALL
+            .iter()
+            .map(|&effect| {
+                let frames = (1..=FRAME_COUNT)
+                    .map(|frame| {
+                        let coverage = frame as f32 / FRAME_COUNT as f32;
+                        let data = TextureData::U8(
+                            overlay_pixels(effect, coverage),
+                            TextureMetadata::srgb_scale().with_size(OVERLAY_SIZE, OVERLAY_SIZE),
+                        );
+                        loader.load_from_data(data, (), &storage)
+                    })
+                    .collect();
+                (effect, frames)
+            })
+            .collect();
+        OverlayFrames { frames }
+    }
+
+    /// The frame to draw for an effect covering part of the screen, None if nothing is covered.
+    pub fn frame(&self, effect: TransitionEffect, coverage: f32) -> Option<&TextureHandle> {
+        let index = (coverage * FRAME_COUNT as f32).ceil() as usize;
+        if index == 0 {
+            return None;
+        }
+        self.frames[&effect].get(index.min(FRAME_COUNT) - 1)
+    }
+}
+
+/// Black RGBA pixels whose opacity draws an effect.
+fn overlay_pixels(effect: TransitionEffect, coverage: f32) -> Vec<u8> {
+    let size = usize::from(OVERLAY_SIZE);
+    let mut pixels = Vec::with_capacity(size * size * 4);
+    for row in 0..size {
+        for column in 0..size {
+            // Sample the center of the pixel.
+            let x = (column as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let y = (row as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let alpha = effect.opacity(coverage, x, y);
+            pixels.extend_from_slice(&[0, 0, 0, (alpha * 255.0).round() as u8]);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(transition: &mut ScreenTransition, seconds: f32) -> Option<GameState> {
+        let mut switched = None;
+        let mut elapsed = 0.0;
+        while elapsed < seconds {
+            if let Some(state) = transition.advance(0.01) {
+                switched = Some(state);
+            }
+            elapsed += 0.01;
+        }
+        switched
+    }
+
+    #[test]
+    fn the_state_switches_once_the_screen_is_covered() {
+        let mut transition = ScreenTransition::default();
+        transition.start(TransitionEffect::Iris, GameState::Overworld((1.0, 2.0)));
+        assert!(transition.is_running());
+
+        assert!(play(&mut transition, 0.3).is_none());
+        match play(&mut transition, 0.2) {
+            Some(GameState::Overworld(position)) => assert_eq!(position, (1.0, 2.0)),
+            other => panic!("unexpected switch: {:?}", other),
+        }
+        assert!(transition.is_running());
+
+        assert!(play(&mut transition, 0.5).is_none());
+        assert!(!transition.is_running());
+        assert!(transition.coverage().is_none());
+    }
+
+    #[test]
+    fn a_running_transition_is_not_restarted() {
+        let mut transition = ScreenTransition::default();
+        transition.start(TransitionEffect::Fade, GameState::Overworld((1.0, 0.0)));
+        transition.start(
+            TransitionEffect::BattleSwirl,
+            GameState::Overworld((2.0, 0.0)),
+        );
+        assert_eq!(transition.coverage().unwrap().0, TransitionEffect::Fade);
+        match play(&mut transition, 0.4) {
+            Some(GameState::Overworld(position)) => assert_eq!(position, (1.0, 0.0)),
+            other => panic!("unexpected switch: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn effects_cover_the_whole_screen() {
+        for &effect in &TransitionEffect::ALL {
+            for &(x, y) in &[(0.0, 0.0), (1.0, 1.0), (-0.99, 0.3), (0.5, -0.7)] {
+                assert_eq!(effect.opacity(1.0, x, y), 1.0, "{:?}", effect);
+                assert_eq!(effect.opacity(0.0, x, y), 0.0, "{:?}", effect);
+            }
+        }
+    }
+}